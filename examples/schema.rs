@@ -3,7 +3,10 @@ use std::fs::create_dir_all;
 
 use cosmwasm_schema::{export_schema, remove_schemas, schema_for};
 
-use did_contract::msg::{ControllerResponse, ExecuteMsg, InstantiateMsg, QueryMsg};
+use did_contract::msg::{
+    ControllerResponse, DidChangedExecuteMsg, ExecuteMsg, InstantiateMsg, MigrateMsg, QueryMsg,
+    SudoMsg,
+};
 
 fn main() {
     let mut out_dir = current_dir().unwrap();
@@ -14,5 +17,8 @@ fn main() {
     export_schema(&schema_for!(InstantiateMsg), &out_dir);
     export_schema(&schema_for!(ExecuteMsg), &out_dir);
     export_schema(&schema_for!(QueryMsg), &out_dir);
+    export_schema(&schema_for!(MigrateMsg), &out_dir);
+    export_schema(&schema_for!(SudoMsg), &out_dir);
     export_schema(&schema_for!(ControllerResponse), &out_dir);
+    export_schema(&schema_for!(DidChangedExecuteMsg), &out_dir);
 }
@@ -0,0 +1,113 @@
+use cosmwasm_std::{
+    to_vec, Addr, Binary, ContractResult, QuerierWrapper, QueryRequest, SystemResult,
+};
+use std::convert::TryFrom;
+
+use crate::msg::KeyType;
+
+pub const ACCOUNT_QUERY_PATH: &str = "/cosmos.auth.v1beta1.Query/Account";
+
+/// public key of the account from the auth module, none when the account is unknown, has not
+/// signed a transaction yet or its key type has no verification method
+pub fn query_account_key(querier: &QuerierWrapper, address: &Addr) -> Option<(KeyType, Vec<u8>)> {
+    let request: QueryRequest<cosmwasm_std::Empty> = QueryRequest::Stargate {
+        path: ACCOUNT_QUERY_PATH.to_string(),
+        // QueryAccountRequest { address }
+        data: Binary::from(encode_field(1, address.as_bytes())),
+    };
+    let response = match querier.raw_query(&to_vec(&request).ok()?) {
+        SystemResult::Ok(ContractResult::Ok(response)) => response,
+        _ => return None,
+    };
+
+    // QueryAccountResponse { account: Any }
+    let account = field(&response, 1)?;
+    let account = base_account(
+        &String::from_utf8(field(account, 1)?.to_vec()).ok()?,
+        field(account, 2)?,
+    )?;
+    // BaseAccount { address, pub_key: Any, .. }
+    let public_key = field(account, 2)?;
+    let key_type = match String::from_utf8(field(public_key, 1)?.to_vec())
+        .ok()?
+        .as_str()
+    {
+        "/cosmos.crypto.secp256k1.PubKey" => KeyType::Secp256k1,
+        "/cosmos.crypto.ed25519.PubKey" => KeyType::Ed25519,
+        _ => return None,
+    };
+    // PubKey { key }
+    let key = field(field(public_key, 2)?, 1)?;
+    Some((key_type, key.to_vec()))
+}
+
+/// BaseAccount of base and vesting accounts
+fn base_account<'a>(type_url: &str, value: &'a [u8]) -> Option<&'a [u8]> {
+    match type_url {
+        "/cosmos.auth.v1beta1.BaseAccount" => Some(value),
+        // vesting accounts embed a BaseVestingAccount, which embeds the BaseAccount
+        "/cosmos.vesting.v1beta1.ContinuousVestingAccount"
+        | "/cosmos.vesting.v1beta1.DelayedVestingAccount"
+        | "/cosmos.vesting.v1beta1.PeriodicVestingAccount"
+        | "/cosmos.vesting.v1beta1.PermanentLockedAccount" => field(field(value, 1)?, 1),
+        _ => None,
+    }
+}
+
+/// protobuf length delimited field
+pub fn encode_field(number: u32, value: &[u8]) -> Vec<u8> {
+    let mut encoded = encode_varint((number << 3 | 2) as u64);
+    encoded.extend(encode_varint(value.len() as u64));
+    encoded.extend_from_slice(value);
+    encoded
+}
+
+/// first length delimited field with the number, other wire types are skipped
+fn field(message: &[u8], number: u32) -> Option<&[u8]> {
+    let mut rest = message;
+    while !rest.is_empty() {
+        let (tag, len) = decode_varint(rest)?;
+        rest = &rest[len..];
+        let value_len = match tag & 7 {
+            0 => decode_varint(rest)?.1,
+            1 => 8,
+            2 => {
+                let (value_len, len) = decode_varint(rest)?;
+                rest = &rest[len..];
+                usize::try_from(value_len).ok()?
+            }
+            5 => 4,
+            _ => return None,
+        };
+        if value_len > rest.len() {
+            return None;
+        }
+        if tag >> 3 == number as u64 && tag & 7 == 2 {
+            return Some(&rest[..value_len]);
+        }
+        rest = &rest[value_len..];
+    }
+    None
+}
+
+fn encode_varint(mut value: u64) -> Vec<u8> {
+    let mut encoded = vec![];
+    while value >= 0x80 {
+        encoded.push(value as u8 | 0x80);
+        value >>= 7;
+    }
+    encoded.push(value as u8);
+    encoded
+}
+
+/// value and number of bytes read
+fn decode_varint(bytes: &[u8]) -> Option<(u64, usize)> {
+    let mut value = 0u64;
+    for (i, byte) in bytes.iter().take(10).enumerate() {
+        value |= ((byte & 0x7f) as u64) << (7 * i);
+        if byte & 0x80 == 0 {
+            return Some((value, i + 1));
+        }
+    }
+    None
+}
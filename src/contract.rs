@@ -1,58 +1,486 @@
 #[cfg(not(feature = "library"))]
 use cosmwasm_std::{
-    entry_point, to_binary, Addr, Binary, Deps, DepsMut, Env, MessageInfo, Response, StdResult,
+    entry_point, to_binary, Addr, BankMsg, Binary, BlockInfo, Coin, Deps, DepsMut, Env, Event,
+    MessageInfo, Order, Reply, Response, StdError, StdResult, Storage, SubMsg, SubMsgResult,
     Timestamp,
 };
-use cw2::set_contract_version;
+use cw2::{get_contract_version, set_contract_version};
+use cw_storage_plus::Bound;
+use std::collections::BTreeMap;
 
+use crate::auth::query_account_key;
+use crate::did::{format_did, parse_did_url};
 use crate::error::ContractError;
-use crate::helper::only_controller;
+use crate::helper::{
+    add_coin, alias_message, bump_version, check_commitment, check_recovery_commitment,
+    collect_fee, hook_submsgs, ignoring_hook_submsgs, index_attribute, keep_lease, load_controller,
+    lock_deposit, normalize_dids, only_active, only_controller, only_guardian,
+    only_key_relationship, only_owner, only_unleased, only_unpaused, only_version, refund_msg,
+    register_identifier, update_stats, valid_fragment, validate_fees, validate_service,
+    DryRunStorage, HOOK_REPLY_ID,
+};
+use crate::keys::{
+    decode_key_attribute, decode_public_key, signed_by_account, to_multibase, verify_signature,
+};
 use crate::msg::{
-    AttributeResponse, ChangedResponse, ControllerResponse, ExecuteMsg, InstantiateMsg, QueryMsg,
-    ValidToResponse,
+    AliasInfo, AliasesResponse, AllIdentifiersResponse, AttributeItem, AttributeResponse,
+    AttributesResponse, CanExecuteResponse, ChangedResponse, CommitmentResponse, ConfigResponse,
+    ControllerItem, ControllerPolicy, ControllerPolicyResponse, ControllerResponse,
+    ControllersResponse, DeactivatedResponse, Deadline, Delay, DelegateInfo, DepositResponse,
+    DepositsResponse, DereferenceError, DereferenceResponse, DereferencedContent, DidChange,
+    DidDocument, DidDocumentMetadata, DocumentAttribute, DocumentItem, ExecuteMsg, FailedCheck,
+    FeesResponse, GuardiansResponse, HookFailure, HookResponse, HooksResponse, IdentifierInfo,
+    IdentifiersResponse, InstantiateMsg, KeyType, KeysForRelationshipResponse, Lease,
+    LeaseResponse, MetadataResponse, MigrateMsg, Operation, OperationFee, PendingChangeInfo,
+    PendingChangeResponse, PublicKey, QueryMsg, RecoveryInfo, RecoveryResponse, Relationship,
+    RelationshipTarget, RelationshipValidity, ResolveManyResponse, ResolveResponse,
+    ScheduledChange, Service, ServiceEndpoint, ServicesResponse, SignatureMethod, StatsResponse,
+    SudoMsg, ValidToItem, ValidToResponse, ValidTosResponse, VerificationMethodInfo,
+    VerifySignatureResponse, VersionResponse,
+};
+use crate::state::{
+    controllers, leases, Attribute, Backfill, Config, GuardianSet, PendingChange, Recovery,
+    VerificationMethod, ALIASES, ATTRIBUTES, ATTRIBUTE_INDEX, BACKFILL, CHANGED, COLLECTED_FEES,
+    COMMITMENTS, CONFIG, CONTROLLER_FRAGMENT, CONTROLLER_POLICIES, DEACTIVATED, DEFAULT_MAX_BATCH,
+    DEFAULT_MAX_SERVICES, DELEGATES, DEPOSITS, GLOBAL_HOOKS, GUARDIANS, HOOKS, IDENTIFIERS,
+    KEY_RELATIONSHIPS, MAX_HOOKS, NONCE, PENDING_CHANGES, RECOVERIES, SERVICES, STATS, UPDATED,
+    VALIDITIES, VERIFICATION_METHODS, VERSIONS,
 };
-use crate::state::{Attribute, ATTRIBUTES, CHANGED, CONTROLLERS, VALIDITIES};
 
 // version info for migration info
 const CONTRACT_NAME: &str = "crates.io:did-contract";
 const CONTRACT_VERSION: &str = env!("CARGO_PKG_VERSION");
 
+// first version with the controller index and the stats
+const INDEXED_VERSION: &str = "0.2.0";
+
+// settings for pagination
+const DEFAULT_LIMIT: u32 = 10;
+const MAX_LIMIT: u32 = 30;
+const DEFAULT_BACKFILL_LIMIT: u32 = 1000;
+
 #[cfg_attr(not(feature = "library"), entry_point)]
 pub fn instantiate(
     deps: DepsMut,
-    _env: Env,
-    _info: MessageInfo,
-    _msg: InstantiateMsg,
+    env: Env,
+    info: MessageInfo,
+    msg: InstantiateMsg,
 ) -> Result<Response, ContractError> {
     set_contract_version(deps.storage, CONTRACT_NAME, CONTRACT_VERSION)?;
+    validate_fees(&msg.fees)?;
+
+    let config = Config {
+        owner: msg.owner.unwrap_or(info.sender),
+        pending_owner: None,
+        paused: false,
+        paused_operations: vec![],
+        fees: msg.fees,
+        deposit_per_byte: msg.deposit_per_byte,
+        indexed_prefixes: vec![],
+        max_batch: None,
+        max_services: None,
+        network: Some(msg.network.unwrap_or(env.block.chain_id)),
+    };
+    CONFIG.save(deps.storage, &config)?;
+
+    Ok(Response::new().add_attribute("owner", config.owner))
+}
+
+#[cfg_attr(not(feature = "library"), entry_point)]
+pub fn migrate(deps: DepsMut, env: Env, msg: MigrateMsg) -> Result<Response, ContractError> {
+    let version = get_contract_version(deps.storage)?;
+    if version.contract != CONTRACT_NAME {
+        return Err(ContractError::CannotMigrate {
+            previous_contract: version.contract,
+        });
+    }
+    let backfill_limit = msg.limit.unwrap_or(DEFAULT_BACKFILL_LIMIT) as usize;
+
+    // deployments before the config get one without fees and deposits.
+    // attribute values stored before deposits keep no DEPOSITS entry and are refunded nothing.
+    if CONFIG.may_load(deps.storage)?.is_none() {
+        let config = Config {
+            owner: msg.owner.ok_or(ContractError::MissingOwner {})?,
+            pending_owner: None,
+            paused: false,
+            paused_operations: vec![],
+            fees: vec![],
+            deposit_per_byte: None,
+            indexed_prefixes: vec![],
+            max_batch: None,
+            max_services: None,
+            network: None,
+        };
+        CONFIG.save(deps.storage, &config)?;
+    }
+    // DIDs carry the chain id from now on
+    CONFIG.update(deps.storage, |mut config| -> StdResult<_> {
+        config
+            .network
+            .get_or_insert_with(|| env.block.chain_id.clone());
+        Ok(config)
+    })?;
+
+    // state stored before the indexes is backfilled over as many migrations as it takes
+    if predates(&version.version, INDEXED_VERSION) {
+        BACKFILL.save(deps.storage, &Backfill::Controllers { start_after: None })?;
+    }
+    set_contract_version(deps.storage, CONTRACT_NAME, CONTRACT_VERSION)?;
+    let done = run_backfill(deps.storage, &env.block, backfill_limit)?;
+
+    Ok(Response::new()
+        .add_attribute("migrateMsg", "migrate")
+        .add_attribute("previousVersion", version.version)
+        .add_attribute("version", CONTRACT_VERSION)
+        .add_attribute("backfillDone", done.to_string()))
+}
+
+/// compares dotted version numbers, parts that are no number count as 0
+fn predates(version: &str, since: &str) -> bool {
+    let parse = |version: &str| -> Vec<u64> {
+        version
+            .split('.')
+            .map(|part| part.parse().unwrap_or(0))
+            .collect()
+    };
+    parse(version) < parse(since)
+}
 
-    Ok(Response::new())
+/// runs the backfill steps for up to limit entries and returns whether it is done.
+/// identifiers are registered at the migration blocks, controller changes before the stats
+/// are not counted.
+fn run_backfill(storage: &mut dyn Storage, block: &BlockInfo, limit: usize) -> StdResult<bool> {
+    let mut remaining = limit;
+    while let Some(step) = BACKFILL.may_load(storage)? {
+        if remaining == 0 {
+            return Ok(false);
+        }
+        let next = match step {
+            Backfill::Controllers { start_after } => {
+                // saving again indexes the controllers stored before the controller index
+                let stored = controllers()
+                    .range(
+                        storage,
+                        start_after.as_ref().map(Bound::exclusive),
+                        None,
+                        Order::Ascending,
+                    )
+                    .take(remaining)
+                    .collect::<StdResult<Vec<(Addr, Addr)>>>()?;
+                remaining -= stored.len();
+                let last = stored.last().map(|(identifier, _)| identifier.clone());
+                for (identifier, controller) in stored {
+                    controllers().save(storage, &identifier, &controller)?;
+                }
+                match last {
+                    Some(last) if remaining == 0 => Backfill::Controllers {
+                        start_after: Some(last),
+                    },
+                    _ => Backfill::Identifiers { start_after: None },
+                }
+            }
+            Backfill::Identifiers { start_after } => {
+                let changed = CHANGED
+                    .keys(
+                        storage,
+                        start_after.as_ref().map(Bound::exclusive),
+                        None,
+                        Order::Ascending,
+                    )
+                    .take(remaining)
+                    .collect::<StdResult<Vec<Addr>>>()?;
+                remaining -= changed.len();
+                for identifier in &changed {
+                    register_identifier(storage, block, identifier)?;
+                }
+                match changed.last() {
+                    Some(last) if remaining == 0 => Backfill::Identifiers {
+                        start_after: Some(last.clone()),
+                    },
+                    _ => Backfill::Attributes {
+                        start_after: None,
+                        attributes: 0,
+                        active_values: 0,
+                    },
+                }
+            }
+            Backfill::Attributes {
+                start_after,
+                mut attributes,
+                mut active_values,
+            } => {
+                let start = start_after
+                    .as_ref()
+                    .map(|(identifier, name)| Bound::exclusive((identifier, name.as_str())));
+                let stored = ATTRIBUTES
+                    .range(storage, start, None, Order::Ascending)
+                    .take(remaining)
+                    .collect::<StdResult<Vec<_>>>()?;
+                remaining -= stored.len();
+                let last = stored.last().map(|(key, _)| key.clone());
+                for ((identifier, name), attribute) in stored {
+                    attributes += 1;
+                    for value in attribute.values {
+                        let valid_to =
+                            VALIDITIES.may_load(storage, (&identifier, &name, &value))?;
+                        if valid_to != Some(Timestamp::from_seconds(0)) {
+                            active_values += 1;
+                        }
+                    }
+                }
+                match last {
+                    Some(last) if remaining == 0 => Backfill::Attributes {
+                        start_after: Some(last),
+                        attributes,
+                        active_values,
+                    },
+                    _ => {
+                        update_stats(storage, |stats| {
+                            stats.attributes = attributes;
+                            stats.active_values = active_values;
+                        })?;
+                        Backfill::Deactivated {
+                            start_after: None,
+                            deactivated: 0,
+                        }
+                    }
+                }
+            }
+            Backfill::Deactivated {
+                start_after,
+                mut deactivated,
+            } => {
+                let stored = DEACTIVATED
+                    .keys(
+                        storage,
+                        start_after.as_ref().map(Bound::exclusive),
+                        None,
+                        Order::Ascending,
+                    )
+                    .take(remaining)
+                    .collect::<StdResult<Vec<Addr>>>()?;
+                remaining -= stored.len();
+                deactivated += stored.len() as u64;
+                match stored.last() {
+                    Some(last) if remaining == 0 => Backfill::Deactivated {
+                        start_after: Some(last.clone()),
+                        deactivated,
+                    },
+                    _ => {
+                        update_stats(storage, |stats| stats.deactivated = deactivated)?;
+                        BACKFILL.remove(storage);
+                        continue;
+                    }
+                }
+            }
+        };
+        BACKFILL.save(storage, &next)?;
+    }
+    Ok(true)
 }
 
 #[cfg_attr(not(feature = "library"), entry_point)]
 pub fn execute(
     deps: DepsMut,
     env: Env,
-    info: MessageInfo,
-    msg: ExecuteMsg,
+    mut info: MessageInfo,
+    mut msg: ExecuteMsg,
 ) -> Result<Response, ContractError> {
-    match msg {
+    if BACKFILL.may_load(deps.storage)?.is_some() {
+        return Err(ContractError::MigrationInProgress {});
+    }
+    let network = CONFIG.load(deps.storage)?.network;
+    normalize_dids(network.as_deref(), msg.dids_mut())?;
+    let did = msg
+        .identifier()
+        .map(|identifier| format_did(network.as_deref(), identifier));
+    if let Some(operation) = msg.operation() {
+        only_unpaused(deps.storage, operation)?;
+        info.funds = collect_fee(deps.storage, operation, info.funds)?;
+    }
+    if let Some(identifier) = msg.identifier() {
+        // deactivated identifiers are still pruned to refund their deposits
+        if !matches!(msg, ExecuteMsg::PruneAttribute { .. }) {
+            only_active(deps.storage, identifier)?;
+        }
+        if let Some(expected_version) = msg.expected_version() {
+            only_version(deps.storage, identifier, expected_version)?;
+        }
+    }
+    // funds left after the fee are refunded, set attribute locks its deposit from them first
+    let refund = match msg {
+        ExecuteMsg::SetAttribute { .. } => None,
+        _ => refund_msg(&info.sender, info.funds.clone()),
+    };
+
+    let response = match msg {
         ExecuteMsg::ChangeController {
             identifier,
             new_controller,
-        } => try_change_controller(deps, env, info, identifier, new_controller),
+            next_hash,
+            lease,
+            ..
+        } => try_change_controller(
+            deps,
+            env,
+            info,
+            identifier,
+            new_controller,
+            next_hash,
+            lease,
+        ),
+        ExecuteMsg::CommitNextController {
+            identifier,
+            next_hash,
+            ..
+        } => try_commit_next_controller(deps, env, info, identifier, next_hash),
         ExecuteMsg::SetAttribute {
             identifier,
             name,
             value,
             validity,
+            ..
         } => try_set_attribute(deps, env, info, identifier, name, value, validity),
         ExecuteMsg::RevokeAttribute {
             identifier,
             name,
             value,
+            ..
         } => try_revoke_attribute(deps, env, info, identifier, name, value),
-    }
+        ExecuteMsg::PruneAttribute {
+            identifier, name, ..
+        } => try_prune_attribute(deps, env, info, identifier, name),
+        ExecuteMsg::AddVerificationMethod {
+            identifier,
+            id,
+            key_type,
+            controller,
+            public_key,
+            relationships,
+            validity,
+            ..
+        } => try_add_verification_method(
+            deps,
+            env,
+            info,
+            identifier,
+            id,
+            key_type,
+            controller,
+            public_key,
+            relationships,
+            validity,
+        ),
+        ExecuteMsg::RemoveVerificationMethod { identifier, id, .. } => {
+            try_remove_verification_method(deps, env, info, identifier, id)
+        }
+        ExecuteMsg::AddService {
+            identifier,
+            service,
+            ..
+        } => try_add_service(deps, env, info, identifier, service),
+        ExecuteMsg::RemoveService { identifier, id, .. } => {
+            try_remove_service(deps, env, info, identifier, id)
+        }
+        ExecuteMsg::GrantRelationship {
+            identifier,
+            target,
+            relationship,
+            validity,
+            ..
+        } => try_grant_relationship(deps, env, info, identifier, target, relationship, validity),
+        ExecuteMsg::RevokeRelationship {
+            identifier,
+            target,
+            relationship,
+            ..
+        } => try_revoke_relationship(deps, env, info, identifier, target, relationship),
+        ExecuteMsg::LinkAlias {
+            identifier,
+            other,
+            signature,
+            ..
+        } => try_link_alias(deps, env, info, identifier, other, signature),
+        ExecuteMsg::UnlinkAlias {
+            identifier, other, ..
+        } => try_unlink_alias(deps, env, info, identifier, other),
+        ExecuteMsg::SetControllerPolicy {
+            identifier, policy, ..
+        } => try_set_controller_policy(deps, env, info, identifier, policy),
+        ExecuteMsg::CancelPendingChange { identifier, .. } => {
+            try_cancel_pending_change(deps, env, info, identifier)
+        }
+        ExecuteMsg::ExecutePendingChange { identifier, .. } => {
+            try_execute_pending_change(deps, env, info, identifier)
+        }
+        ExecuteMsg::SetGuardians {
+            identifier,
+            guardians,
+            threshold,
+            delay,
+            ..
+        } => try_set_guardians(deps, env, info, identifier, guardians, threshold, delay),
+        ExecuteMsg::StartRecovery {
+            identifier,
+            new_controller,
+            ..
+        } => try_start_recovery(deps, env, info, identifier, new_controller),
+        ExecuteMsg::ApproveRecovery { identifier, .. } => {
+            try_approve_recovery(deps, env, info, identifier)
+        }
+        ExecuteMsg::CancelRecovery { identifier, .. } => {
+            try_cancel_recovery(deps, env, info, identifier)
+        }
+        ExecuteMsg::ExecuteRecovery { identifier, .. } => {
+            try_execute_recovery(deps, env, info, identifier)
+        }
+        ExecuteMsg::AddHook {
+            identifier,
+            hook,
+            on_failure,
+            ..
+        } => try_add_hook(deps, env, info, identifier, hook, on_failure),
+        ExecuteMsg::RemoveHook {
+            identifier, hook, ..
+        } => try_remove_hook(deps, env, info, identifier, hook),
+        ExecuteMsg::AddGlobalHook { hook, on_failure } => {
+            try_add_global_hook(deps, info, hook, on_failure)
+        }
+        ExecuteMsg::RemoveGlobalHook { hook } => try_remove_global_hook(deps, info, hook),
+        ExecuteMsg::UpdateConfig {
+            paused,
+            paused_operations,
+            fees,
+            deposit_per_byte,
+            indexed_prefixes,
+            max_batch,
+            max_services,
+        } => try_update_config(
+            deps,
+            info,
+            paused,
+            paused_operations,
+            fees,
+            deposit_per_byte,
+            indexed_prefixes,
+            max_batch,
+            max_services,
+        ),
+        ExecuteMsg::TransferOwnership { new_owner } => {
+            try_transfer_ownership(deps, info, new_owner)
+        }
+        ExecuteMsg::AcceptOwnership {} => try_accept_ownership(deps, info),
+        ExecuteMsg::WithdrawFees { recipient } => try_withdraw_fees(deps, info, recipient),
+    }?;
+    let response = match refund {
+        Some(refund) => response.add_message(refund),
+        None => response,
+    };
+
+    Ok(match did {
+        Some(did) => response.add_attribute("did", did),
+        None => response,
+    })
 }
 
 pub fn try_change_controller(
@@ -61,33 +489,78 @@ pub fn try_change_controller(
     info: MessageInfo,
     identifier: Addr,
     new_controller: Addr,
+    next_hash: Option<Binary>,
+    lease: Option<Lease>,
 ) -> Result<Response, ContractError> {
-    CONTROLLERS.update(
+    // check controller
+    let loaded_controller = load_controller(deps.storage, &env.block, &identifier)?;
+    only_controller(&info.sender, &identifier, loaded_controller)?;
+    check_commitment(deps.storage, &identifier, &new_controller, &next_hash)?;
+    if next_hash.is_some() {
+        only_unleased(deps.storage, &env.block, &identifier)?;
+    }
+    if let Some(lease) = &lease {
+        if lease.expires <= env.block.time {
+            return Err(ContractError::LeaseEnded {
+                expires: lease.expires,
+            });
+        }
+    }
+    let lease = keep_lease(deps.storage, &env.block, &identifier, lease)?;
+
+    let loaded_policy = CONTROLLER_POLICIES.may_load(deps.storage, &identifier)?;
+    if let Some(policy) = loaded_policy {
+        let change = ScheduledChange::ChangeController {
+            new_controller,
+            next_hash,
+            lease,
+        };
+        return schedule_change(deps, env, info, identifier, policy, change);
+    }
+
+    let (changed, version, hooks) = apply_controller_change(
         deps.storage,
+        &env,
         &identifier,
-        |loaded_controller: Option<Addr>| -> Result<_, ContractError> {
-            only_controller(&info.sender, &identifier, loaded_controller)?;
-
-            Ok(new_controller.clone())
-        },
+        &new_controller,
+        next_hash,
+        lease,
     )?;
 
-    let loaded_changed = CHANGED.may_load(deps.storage, &identifier)?;
-    let changed = loaded_changed.unwrap_or(0);
-
-    let res = Response::new()
+    Ok(Response::new()
+        .add_submessages(hooks)
         .add_attribute("executeMsg", "changeController")
-        .add_attribute("identifier", identifier.clone())
+        .add_attribute("identifier", identifier)
         .add_attribute("controller", new_controller)
-        .add_attribute("previousChange", changed.to_string());
+        .add_attribute("previousChange", changed.to_string())
+        .add_attribute("version", version.to_string()))
+}
 
-    CHANGED.update(
-        deps.storage,
-        &identifier,
-        |_changed: Option<u64>| -> Result<_, ContractError> { Ok(env.block.height) },
-    )?;
+pub fn try_commit_next_controller(
+    deps: DepsMut,
+    env: Env,
+    info: MessageInfo,
+    identifier: Addr,
+    next_hash: Binary,
+) -> Result<Response, ContractError> {
+    // check controller
+    let loaded_controller = load_controller(deps.storage, &env.block, &identifier)?;
+    only_controller(&info.sender, &identifier, loaded_controller)?;
+    only_unleased(deps.storage, &env.block, &identifier)?;
 
-    Ok(res)
+    // an existing commitment only moves on with a rotation
+    if COMMITMENTS.has(deps.storage, &identifier) {
+        return Err(ContractError::CommitmentExists { identifier });
+    }
+    COMMITMENTS.save(deps.storage, &identifier, &next_hash)?;
+    let version = bump_version(deps.storage, &env.block, &identifier)?;
+
+    Ok(Response::new()
+        .add_attribute("executeMsg", "commitNextController")
+        .add_attribute("identifier", identifier)
+        .add_attribute("nextHash", next_hash.to_base64())
+        .add_attribute("version", version.to_string())
+        .add_attribute("from", info.sender))
 }
 
 pub fn try_set_attribute(
@@ -100,16 +573,24 @@ pub fn try_set_attribute(
     validity: u64,
 ) -> Result<Response, ContractError> {
     // check controller
-    let loaded_controller = CONTROLLERS.may_load(deps.storage, &identifier)?;
+    let loaded_controller = load_controller(deps.storage, &env.block, &identifier)?;
     only_controller(&info.sender, &identifier, loaded_controller)?;
 
     let loaded_changed = CHANGED.may_load(deps.storage, &identifier)?;
     let changed = loaded_changed.unwrap_or(0);
 
+    register_identifier(deps.storage, &env.block, &identifier)?;
+
     let loaded_attribute = ATTRIBUTES.may_load(deps.storage, (&identifier, &name))?;
     let mut attribute = loaded_attribute.unwrap_or(Attribute { values: vec![] });
 
+    let mut remaining = info.funds.clone();
     if attribute.values.iter().any(|v| v == &value) {
+        // setting a revoked value again makes it active again
+        let valid_to = VALIDITIES.may_load(deps.storage, (&identifier, &name, &value))?;
+        if valid_to == Some(Timestamp::from_seconds(0)) {
+            update_stats(deps.storage, |stats| stats.active_values += 1)?;
+        }
         VALIDITIES.update(
             deps.storage,
             (&identifier, &name, &value),
@@ -118,6 +599,15 @@ pub fn try_set_attribute(
             },
         )?;
     } else {
+        remaining = lock_deposit(deps.storage, &identifier, &name, &value, remaining)?;
+        let new_attribute = attribute.values.is_empty();
+        update_stats(deps.storage, |stats| {
+            stats.active_values += 1;
+            if new_attribute {
+                stats.attributes += 1;
+            }
+        })?;
+
         ATTRIBUTES.update(
             deps.storage,
             (&identifier, &name),
@@ -136,7 +626,21 @@ pub fn try_set_attribute(
         )?;
     }
 
+    index_attribute(deps.storage, &identifier, &name, &value)?;
+
+    let version = bump_version(deps.storage, &env.block, &identifier)?;
+    let hooks = hook_submsgs(
+        deps.storage,
+        &identifier,
+        DidChange::SetAttribute {
+            name: name.clone(),
+            value: value.clone(),
+            valid_to: env.block.time.plus_seconds(validity),
+        },
+    )?;
+
     let res = Response::new()
+        .add_submessages(hooks)
         .add_attribute("executeMsg", "setAttribute")
         .add_attribute("identifier", identifier.clone())
         .add_attribute("name", name)
@@ -146,7 +650,13 @@ pub fn try_set_attribute(
             env.block.time.plus_seconds(validity).seconds().to_string(),
         )
         .add_attribute("previousChange", changed.to_string())
-        .add_attribute("from", info.sender);
+        .add_attribute("version", version.to_string())
+        .add_attribute("from", info.sender.clone());
+    // funds beyond the deposit are refunded
+    let res = match refund_msg(&info.sender, remaining) {
+        Some(refund) => res.add_message(refund),
+        None => res,
+    };
 
     CHANGED.update(
         deps.storage,
@@ -166,7 +676,7 @@ pub fn try_revoke_attribute(
     value: String,
 ) -> Result<Response, ContractError> {
     // check controller
-    let loaded_controller = CONTROLLERS.may_load(deps.storage, &identifier)?;
+    let loaded_controller = load_controller(deps.storage, &env.block, &identifier)?;
     only_controller(&info.sender, &identifier, loaded_controller)?;
 
     let loaded_changed = CHANGED.may_load(deps.storage, &identifier)?;
@@ -186,6 +696,7 @@ pub fn try_revoke_attribute(
     };
 
     if attribute.values.iter().any(|v| v == &value) {
+        revoke_stats(deps.storage, &identifier, &name, &value)?;
         VALIDITIES.update(
             deps.storage,
             (&identifier, &name, &value),
@@ -193,6 +704,7 @@ pub fn try_revoke_attribute(
                 Ok(Timestamp::from_seconds(0))
             },
         )?;
+        ATTRIBUTE_INDEX.remove(deps.storage, (&name, &value, &identifier));
     } else {
         return Err(ContractError::InvalidKeyPair {
             identifier,
@@ -201,13 +713,25 @@ pub fn try_revoke_attribute(
         });
     }
 
+    let version = bump_version(deps.storage, &env.block, &identifier)?;
+    let hooks = hook_submsgs(
+        deps.storage,
+        &identifier,
+        DidChange::RevokeAttribute {
+            name: name.clone(),
+            value: value.clone(),
+        },
+    )?;
+
     let res = Response::new()
+        .add_submessages(hooks)
         .add_attribute("executeMsg", "revokeAttribute")
         .add_attribute("identifier", identifier.clone())
         .add_attribute("name", name)
         .add_attribute("value", value)
         .add_attribute("validTo", 0.to_string())
         .add_attribute("previousChange", changed.to_string())
+        .add_attribute("version", version.to_string())
         .add_attribute("from", info.sender);
 
     CHANGED.update(
@@ -219,533 +743,7806 @@ pub fn try_revoke_attribute(
     Ok(res)
 }
 
-#[cfg_attr(not(feature = "library"), entry_point)]
-pub fn query(deps: Deps, _env: Env, msg: QueryMsg) -> StdResult<Binary> {
-    match msg {
-        QueryMsg::Controller { identifier } => to_binary(&query_controller(deps, identifier)?),
-        QueryMsg::Attribute { identifier, name } => {
-            to_binary(&query_attribute(deps, identifier, name)?)
-        }
-        QueryMsg::ValidTo {
-            identifier,
-            name,
-            value,
-        } => to_binary(&query_valid_to(deps, identifier, name, value)?),
-        QueryMsg::Changed { identifier } => to_binary(&query_changed(deps, identifier)?),
+/// a value revoked twice is only counted once
+fn revoke_stats(
+    storage: &mut dyn Storage,
+    identifier: &Addr,
+    name: &str,
+    value: &str,
+) -> StdResult<()> {
+    let valid_to = VALIDITIES.may_load(storage, (identifier, name, value))?;
+    if valid_to == Some(Timestamp::from_seconds(0)) {
+        return Ok(());
     }
+    update_stats(storage, |stats| {
+        stats.active_values = stats.active_values.saturating_sub(1)
+    })
 }
 
-fn query_controller(deps: Deps, identifier: Addr) -> StdResult<ControllerResponse> {
-    let loaded_controller = CONTROLLERS.may_load(deps.storage, &identifier)?;
-    match loaded_controller {
-        Some(v) => Ok(ControllerResponse { controller: v }),
-        None => Ok(ControllerResponse {
-            controller: identifier,
-        }),
-    }
-}
+pub fn try_prune_attribute(
+    deps: DepsMut,
+    env: Env,
+    info: MessageInfo,
+    identifier: Addr,
+    name: String,
+) -> Result<Response, ContractError> {
+    // check controller
+    let loaded_controller = load_controller(deps.storage, &env.block, &identifier)?;
+    only_controller(&info.sender, &identifier, loaded_controller)?;
+
+    let loaded_changed = CHANGED.may_load(deps.storage, &identifier)?;
+    let changed = loaded_changed.unwrap_or(0);
 
-fn query_attribute(deps: Deps, identifier: Addr, name: String) -> StdResult<AttributeResponse> {
     let loaded_attribute = ATTRIBUTES.may_load(deps.storage, (&identifier, &name))?;
-    match loaded_attribute {
-        Some(v) => Ok(AttributeResponse { values: v.values }),
-        None => Ok(AttributeResponse { values: vec![] }),
-    }
-}
+    let mut attribute = loaded_attribute.unwrap_or(Attribute { values: vec![] });
 
-fn query_valid_to(
-    deps: Deps,
-    identifier: Addr,
-    name: String,
-    value: String,
-) -> StdResult<ValidToResponse> {
-    let loaded_attribute = VALIDITIES.may_load(deps.storage, (&identifier, &name, &value))?;
-    match loaded_attribute {
-        Some(v) => Ok(ValidToResponse { valid_to: v }),
-        None => Ok(ValidToResponse {
-            valid_to: Timestamp::from_seconds(0),
-        }),
+    // no value of a deactivated identifier is valid anymore
+    let deactivated = DEACTIVATED.has(deps.storage, &identifier);
+    let mut pruned = vec![];
+    let mut kept = vec![];
+    let mut expired = 0;
+    for value in attribute.values {
+        let valid_to = VALIDITIES
+            .may_load(deps.storage, (&identifier, &name, &value))?
+            .unwrap_or_else(|| Timestamp::from_seconds(0));
+        if deactivated || valid_to <= env.block.time {
+            // revoked values are not active anymore
+            if valid_to != Timestamp::from_seconds(0) {
+                expired += 1;
+            }
+            pruned.push(value);
+        } else {
+            kept.push(value);
+        }
+    }
+    if pruned.is_empty() {
+        return Err(ContractError::NothingToPrune {});
     }
-}
 
-fn query_changed(deps: Deps, identifier: Addr) -> StdResult<ChangedResponse> {
-    let loaded_changed = CHANGED.may_load(deps.storage, &identifier)?;
-    match loaded_changed {
-        Some(v) => Ok(ChangedResponse { block: v }),
-        None => Ok(ChangedResponse { block: 0 }),
+    let mut refund = vec![];
+    for value in &pruned {
+        VALIDITIES.remove(deps.storage, (&identifier, &name, value));
+        ATTRIBUTE_INDEX.remove(deps.storage, (&name, value, &identifier));
+        if let Some(deposit) = DEPOSITS.may_load(deps.storage, (&identifier, &name, value))? {
+            DEPOSITS.remove(deps.storage, (&identifier, &name, value));
+            add_coin(&mut refund, deposit);
+        }
     }
-}
 
-#[cfg(test)]
-mod tests {
-    use super::*;
-    use crate::helper::get_attribute_value;
-    use cosmwasm_std::testing::{mock_dependencies_with_balance, mock_env, mock_info};
-    use cosmwasm_std::{coins, from_binary};
+    attribute.values = kept;
+    let removed = attribute.values.is_empty();
+    if removed {
+        ATTRIBUTES.remove(deps.storage, (&identifier, &name));
+    } else {
+        ATTRIBUTES.save(deps.storage, (&identifier, &name), &attribute)?;
+    }
+    update_stats(deps.storage, |stats| {
+        stats.active_values = stats.active_values.saturating_sub(expired);
+        if removed {
+            stats.attributes = stats.attributes.saturating_sub(1);
+        }
+    })?;
 
-    #[test]
-    fn proper_initialization() {
-        let mut deps = mock_dependencies_with_balance(&coins(2, "token"));
+    let version = bump_version(deps.storage, &env.block, &identifier)?;
+    let hooks = hook_submsgs(
+        deps.storage,
+        &identifier,
+        DidChange::PruneAttribute {
+            name: name.clone(),
+            values: pruned.clone(),
+        },
+    )?;
 
-        let msg = InstantiateMsg {};
-        let info = mock_info("creator", &coins(1000, "earth"));
+    let mut res = Response::new()
+        .add_submessages(hooks)
+        .add_attribute("executeMsg", "pruneAttribute")
+        .add_attribute("identifier", identifier.clone())
+        .add_attribute("name", name)
+        .add_attribute("pruned", pruned.len().to_string())
+        .add_attribute("previousChange", changed.to_string())
+        .add_attribute("version", version.to_string())
+        .add_attribute("from", info.sender.clone());
 
-        // we can just call .unwrap() to assert this was a success
-        let res = instantiate(deps.as_mut(), mock_env(), info, msg).unwrap();
-        assert_eq!(0, res.messages.len());
+    // deposits are refunded to the controller
+    if !refund.is_empty() {
+        res = res.add_message(BankMsg::Send {
+            to_address: info.sender.to_string(),
+            amount: refund,
+        });
     }
 
-    #[test]
-    fn controller() {
-        let mut deps = mock_dependencies_with_balance(&coins(2, "token"));
-        let msg = InstantiateMsg {};
-        let info = mock_info("creator", &coins(2, "token"));
-        let _res = instantiate(deps.as_mut(), mock_env(), info, msg).unwrap();
+    CHANGED.update(
+        deps.storage,
+        &identifier,
+        |_changed: Option<u64>| -> Result<_, ContractError> { Ok(env.block.height) },
+    )?;
 
-        let identifier = String::from("identifier0001");
+    Ok(res)
+}
 
-        let res = query(
-            deps.as_ref(),
-            mock_env(),
-            QueryMsg::Controller {
-                identifier: Addr::unchecked(&identifier),
-            },
-        )
-        .unwrap();
-        let value: ControllerResponse = from_binary(&res).unwrap();
-        assert_eq!(identifier, value.controller);
+#[allow(clippy::too_many_arguments)]
+pub fn try_add_verification_method(
+    deps: DepsMut,
+    env: Env,
+    info: MessageInfo,
+    identifier: Addr,
+    id: String,
+    key_type: KeyType,
+    controller: Option<Addr>,
+    public_key: PublicKey,
+    relationships: Vec<Relationship>,
+    validity: u64,
+) -> Result<Response, ContractError> {
+    // check controller
+    let loaded_controller = load_controller(deps.storage, &env.block, &identifier)?;
+    only_controller(&info.sender, &identifier, loaded_controller)?;
 
-        let res = query(
-            deps.as_ref(),
-            mock_env(),
-            QueryMsg::Changed {
-                identifier: Addr::unchecked(&identifier),
-            },
-        )
-        .unwrap();
-        let value: ChangedResponse = from_binary(&res).unwrap();
-        assert_eq!(0, value.block);
+    if !valid_fragment(&id) {
+        return Err(ContractError::InvalidVerificationMethodId { id });
+    }
+    if VERIFICATION_METHODS.has(deps.storage, (&identifier, &id)) {
+        return Err(ContractError::VerificationMethodExists { id });
     }
+    for relationship in &relationships {
+        only_key_relationship(key_type, *relationship)?;
+    }
+    let key = decode_public_key(key_type, &public_key)?;
 
-    #[test]
-    fn change_controller() {
-        let mut deps = mock_dependencies_with_balance(&coins(2, "token"));
-        let msg = InstantiateMsg {};
-        let info = mock_info("creator", &coins(2, "token"));
-        let _res = instantiate(deps.as_mut(), mock_env(), info, msg).unwrap();
+    register_identifier(deps.storage, &env.block, &identifier)?;
+    let method = VerificationMethod {
+        key_type,
+        controller: controller.unwrap_or_else(|| identifier.clone()),
+        public_key: Binary::from(key),
+        valid_to: env.block.time.plus_seconds(validity),
+    };
+    VERIFICATION_METHODS.save(deps.storage, (&identifier, &id), &method)?;
+    for relationship in relationships {
+        KEY_RELATIONSHIPS.save(
+            deps.storage,
+            (&identifier, relationship.as_str(), &id),
+            &method.valid_to,
+        )?;
+    }
 
-        let identifier1 = String::from("identifier0001");
-        let controller1 = String::from("addr0001");
+    let changed = CHANGED.may_load(deps.storage, &identifier)?.unwrap_or(0);
+    CHANGED.save(deps.storage, &identifier, &env.block.height)?;
+    let version = bump_version(deps.storage, &env.block, &identifier)?;
+
+    let hooks = hook_submsgs(
+        deps.storage,
+        &identifier,
+        DidChange::AddVerificationMethod { id: id.clone() },
+    )?;
+
+    Ok(Response::new()
+        .add_submessages(hooks)
+        .add_attribute("executeMsg", "addVerificationMethod")
+        .add_attribute("identifier", identifier)
+        .add_attribute("id", id)
+        .add_attribute("validTo", method.valid_to.seconds().to_string())
+        .add_attribute("previousChange", changed.to_string())
+        .add_attribute("version", version.to_string())
+        .add_attribute("from", info.sender))
+}
+
+pub fn try_remove_verification_method(
+    deps: DepsMut,
+    env: Env,
+    info: MessageInfo,
+    identifier: Addr,
+    id: String,
+) -> Result<Response, ContractError> {
+    // check controller
+    let loaded_controller = load_controller(deps.storage, &env.block, &identifier)?;
+    only_controller(&info.sender, &identifier, loaded_controller)?;
+
+    if !VERIFICATION_METHODS.has(deps.storage, (&identifier, &id)) {
+        return Err(ContractError::VerificationMethodNotFound { id });
+    }
+    VERIFICATION_METHODS.remove(deps.storage, (&identifier, &id));
+    for relationship in Relationship::ALL {
+        KEY_RELATIONSHIPS.remove(deps.storage, (&identifier, relationship.as_str(), &id));
+    }
+
+    let changed = CHANGED.may_load(deps.storage, &identifier)?.unwrap_or(0);
+    CHANGED.save(deps.storage, &identifier, &env.block.height)?;
+    let version = bump_version(deps.storage, &env.block, &identifier)?;
+
+    let hooks = hook_submsgs(
+        deps.storage,
+        &identifier,
+        DidChange::RemoveVerificationMethod { id: id.clone() },
+    )?;
+
+    Ok(Response::new()
+        .add_submessages(hooks)
+        .add_attribute("executeMsg", "removeVerificationMethod")
+        .add_attribute("identifier", identifier)
+        .add_attribute("id", id)
+        .add_attribute("previousChange", changed.to_string())
+        .add_attribute("version", version.to_string())
+        .add_attribute("from", info.sender))
+}
+
+pub fn try_add_service(
+    deps: DepsMut,
+    env: Env,
+    info: MessageInfo,
+    identifier: Addr,
+    service: Service,
+) -> Result<Response, ContractError> {
+    // check controller
+    let loaded_controller = load_controller(deps.storage, &env.block, &identifier)?;
+    only_controller(&info.sender, &identifier, loaded_controller)?;
+
+    validate_service(&service)?;
+    if SERVICES.has(deps.storage, (&identifier, &service.id)) {
+        return Err(ContractError::ServiceExists { id: service.id });
+    }
+    let max = CONFIG
+        .load(deps.storage)?
+        .max_services
+        .unwrap_or(DEFAULT_MAX_SERVICES);
+    let count = SERVICES
+        .prefix(&identifier)
+        .keys_raw(deps.storage, None, None, Order::Ascending)
+        .count();
+    if count >= max as usize {
+        return Err(ContractError::TooManyServices { max });
+    }
+
+    register_identifier(deps.storage, &env.block, &identifier)?;
+    SERVICES.save(deps.storage, (&identifier, &service.id), &service)?;
+
+    let changed = CHANGED.may_load(deps.storage, &identifier)?.unwrap_or(0);
+    CHANGED.save(deps.storage, &identifier, &env.block.height)?;
+    let version = bump_version(deps.storage, &env.block, &identifier)?;
+
+    let hooks = hook_submsgs(
+        deps.storage,
+        &identifier,
+        DidChange::AddService {
+            id: service.id.clone(),
+        },
+    )?;
+
+    Ok(Response::new()
+        .add_submessages(hooks)
+        .add_attribute("executeMsg", "addService")
+        .add_attribute("identifier", identifier)
+        .add_attribute("id", service.id)
+        .add_attribute("type", service.service_type)
+        .add_attribute("previousChange", changed.to_string())
+        .add_attribute("version", version.to_string())
+        .add_attribute("from", info.sender))
+}
+
+pub fn try_remove_service(
+    deps: DepsMut,
+    env: Env,
+    info: MessageInfo,
+    identifier: Addr,
+    id: String,
+) -> Result<Response, ContractError> {
+    // check controller
+    let loaded_controller = load_controller(deps.storage, &env.block, &identifier)?;
+    only_controller(&info.sender, &identifier, loaded_controller)?;
+
+    if !SERVICES.has(deps.storage, (&identifier, &id)) {
+        return Err(ContractError::ServiceNotFound { id });
+    }
+    SERVICES.remove(deps.storage, (&identifier, &id));
+
+    let changed = CHANGED.may_load(deps.storage, &identifier)?.unwrap_or(0);
+    CHANGED.save(deps.storage, &identifier, &env.block.height)?;
+    let version = bump_version(deps.storage, &env.block, &identifier)?;
+
+    let hooks = hook_submsgs(
+        deps.storage,
+        &identifier,
+        DidChange::RemoveService { id: id.clone() },
+    )?;
+
+    Ok(Response::new()
+        .add_submessages(hooks)
+        .add_attribute("executeMsg", "removeService")
+        .add_attribute("identifier", identifier)
+        .add_attribute("id", id)
+        .add_attribute("previousChange", changed.to_string())
+        .add_attribute("version", version.to_string())
+        .add_attribute("from", info.sender))
+}
+
+pub fn try_grant_relationship(
+    deps: DepsMut,
+    env: Env,
+    info: MessageInfo,
+    identifier: Addr,
+    target: RelationshipTarget,
+    relationship: Relationship,
+    validity: u64,
+) -> Result<Response, ContractError> {
+    // check controller
+    let loaded_controller = load_controller(deps.storage, &env.block, &identifier)?;
+    only_controller(&info.sender, &identifier, loaded_controller)?;
+
+    let valid_to = env.block.time.plus_seconds(validity);
+    match &target {
+        RelationshipTarget::Key { id } => {
+            let method = VERIFICATION_METHODS
+                .may_load(deps.storage, (&identifier, id))?
+                .ok_or_else(|| ContractError::VerificationMethodNotFound { id: id.clone() })?;
+            only_key_relationship(method.key_type, relationship)?;
+            KEY_RELATIONSHIPS.save(
+                deps.storage,
+                (&identifier, relationship.as_str(), id),
+                &valid_to,
+            )?;
+        }
+        RelationshipTarget::Delegate { address } => {
+            // an account has no key to agree on
+            if relationship == Relationship::KeyAgreement {
+                return Err(ContractError::InvalidDelegateRelationship { relationship });
+            }
+            register_identifier(deps.storage, &env.block, &identifier)?;
+            DELEGATES.save(
+                deps.storage,
+                (&identifier, relationship.as_str(), address),
+                &valid_to,
+            )?;
+        }
+    }
+
+    let changed = CHANGED.may_load(deps.storage, &identifier)?.unwrap_or(0);
+    CHANGED.save(deps.storage, &identifier, &env.block.height)?;
+    let version = bump_version(deps.storage, &env.block, &identifier)?;
+
+    let hooks = hook_submsgs(
+        deps.storage,
+        &identifier,
+        DidChange::GrantRelationship {
+            target: target.clone(),
+            relationship,
+        },
+    )?;
+
+    Ok(Response::new()
+        .add_submessages(hooks)
+        .add_attribute("executeMsg", "grantRelationship")
+        .add_attribute("identifier", identifier)
+        .add_attribute("relationship", relationship.as_str())
+        .add_attributes(target_attributes(&target))
+        .add_attribute("validTo", valid_to.seconds().to_string())
+        .add_attribute("previousChange", changed.to_string())
+        .add_attribute("version", version.to_string())
+        .add_attribute("from", info.sender))
+}
+
+pub fn try_revoke_relationship(
+    deps: DepsMut,
+    env: Env,
+    info: MessageInfo,
+    identifier: Addr,
+    target: RelationshipTarget,
+    relationship: Relationship,
+) -> Result<Response, ContractError> {
+    // check controller
+    let loaded_controller = load_controller(deps.storage, &env.block, &identifier)?;
+    only_controller(&info.sender, &identifier, loaded_controller)?;
+
+    // revoking what is not granted is a no-op, as revoking attributes is
+    match &target {
+        RelationshipTarget::Key { id } => {
+            KEY_RELATIONSHIPS.remove(deps.storage, (&identifier, relationship.as_str(), id))
+        }
+        RelationshipTarget::Delegate { address } => {
+            DELEGATES.remove(deps.storage, (&identifier, relationship.as_str(), address))
+        }
+    }
+
+    let changed = CHANGED.may_load(deps.storage, &identifier)?.unwrap_or(0);
+    CHANGED.save(deps.storage, &identifier, &env.block.height)?;
+    let version = bump_version(deps.storage, &env.block, &identifier)?;
+
+    let hooks = hook_submsgs(
+        deps.storage,
+        &identifier,
+        DidChange::RevokeRelationship {
+            target: target.clone(),
+            relationship,
+        },
+    )?;
+
+    Ok(Response::new()
+        .add_submessages(hooks)
+        .add_attribute("executeMsg", "revokeRelationship")
+        .add_attribute("identifier", identifier)
+        .add_attribute("relationship", relationship.as_str())
+        .add_attributes(target_attributes(&target))
+        .add_attribute("previousChange", changed.to_string())
+        .add_attribute("version", version.to_string())
+        .add_attribute("from", info.sender))
+}
+
+fn target_attributes(target: &RelationshipTarget) -> Vec<(&'static str, String)> {
+    match target {
+        RelationshipTarget::Key { id } => vec![("id", id.clone())],
+        RelationshipTarget::Delegate { address } => vec![("delegate", address.to_string())],
+    }
+}
+
+pub fn try_link_alias(
+    deps: DepsMut,
+    env: Env,
+    info: MessageInfo,
+    identifier: Addr,
+    other: Addr,
+    signature: Option<Binary>,
+) -> Result<Response, ContractError> {
+    // check controller
+    let loaded_controller = load_controller(deps.storage, &env.block, &identifier)?;
+    only_controller(&info.sender, &identifier, loaded_controller)?;
+
+    if other == identifier {
+        return Err(ContractError::InvalidAlias {
+            reason: String::from("an identifier cannot be its own alias"),
+        });
+    }
+    // the signature of its controller account asserts the link on behalf of the other identifier,
+    // delegates and keys only act for the identifier and cannot link it
+    if let Some(signature) = signature {
+        only_active(deps.storage, &other)?;
+        let nonce = NONCE.may_load(deps.storage, &other)?.unwrap_or(0);
+        let message = alias_message(&env.contract.address, &other, &identifier, nonce);
+        let other_controller =
+            load_controller(deps.storage, &env.block, &other)?.unwrap_or_else(|| other.clone());
+        if !signed_by_account(deps.api, &other_controller, &message, &signature) {
+            return Err(ContractError::InvalidAliasSignature {});
+        }
+        NONCE.save(deps.storage, &other, &(nonce + 1))?;
+        register_identifier(deps.storage, &env.block, &other)?;
+        ALIASES.save(deps.storage, (&other, &identifier), &env.block.time)?;
+    }
+
+    register_identifier(deps.storage, &env.block, &identifier)?;
+    ALIASES.save(deps.storage, (&identifier, &other), &env.block.time)?;
+    let verified = ALIASES.has(deps.storage, (&other, &identifier));
+
+    let changed = CHANGED.may_load(deps.storage, &identifier)?.unwrap_or(0);
+    CHANGED.save(deps.storage, &identifier, &env.block.height)?;
+    let version = bump_version(deps.storage, &env.block, &identifier)?;
+
+    let hooks = hook_submsgs(
+        deps.storage,
+        &identifier,
+        DidChange::LinkAlias {
+            other: other.clone(),
+        },
+    )?;
+
+    let mut res = Response::new()
+        .add_submessages(hooks)
+        .add_attribute("executeMsg", "linkAlias")
+        .add_attribute("identifier", identifier.clone())
+        .add_attribute("other", other.clone())
+        .add_attribute("verified", verified.to_string())
+        .add_attribute("previousChange", changed.to_string())
+        .add_attribute("version", version.to_string())
+        .add_attribute("from", info.sender);
+
+    // a verified link shows in the document of the other identifier as well
+    if verified {
+        let change = DidChange::LinkAlias {
+            other: identifier.clone(),
+        };
+        let (other_version, other_hooks) = change_alias_side(deps.storage, &env, &other, change)?;
+        res = res
+            .add_submessages(other_hooks)
+            .add_attribute("otherVersion", other_version.to_string());
+    }
+
+    Ok(res)
+}
+
+/// bumps CHANGED and the version of the other side of an alias link whose document changed,
+/// returns the new version with the hooks of the other side, whose failures are ignored
+fn change_alias_side(
+    storage: &mut dyn Storage,
+    env: &Env,
+    other: &Addr,
+    change: DidChange,
+) -> Result<(u64, Vec<SubMsg>), ContractError> {
+    register_identifier(storage, &env.block, other)?;
+    CHANGED.save(storage, other, &env.block.height)?;
+    let version = bump_version(storage, &env.block, other)?;
+    let hooks = ignoring_hook_submsgs(storage, other, change)?;
+    Ok((version, hooks))
+}
+
+pub fn try_unlink_alias(
+    deps: DepsMut,
+    env: Env,
+    info: MessageInfo,
+    identifier: Addr,
+    other: Addr,
+) -> Result<Response, ContractError> {
+    // check controller
+    let loaded_controller = load_controller(deps.storage, &env.block, &identifier)?;
+    only_controller(&info.sender, &identifier, loaded_controller)?;
+
+    if !ALIASES.has(deps.storage, (&identifier, &other)) {
+        return Err(ContractError::AliasNotFound { other });
+    }
+    ALIASES.remove(deps.storage, (&identifier, &other));
+
+    let changed = CHANGED.may_load(deps.storage, &identifier)?.unwrap_or(0);
+    CHANGED.save(deps.storage, &identifier, &env.block.height)?;
+    let version = bump_version(deps.storage, &env.block, &identifier)?;
+
+    let hooks = hook_submsgs(
+        deps.storage,
+        &identifier,
+        DidChange::UnlinkAlias {
+            other: other.clone(),
+        },
+    )?;
+
+    let mut res = Response::new()
+        .add_submessages(hooks)
+        .add_attribute("executeMsg", "unlinkAlias")
+        .add_attribute("identifier", identifier.clone())
+        .add_attribute("other", other.clone())
+        .add_attribute("previousChange", changed.to_string())
+        .add_attribute("version", version.to_string())
+        .add_attribute("from", info.sender);
+
+    // the other identifier loses the alias from its document when the link was verified
+    if ALIASES.has(deps.storage, (&other, &identifier)) {
+        let change = DidChange::UnlinkAlias {
+            other: identifier.clone(),
+        };
+        let (other_version, other_hooks) = change_alias_side(deps.storage, &env, &other, change)?;
+        res = res
+            .add_submessages(other_hooks)
+            .add_attribute("otherVersion", other_version.to_string());
+    }
+
+    Ok(res)
+}
+
+pub fn try_set_controller_policy(
+    deps: DepsMut,
+    env: Env,
+    info: MessageInfo,
+    identifier: Addr,
+    policy: Option<ControllerPolicy>,
+) -> Result<Response, ContractError> {
+    // check controller
+    let loaded_controller = load_controller(deps.storage, &env.block, &identifier)?;
+    only_controller(&info.sender, &identifier, loaded_controller)?;
+    only_unleased(deps.storage, &env.block, &identifier)?;
+
+    if let Some(policy) = &policy {
+        policy.delay.validate()?;
+    }
+
+    // weakening the timelock has to wait for the current timelock
+    let loaded_policy = CONTROLLER_POLICIES.may_load(deps.storage, &identifier)?;
+    if let Some(current) = loaded_policy {
+        let change = ScheduledChange::SetControllerPolicy { policy };
+        return schedule_change(deps, env, info, identifier, current, change);
+    }
+
+    if let Some(policy) = &policy {
+        CONTROLLER_POLICIES.save(deps.storage, &identifier, policy)?;
+    }
+    let version = bump_version(deps.storage, &env.block, &identifier)?;
+
+    Ok(Response::new()
+        .add_attribute("executeMsg", "setControllerPolicy")
+        .add_attribute("identifier", identifier)
+        .add_attribute("version", version.to_string())
+        .add_attribute("from", info.sender))
+}
+
+fn schedule_change(
+    deps: DepsMut,
+    env: Env,
+    info: MessageInfo,
+    identifier: Addr,
+    policy: ControllerPolicy,
+    change: ScheduledChange,
+) -> Result<Response, ContractError> {
+    if PENDING_CHANGES.has(deps.storage, &identifier) {
+        return Err(ContractError::PendingChangeExists { identifier });
+    }
+
+    let pending = PendingChange {
+        change,
+        executable_at: policy.delay.after(&env.block)?,
+    };
+    PENDING_CHANGES.save(deps.storage, &identifier, &pending)?;
+    let version = bump_version(deps.storage, &env.block, &identifier)?;
+
+    Ok(Response::new()
+        .add_attribute("executeMsg", "scheduleChange")
+        .add_attribute("identifier", identifier)
+        .add_attribute("change", change_name(&pending.change))
+        .add_attribute("executableAt", pending.executable_at.to_string())
+        .add_attribute("version", version.to_string())
+        .add_attribute("from", info.sender))
+}
+
+pub fn try_cancel_pending_change(
+    deps: DepsMut,
+    env: Env,
+    info: MessageInfo,
+    identifier: Addr,
+) -> Result<Response, ContractError> {
+    // the veto key can cancel besides the controller
+    let policy = CONTROLLER_POLICIES.may_load(deps.storage, &identifier)?;
+    if policy.and_then(|p| p.veto).as_ref() != Some(&info.sender) {
+        let loaded_controller = load_controller(deps.storage, &env.block, &identifier)?;
+        only_controller(&info.sender, &identifier, loaded_controller)?;
+    }
+
+    let pending = PENDING_CHANGES
+        .may_load(deps.storage, &identifier)?
+        .ok_or_else(|| ContractError::NoPendingChange {
+            identifier: identifier.clone(),
+        })?;
+    PENDING_CHANGES.remove(deps.storage, &identifier);
+    let version = bump_version(deps.storage, &env.block, &identifier)?;
+
+    Ok(Response::new()
+        .add_attribute("executeMsg", "cancelChange")
+        .add_attribute("identifier", identifier)
+        .add_attribute("change", change_name(&pending.change))
+        .add_attribute("version", version.to_string())
+        .add_attribute("from", info.sender))
+}
+
+pub fn try_execute_pending_change(
+    deps: DepsMut,
+    env: Env,
+    info: MessageInfo,
+    identifier: Addr,
+) -> Result<Response, ContractError> {
+    let pending = PENDING_CHANGES
+        .may_load(deps.storage, &identifier)?
+        .ok_or_else(|| ContractError::NoPendingChange {
+            identifier: identifier.clone(),
+        })?;
+    if !pending.executable_at.is_reached(&env.block) {
+        return Err(ContractError::PendingChangeNotReady { identifier });
+    }
+    // a commitment may have been made after the change was scheduled
+    match &pending.change {
+        ScheduledChange::ChangeController {
+            new_controller,
+            next_hash,
+            ..
+        } => check_commitment(deps.storage, &identifier, new_controller, next_hash)?,
+        ScheduledChange::Recovery { new_controller } => {
+            check_recovery_commitment(deps.storage, &identifier, new_controller)?
+        }
+        ScheduledChange::SetControllerPolicy { .. } | ScheduledChange::SetGuardians { .. } => {}
+    }
+    PENDING_CHANGES.remove(deps.storage, &identifier);
+
+    let res = Response::new()
+        .add_attribute("executeMsg", "executeChange")
+        .add_attribute("identifier", identifier.clone())
+        .add_attribute("change", change_name(&pending.change));
+
+    let res = match pending.change {
+        ScheduledChange::ChangeController {
+            new_controller,
+            next_hash,
+            lease,
+        } => {
+            let lease = keep_lease(deps.storage, &env.block, &identifier, lease)?;
+            let (changed, version, hooks) = apply_controller_change(
+                deps.storage,
+                &env,
+                &identifier,
+                &new_controller,
+                next_hash,
+                lease,
+            )?;
+            res.add_submessages(hooks)
+                .add_attribute("controller", new_controller)
+                .add_attribute("previousChange", changed.to_string())
+                .add_attribute("version", version.to_string())
+        }
+        ScheduledChange::SetControllerPolicy { policy } => {
+            match policy {
+                Some(policy) => CONTROLLER_POLICIES.save(deps.storage, &identifier, &policy)?,
+                None => CONTROLLER_POLICIES.remove(deps.storage, &identifier),
+            }
+            let version = bump_version(deps.storage, &env.block, &identifier)?;
+            res.add_attribute("version", version.to_string())
+        }
+        ScheduledChange::SetGuardians {
+            guardians,
+            threshold,
+            delay,
+        } => {
+            let guardian_set = GuardianSet {
+                guardians,
+                threshold,
+                delay,
+            };
+            save_guardians(deps.storage, &identifier, &guardian_set)?;
+            let version = bump_version(deps.storage, &env.block, &identifier)?;
+            res.add_attribute("guardians", guardian_set.guardians.len().to_string())
+                .add_attribute("threshold", threshold.to_string())
+                .add_attribute("delay", delay.to_string())
+                .add_attribute("version", version.to_string())
+        }
+        ScheduledChange::Recovery { new_controller } => {
+            let lease = keep_lease(deps.storage, &env.block, &identifier, None)?;
+            let (changed, version, hooks) = apply_controller_change(
+                deps.storage,
+                &env,
+                &identifier,
+                &new_controller,
+                None,
+                lease,
+            )?;
+            res.add_submessages(hooks)
+                .add_attribute("controller", new_controller)
+                .add_attribute("previousChange", changed.to_string())
+                .add_attribute("version", version.to_string())
+        }
+    };
+
+    Ok(res.add_attribute("from", info.sender))
+}
+
+fn change_name(change: &ScheduledChange) -> &'static str {
+    match change {
+        ScheduledChange::ChangeController { .. } => "changeController",
+        ScheduledChange::SetControllerPolicy { .. } => "setControllerPolicy",
+        ScheduledChange::SetGuardians { .. } => "setGuardians",
+        ScheduledChange::Recovery { .. } => "recovery",
+    }
+}
+
+pub fn try_set_guardians(
+    deps: DepsMut,
+    env: Env,
+    info: MessageInfo,
+    identifier: Addr,
+    guardians: Vec<Addr>,
+    threshold: u32,
+    delay: u64,
+) -> Result<Response, ContractError> {
+    // check controller
+    let loaded_controller = load_controller(deps.storage, &env.block, &identifier)?;
+    only_controller(&info.sender, &identifier, loaded_controller)?;
+    only_unleased(deps.storage, &env.block, &identifier)?;
+
+    let mut guardians = guardians;
+    guardians.sort();
+    guardians.dedup();
+    if threshold == 0 || threshold as usize > guardians.len() {
+        return Err(ContractError::InvalidThreshold {
+            threshold,
+            guardians: guardians.len() as u32,
+        });
+    }
+    Delay::Time(delay).validate()?;
+
+    // guardians take control, so they wait for the timelock like any controller change
+    let loaded_policy = CONTROLLER_POLICIES.may_load(deps.storage, &identifier)?;
+    if let Some(policy) = loaded_policy {
+        if let Delay::Time(min) = policy.delay {
+            if delay < min {
+                return Err(ContractError::RecoveryDelayTooShort { min });
+            }
+        }
+        let change = ScheduledChange::SetGuardians {
+            guardians,
+            threshold,
+            delay,
+        };
+        return schedule_change(deps, env, info, identifier, policy, change);
+    }
+
+    let guardian_set = GuardianSet {
+        guardians,
+        threshold,
+        delay,
+    };
+    save_guardians(deps.storage, &identifier, &guardian_set)?;
+    let version = bump_version(deps.storage, &env.block, &identifier)?;
+
+    Ok(Response::new()
+        .add_attribute("executeMsg", "setGuardians")
+        .add_attribute("identifier", identifier)
+        .add_attribute("guardians", guardian_set.guardians.len().to_string())
+        .add_attribute("threshold", threshold.to_string())
+        .add_attribute("delay", delay.to_string())
+        .add_attribute("version", version.to_string())
+        .add_attribute("from", info.sender))
+}
+
+fn save_guardians(
+    storage: &mut dyn Storage,
+    identifier: &Addr,
+    guardian_set: &GuardianSet,
+) -> StdResult<()> {
+    GUARDIANS.save(storage, identifier, guardian_set)?;
+    // approvals of the previous guardians do not count anymore
+    RECOVERIES.remove(storage, identifier);
+    Ok(())
+}
+
+pub fn try_start_recovery(
+    deps: DepsMut,
+    env: Env,
+    info: MessageInfo,
+    identifier: Addr,
+    new_controller: Addr,
+) -> Result<Response, ContractError> {
+    let guardian_set = only_guardian(deps.storage, &info.sender, &identifier)?;
+    check_recovery_commitment(deps.storage, &identifier, &new_controller)?;
+
+    if RECOVERIES.has(deps.storage, &identifier) {
+        return Err(ContractError::RecoveryInProgress { identifier });
+    }
+
+    let recovery = Recovery {
+        new_controller,
+        approvals: vec![info.sender.clone()],
+        executable_at: None,
+    };
+    let recovery = approve_recovery(&env, &guardian_set, recovery)?;
+    RECOVERIES.save(deps.storage, &identifier, &recovery)?;
+    let version = bump_version(deps.storage, &env.block, &identifier)?;
+
+    Ok(Response::new()
+        .add_attribute("executeMsg", "startRecovery")
+        .add_attribute("identifier", identifier)
+        .add_attribute("newController", recovery.new_controller)
+        .add_attribute("approvals", recovery.approvals.len().to_string())
+        .add_attribute("version", version.to_string())
+        .add_attribute("from", info.sender))
+}
+
+pub fn try_approve_recovery(
+    deps: DepsMut,
+    env: Env,
+    info: MessageInfo,
+    identifier: Addr,
+) -> Result<Response, ContractError> {
+    let guardian_set = only_guardian(deps.storage, &info.sender, &identifier)?;
+
+    let mut recovery = RECOVERIES
+        .may_load(deps.storage, &identifier)?
+        .ok_or_else(|| ContractError::NoRecovery {
+            identifier: identifier.clone(),
+        })?;
+    if !recovery.approvals.contains(&info.sender) {
+        recovery.approvals.push(info.sender.clone());
+    }
+    let recovery = approve_recovery(&env, &guardian_set, recovery)?;
+    RECOVERIES.save(deps.storage, &identifier, &recovery)?;
+    let version = bump_version(deps.storage, &env.block, &identifier)?;
+
+    Ok(Response::new()
+        .add_attribute("executeMsg", "approveRecovery")
+        .add_attribute("identifier", identifier)
+        .add_attribute("newController", recovery.new_controller)
+        .add_attribute("approvals", recovery.approvals.len().to_string())
+        .add_attribute("version", version.to_string())
+        .add_attribute("from", info.sender))
+}
+
+pub fn try_cancel_recovery(
+    deps: DepsMut,
+    env: Env,
+    info: MessageInfo,
+    identifier: Addr,
+) -> Result<Response, ContractError> {
+    // check controller
+    let loaded_controller = load_controller(deps.storage, &env.block, &identifier)?;
+    only_controller(&info.sender, &identifier, loaded_controller)?;
+
+    if !RECOVERIES.has(deps.storage, &identifier) {
+        return Err(ContractError::NoRecovery { identifier });
+    }
+    RECOVERIES.remove(deps.storage, &identifier);
+    let version = bump_version(deps.storage, &env.block, &identifier)?;
+
+    Ok(Response::new()
+        .add_attribute("executeMsg", "cancelRecovery")
+        .add_attribute("identifier", identifier)
+        .add_attribute("version", version.to_string())
+        .add_attribute("from", info.sender))
+}
+
+pub fn try_execute_recovery(
+    deps: DepsMut,
+    env: Env,
+    info: MessageInfo,
+    identifier: Addr,
+) -> Result<Response, ContractError> {
+    let recovery = RECOVERIES
+        .may_load(deps.storage, &identifier)?
+        .ok_or_else(|| ContractError::NoRecovery {
+            identifier: identifier.clone(),
+        })?;
+    match recovery.executable_at {
+        Some(executable_at) if executable_at <= env.block.time => {}
+        _ => return Err(ContractError::RecoveryNotReady { identifier }),
+    }
+    check_recovery_commitment(deps.storage, &identifier, &recovery.new_controller)?;
+    RECOVERIES.remove(deps.storage, &identifier);
+
+    // the veto can still stop the recovery during the timelock
+    let loaded_policy = CONTROLLER_POLICIES.may_load(deps.storage, &identifier)?;
+    if let Some(policy) = loaded_policy {
+        let change = ScheduledChange::Recovery {
+            new_controller: recovery.new_controller,
+        };
+        return schedule_change(deps, env, info, identifier, policy, change);
+    }
+
+    // a recovery during a lease keeps it, the fallback still takes over
+    let lease = keep_lease(deps.storage, &env.block, &identifier, None)?;
+    let (changed, version, hooks) = apply_controller_change(
+        deps.storage,
+        &env,
+        &identifier,
+        &recovery.new_controller,
+        None,
+        lease,
+    )?;
+
+    Ok(Response::new()
+        .add_submessages(hooks)
+        .add_attribute("executeMsg", "executeRecovery")
+        .add_attribute("identifier", identifier)
+        .add_attribute("controller", recovery.new_controller)
+        .add_attribute("previousChange", changed.to_string())
+        .add_attribute("version", version.to_string())
+        .add_attribute("from", info.sender))
+}
+
+/// starts the timelock once the threshold of approvals is reached
+fn approve_recovery(
+    env: &Env,
+    guardian_set: &GuardianSet,
+    mut recovery: Recovery,
+) -> Result<Recovery, ContractError> {
+    if recovery.executable_at.is_none()
+        && recovery.approvals.len() >= guardian_set.threshold as usize
+    {
+        if let Deadline::AtTime(time) = Delay::Time(guardian_set.delay).after(&env.block)? {
+            recovery.executable_at = Some(time);
+        }
+    }
+    Ok(recovery)
+}
+
+/// stores the new controller, bumps CHANGED and returns the previous change and the new version
+/// with the hooks.
+/// changes scheduled by the previous controller are dropped, and so are its commitment and
+/// lease unless the change comes with new ones.
+fn apply_controller_change(
+    storage: &mut dyn Storage,
+    env: &Env,
+    identifier: &Addr,
+    new_controller: &Addr,
+    next_hash: Option<Binary>,
+    lease: Option<Lease>,
+) -> Result<(u64, u64, Vec<SubMsg>), ContractError> {
+    register_identifier(storage, &env.block, identifier)?;
+    update_stats(storage, |stats| stats.controller_changes += 1)?;
+    controllers().save(storage, identifier, new_controller)?;
+    PENDING_CHANGES.remove(storage, identifier);
+    match next_hash {
+        Some(next_hash) => COMMITMENTS.save(storage, identifier, &next_hash)?,
+        None => COMMITMENTS.remove(storage, identifier),
+    }
+    match lease {
+        Some(lease) => leases().save(storage, identifier, &lease)?,
+        None => leases().remove(storage, identifier)?,
+    }
+
+    let changed = CHANGED.may_load(storage, identifier)?.unwrap_or(0);
+    CHANGED.save(storage, identifier, &env.block.height)?;
+    let version = bump_version(storage, &env.block, identifier)?;
+
+    let hooks = hook_submsgs(
+        storage,
+        identifier,
+        DidChange::ChangeController {
+            controller: new_controller.clone(),
+        },
+    )?;
+    Ok((changed, version, hooks))
+}
+
+pub fn try_add_hook(
+    deps: DepsMut,
+    env: Env,
+    info: MessageInfo,
+    identifier: Addr,
+    hook: Addr,
+    on_failure: HookFailure,
+) -> Result<Response, ContractError> {
+    // check controller
+    let loaded_controller = load_controller(deps.storage, &env.block, &identifier)?;
+    only_controller(&info.sender, &identifier, loaded_controller)?;
+
+    if HOOKS.has(deps.storage, (&identifier, &hook)) {
+        return Err(ContractError::HookAlreadyRegistered { hook });
+    }
+    let registered = HOOKS
+        .prefix(&identifier)
+        .keys(deps.storage, None, None, Order::Ascending)
+        .take(MAX_HOOKS)
+        .count();
+    if registered >= MAX_HOOKS {
+        return Err(ContractError::TooManyHooks { max: MAX_HOOKS });
+    }
+    HOOKS.save(deps.storage, (&identifier, &hook), &on_failure)?;
+    let version = bump_version(deps.storage, &env.block, &identifier)?;
+
+    Ok(Response::new()
+        .add_attribute("executeMsg", "addHook")
+        .add_attribute("identifier", identifier)
+        .add_attribute("hook", hook)
+        .add_attribute("version", version.to_string())
+        .add_attribute("from", info.sender))
+}
+
+pub fn try_remove_hook(
+    deps: DepsMut,
+    env: Env,
+    info: MessageInfo,
+    identifier: Addr,
+    hook: Addr,
+) -> Result<Response, ContractError> {
+    // check controller
+    let loaded_controller = load_controller(deps.storage, &env.block, &identifier)?;
+    only_controller(&info.sender, &identifier, loaded_controller)?;
+
+    if !HOOKS.has(deps.storage, (&identifier, &hook)) {
+        return Err(ContractError::HookNotRegistered { hook });
+    }
+    HOOKS.remove(deps.storage, (&identifier, &hook));
+    let version = bump_version(deps.storage, &env.block, &identifier)?;
+
+    Ok(Response::new()
+        .add_attribute("executeMsg", "removeHook")
+        .add_attribute("identifier", identifier)
+        .add_attribute("hook", hook)
+        .add_attribute("version", version.to_string())
+        .add_attribute("from", info.sender))
+}
+
+pub fn try_add_global_hook(
+    deps: DepsMut,
+    info: MessageInfo,
+    hook: Addr,
+    on_failure: HookFailure,
+) -> Result<Response, ContractError> {
+    only_owner(deps.storage, &info.sender)?;
+
+    if GLOBAL_HOOKS.has(deps.storage, &hook) {
+        return Err(ContractError::HookAlreadyRegistered { hook });
+    }
+    GLOBAL_HOOKS.save(deps.storage, &hook, &on_failure)?;
+
+    Ok(Response::new()
+        .add_attribute("executeMsg", "addGlobalHook")
+        .add_attribute("hook", hook)
+        .add_attribute("from", info.sender))
+}
+
+pub fn try_remove_global_hook(
+    deps: DepsMut,
+    info: MessageInfo,
+    hook: Addr,
+) -> Result<Response, ContractError> {
+    only_owner(deps.storage, &info.sender)?;
+
+    if !GLOBAL_HOOKS.has(deps.storage, &hook) {
+        return Err(ContractError::HookNotRegistered { hook });
+    }
+    GLOBAL_HOOKS.remove(deps.storage, &hook);
+
+    Ok(Response::new()
+        .add_attribute("executeMsg", "removeGlobalHook")
+        .add_attribute("hook", hook)
+        .add_attribute("from", info.sender))
+}
+
+#[allow(clippy::too_many_arguments)]
+pub fn try_update_config(
+    deps: DepsMut,
+    info: MessageInfo,
+    paused: Option<bool>,
+    paused_operations: Option<Vec<Operation>>,
+    fees: Option<Vec<OperationFee>>,
+    deposit_per_byte: Option<Coin>,
+    indexed_prefixes: Option<Vec<String>>,
+    max_batch: Option<u32>,
+    max_services: Option<u32>,
+) -> Result<Response, ContractError> {
+    only_owner(deps.storage, &info.sender)?;
+
+    let config = CONFIG.update(deps.storage, |mut config| -> Result<_, ContractError> {
+        if let Some(paused) = paused {
+            config.paused = paused;
+        }
+        if let Some(paused_operations) = paused_operations {
+            config.paused_operations = paused_operations;
+        }
+        if let Some(fees) = fees {
+            validate_fees(&fees)?;
+            config.fees = fees;
+        }
+        if let Some(deposit_per_byte) = deposit_per_byte {
+            config.deposit_per_byte = Some(deposit_per_byte);
+        }
+        // values set before their prefix is indexed are only indexed when set again
+        if let Some(indexed_prefixes) = indexed_prefixes {
+            config.indexed_prefixes = indexed_prefixes;
+        }
+        if let Some(max_batch) = max_batch {
+            config.max_batch = Some(max_batch);
+        }
+        // identifiers above a lowered limit keep their services but cannot add more
+        if let Some(max_services) = max_services {
+            config.max_services = Some(max_services);
+        }
+        Ok(config)
+    })?;
+
+    Ok(Response::new()
+        .add_attribute("executeMsg", "updateConfig")
+        .add_attribute("paused", config.paused.to_string())
+        .add_attribute("from", info.sender))
+}
+
+pub fn try_transfer_ownership(
+    deps: DepsMut,
+    info: MessageInfo,
+    new_owner: Addr,
+) -> Result<Response, ContractError> {
+    only_owner(deps.storage, &info.sender)?;
+
+    CONFIG.update(deps.storage, |mut config| -> StdResult<_> {
+        config.pending_owner = Some(new_owner.clone());
+        Ok(config)
+    })?;
+
+    Ok(Response::new()
+        .add_attribute("executeMsg", "transferOwnership")
+        .add_attribute("pendingOwner", new_owner)
+        .add_attribute("from", info.sender))
+}
+
+pub fn try_accept_ownership(deps: DepsMut, info: MessageInfo) -> Result<Response, ContractError> {
+    CONFIG.update(deps.storage, |mut config| -> Result<_, ContractError> {
+        if config.pending_owner.as_ref() != Some(&info.sender) {
+            return Err(ContractError::Unauthorized {});
+        }
+        config.owner = info.sender.clone();
+        config.pending_owner = None;
+        Ok(config)
+    })?;
+
+    Ok(Response::new()
+        .add_attribute("executeMsg", "acceptOwnership")
+        .add_attribute("owner", info.sender))
+}
+
+pub fn try_withdraw_fees(
+    deps: DepsMut,
+    info: MessageInfo,
+    recipient: Option<Addr>,
+) -> Result<Response, ContractError> {
+    only_owner(deps.storage, &info.sender)?;
+
+    let collected = COLLECTED_FEES
+        .range(deps.storage, None, None, Order::Ascending)
+        .collect::<StdResult<Vec<_>>>()?;
+    let amount: Vec<Coin> = collected
+        .into_iter()
+        .filter(|(_, amount)| !amount.is_zero())
+        .map(|(denom, amount)| Coin { denom, amount })
+        .collect();
+    if amount.is_empty() {
+        return Err(ContractError::NoFees {});
+    }
+    COLLECTED_FEES.clear(deps.storage);
+
+    let recipient = recipient.unwrap_or(info.sender);
+    Ok(Response::new()
+        .add_message(BankMsg::Send {
+            to_address: recipient.to_string(),
+            amount,
+        })
+        .add_attribute("executeMsg", "withdrawFees")
+        .add_attribute("recipient", recipient))
+}
+
+#[cfg_attr(not(feature = "library"), entry_point)]
+pub fn sudo(deps: DepsMut, env: Env, mut msg: SudoMsg) -> Result<Response, ContractError> {
+    let network = CONFIG.load(deps.storage)?.network;
+    normalize_dids(network.as_deref(), msg.dids_mut())?;
+    match msg {
+        SudoMsg::ForceChangeController {
+            identifier,
+            new_controller,
+            proposal_id,
+        } => sudo_force_change_controller(deps, env, identifier, new_controller, proposal_id),
+        SudoMsg::ForceDeactivate {
+            identifier,
+            proposal_id,
+        } => sudo_force_deactivate(deps, env, identifier, proposal_id),
+        SudoMsg::ForceRevokeAttribute {
+            identifier,
+            name,
+            value,
+            proposal_id,
+        } => sudo_force_revoke_attribute(deps, env, identifier, name, value, proposal_id),
+    }
+}
+
+pub fn sudo_force_change_controller(
+    deps: DepsMut,
+    env: Env,
+    identifier: Addr,
+    new_controller: Addr,
+    proposal_id: u64,
+) -> Result<Response, ContractError> {
+    only_active(deps.storage, &identifier)?;
+
+    let previous_controller = load_controller(deps.storage, &env.block, &identifier)?
+        .unwrap_or_else(|| identifier.clone());
+    let (changed, version, _) =
+        apply_controller_change(deps.storage, &env, &identifier, &new_controller, None, None)?;
+    // whatever the compromised key set up cannot override the governance decision
+    GUARDIANS.remove(deps.storage, &identifier);
+    RECOVERIES.remove(deps.storage, &identifier);
+    CONTROLLER_POLICIES.remove(deps.storage, &identifier);
+    let hooks = ignoring_hook_submsgs(
+        deps.storage,
+        &identifier,
+        DidChange::ChangeController {
+            controller: new_controller.clone(),
+        },
+    )?;
+
+    Ok(Response::new().add_submessages(hooks).add_event(
+        Event::new("did_governance")
+            .add_attribute("sudoMsg", "forceChangeController")
+            .add_attribute("proposalId", proposal_id.to_string())
+            .add_attribute("identifier", identifier)
+            .add_attribute("previousController", previous_controller)
+            .add_attribute("controller", new_controller)
+            .add_attribute("previousChange", changed.to_string())
+            .add_attribute("version", version.to_string()),
+    ))
+}
+
+pub fn sudo_force_deactivate(
+    deps: DepsMut,
+    env: Env,
+    identifier: Addr,
+    proposal_id: u64,
+) -> Result<Response, ContractError> {
+    only_active(deps.storage, &identifier)?;
+    register_identifier(deps.storage, &env.block, &identifier)?;
+    DEACTIVATED.save(deps.storage, &identifier, &true)?;
+    update_stats(deps.storage, |stats| stats.deactivated += 1)?;
+
+    let changed = CHANGED.may_load(deps.storage, &identifier)?.unwrap_or(0);
+    CHANGED.save(deps.storage, &identifier, &env.block.height)?;
+    let version = bump_version(deps.storage, &env.block, &identifier)?;
+
+    let hooks = ignoring_hook_submsgs(deps.storage, &identifier, DidChange::Deactivate {})?;
+
+    Ok(Response::new().add_submessages(hooks).add_event(
+        Event::new("did_governance")
+            .add_attribute("sudoMsg", "forceDeactivate")
+            .add_attribute("proposalId", proposal_id.to_string())
+            .add_attribute("identifier", identifier)
+            .add_attribute("previousChange", changed.to_string())
+            .add_attribute("version", version.to_string()),
+    ))
+}
+
+pub fn sudo_force_revoke_attribute(
+    deps: DepsMut,
+    env: Env,
+    identifier: Addr,
+    name: String,
+    value: String,
+    proposal_id: u64,
+) -> Result<Response, ContractError> {
+    let attribute = ATTRIBUTES.may_load(deps.storage, (&identifier, &name))?;
+    if !attribute.map_or(false, |a| a.values.contains(&value)) {
+        return Err(ContractError::InvalidKeyPair {
+            identifier,
+            name,
+            value,
+        });
+    }
+    revoke_stats(deps.storage, &identifier, &name, &value)?;
+    VALIDITIES.save(
+        deps.storage,
+        (&identifier, &name, &value),
+        &Timestamp::from_seconds(0),
+    )?;
+    ATTRIBUTE_INDEX.remove(deps.storage, (&name, &value, &identifier));
+
+    let changed = CHANGED.may_load(deps.storage, &identifier)?.unwrap_or(0);
+    CHANGED.save(deps.storage, &identifier, &env.block.height)?;
+    let version = bump_version(deps.storage, &env.block, &identifier)?;
+
+    let hooks = ignoring_hook_submsgs(
+        deps.storage,
+        &identifier,
+        DidChange::RevokeAttribute {
+            name: name.clone(),
+            value: value.clone(),
+        },
+    )?;
+
+    Ok(Response::new().add_submessages(hooks).add_event(
+        Event::new("did_governance")
+            .add_attribute("sudoMsg", "forceRevokeAttribute")
+            .add_attribute("proposalId", proposal_id.to_string())
+            .add_attribute("identifier", identifier)
+            .add_attribute("name", name)
+            .add_attribute("value", value)
+            .add_attribute("validTo", 0.to_string())
+            .add_attribute("previousChange", changed.to_string())
+            .add_attribute("version", version.to_string()),
+    ))
+}
+
+#[cfg_attr(not(feature = "library"), entry_point)]
+pub fn reply(_deps: DepsMut, _env: Env, msg: Reply) -> Result<Response, ContractError> {
+    match msg.id {
+        // only failed hooks with HookFailure::Ignore are replied to
+        HOOK_REPLY_ID => match msg.result {
+            SubMsgResult::Err(err) => Ok(Response::new()
+                .add_attribute("replyMsg", "hookFailed")
+                .add_attribute("error", err)),
+            SubMsgResult::Ok(_) => Ok(Response::new()),
+        },
+        id => Err(ContractError::UnknownReplyId { id }),
+    }
+}
+
+#[cfg_attr(not(feature = "library"), entry_point)]
+pub fn query(deps: Deps, env: Env, mut msg: QueryMsg) -> StdResult<Binary> {
+    let network = CONFIG.load(deps.storage)?.network;
+    normalize_dids(network.as_deref(), msg.dids_mut())
+        .map_err(|err| StdError::generic_err(err.to_string()))?;
+
+    match msg {
+        QueryMsg::Controller { identifier } => to_binary(&query_controller(deps, env, identifier)?),
+        QueryMsg::Attribute { identifier, name } => {
+            to_binary(&query_attribute(deps, identifier, name)?)
+        }
+        QueryMsg::ValidTo {
+            identifier,
+            name,
+            value,
+        } => to_binary(&query_valid_to(deps, identifier, name, value)?),
+        QueryMsg::CanExecute { sender, funds, msg } => {
+            to_binary(&query_can_execute(deps, env, sender, funds, *msg)?)
+        }
+        QueryMsg::Resolve { identifier } => {
+            let document = resolve(deps, &env, identifier.clone())?;
+            to_binary(&ResolveResponse {
+                did_document_metadata: document_metadata(deps, &env, &identifier, &document)?,
+                document,
+            })
+        }
+        QueryMsg::Metadata { identifier } => {
+            let document = resolve(deps, &env, identifier.clone())?;
+            to_binary(&MetadataResponse {
+                did_document_metadata: document_metadata(deps, &env, &identifier, &document)?,
+            })
+        }
+        QueryMsg::KeysForRelationship {
+            identifier,
+            relationship,
+        } => to_binary(&query_keys_for_relationship(
+            deps,
+            env,
+            identifier,
+            relationship,
+        )?),
+        QueryMsg::VerifySignature {
+            identifier,
+            message,
+            signature,
+            relationship,
+        } => to_binary(&query_verify_signature(
+            deps,
+            env,
+            identifier,
+            message,
+            signature,
+            relationship,
+        )?),
+        QueryMsg::Dereference { did_url } => to_binary(&query_dereference(deps, env, did_url)?),
+        QueryMsg::Services { identifier } => to_binary(&ServicesResponse {
+            services: load_services(
+                deps,
+                CONFIG.load(deps.storage)?.network.as_deref(),
+                &identifier,
+            )?,
+        }),
+        QueryMsg::Aliases { identifier } => to_binary(&query_aliases(deps, identifier)?),
+        QueryMsg::Controllers { identifiers } => {
+            to_binary(&query_controllers(deps, env, identifiers)?)
+        }
+        QueryMsg::Attributes { keys } => to_binary(&query_attributes(deps, keys)?),
+        QueryMsg::ValidTos { keys } => to_binary(&query_valid_tos(deps, keys)?),
+        QueryMsg::ResolveMany { identifiers } => {
+            to_binary(&query_resolve_many(deps, env, identifiers)?)
+        }
+        QueryMsg::Changed { identifier } => to_binary(&query_changed(deps, identifier)?),
+        QueryMsg::Version { identifier } => to_binary(&query_version(deps, identifier)?),
+        QueryMsg::Hooks { identifier } => to_binary(&query_hooks(deps, identifier)?),
+        QueryMsg::GlobalHooks {} => to_binary(&query_global_hooks(deps)?),
+        QueryMsg::Config {} => to_binary(&query_config(deps)?),
+        QueryMsg::Fees {} => to_binary(&query_fees(deps)?),
+        QueryMsg::Deposits { identifier } => to_binary(&query_deposits(deps, identifier)?),
+        QueryMsg::Deactivated { identifier } => to_binary(&query_deactivated(deps, identifier)?),
+        QueryMsg::ControllerPolicy { identifier } => {
+            to_binary(&query_controller_policy(deps, identifier)?)
+        }
+        QueryMsg::PendingChange { identifier } => {
+            to_binary(&query_pending_change(deps, identifier)?)
+        }
+        QueryMsg::Commitment { identifier } => to_binary(&query_commitment(deps, identifier)?),
+        QueryMsg::Lease { identifier } => to_binary(&query_lease(deps, identifier)?),
+        QueryMsg::AllIdentifiers { start_after, limit } => {
+            to_binary(&query_all_identifiers(deps, start_after, limit)?)
+        }
+        QueryMsg::Stats {} => to_binary(&query_stats(deps)?),
+        QueryMsg::IdentifiersByAttribute {
+            name,
+            value,
+            start_after,
+            limit,
+        } => to_binary(&query_identifiers_by_attribute(
+            deps,
+            env,
+            name,
+            value,
+            start_after,
+            limit,
+        )?),
+        QueryMsg::IdentifiersByController {
+            controller,
+            start_after,
+            limit,
+        } => to_binary(&query_identifiers_by_controller(
+            deps,
+            env,
+            controller,
+            start_after,
+            limit,
+        )?),
+        QueryMsg::Guardians { identifier } => to_binary(&query_guardians(deps, identifier)?),
+        QueryMsg::Recovery { identifier } => to_binary(&query_recovery(deps, identifier)?),
+    }
+}
+
+fn query_controller(deps: Deps, env: Env, identifier: Addr) -> StdResult<ControllerResponse> {
+    let loaded_controller = load_controller(deps.storage, &env.block, &identifier)?;
+    match loaded_controller {
+        Some(v) => Ok(ControllerResponse { controller: v }),
+        None => Ok(ControllerResponse {
+            controller: identifier,
+        }),
+    }
+}
+
+/// identifiers the controller controls at the block time. control moves to the fallback without
+/// a transaction, so both the controller index and the fallbacks of leases are checked.
+fn query_identifiers_by_controller(
+    deps: Deps,
+    env: Env,
+    controller: Addr,
+    start_after: Option<Addr>,
+    limit: Option<u32>,
+) -> StdResult<IdentifiersResponse> {
+    let limit = limit.unwrap_or(DEFAULT_LIMIT).min(MAX_LIMIT) as usize;
+    let controlled = |identifier: StdResult<Addr>| -> StdResult<Option<Addr>> {
+        let identifier = identifier?;
+        let loaded_controller = load_controller(deps.storage, &env.block, &identifier)?;
+        Ok((loaded_controller.as_ref() == Some(&controller)).then_some(identifier))
+    };
+
+    let mut identifiers = controllers()
+        .idx
+        .controller
+        .prefix(controller.clone())
+        .keys(
+            deps.storage,
+            start_after.as_ref().map(Bound::exclusive),
+            None,
+            Order::Ascending,
+        )
+        .map(controlled)
+        .filter_map(StdResult::transpose)
+        .take(limit)
+        .collect::<StdResult<Vec<Addr>>>()?;
+    let fallbacks = leases()
+        .idx
+        .fallback
+        .prefix(controller.clone())
+        .keys(
+            deps.storage,
+            start_after.as_ref().map(Bound::exclusive),
+            None,
+            Order::Ascending,
+        )
+        .map(controlled)
+        .filter_map(StdResult::transpose)
+        .take(limit)
+        .collect::<StdResult<Vec<Addr>>>()?;
+
+    identifiers.extend(fallbacks);
+    identifiers.sort();
+    identifiers.dedup();
+    identifiers.truncate(limit);
+    Ok(IdentifiersResponse { identifiers })
+}
+
+fn query_all_identifiers(
+    deps: Deps,
+    start_after: Option<Addr>,
+    limit: Option<u32>,
+) -> StdResult<AllIdentifiersResponse> {
+    let limit = limit.unwrap_or(DEFAULT_LIMIT).min(MAX_LIMIT) as usize;
+    let start = start_after.as_ref().map(Bound::exclusive);
+
+    let identifiers = IDENTIFIERS
+        .range(deps.storage, start, None, Order::Ascending)
+        .take(limit)
+        .map(|item| {
+            let (identifier, registration) = item?;
+            Ok(IdentifierInfo {
+                identifier,
+                created_height: registration.height,
+                created_time: registration.time,
+            })
+        })
+        .collect::<StdResult<Vec<IdentifierInfo>>>()?;
+    Ok(AllIdentifiersResponse { identifiers })
+}
+
+fn query_stats(deps: Deps) -> StdResult<StatsResponse> {
+    let stats = STATS.may_load(deps.storage)?.unwrap_or_default();
+    Ok(StatsResponse {
+        identifiers: stats.identifiers,
+        attributes: stats.attributes,
+        active_values: stats.active_values,
+        deactivated: stats.deactivated,
+        controller_changes: stats.controller_changes,
+    })
+}
+
+/// expired values stay in the index until they are pruned, they are left out
+fn query_identifiers_by_attribute(
+    deps: Deps,
+    env: Env,
+    name: String,
+    value: String,
+    start_after: Option<Addr>,
+    limit: Option<u32>,
+) -> StdResult<IdentifiersResponse> {
+    let limit = limit.unwrap_or(DEFAULT_LIMIT).min(MAX_LIMIT) as usize;
+    let start = start_after.as_ref().map(Bound::exclusive);
+
+    let mut identifiers = vec![];
+    for item in
+        ATTRIBUTE_INDEX
+            .prefix((&name, &value))
+            .keys(deps.storage, start, None, Order::Ascending)
+    {
+        if identifiers.len() == limit {
+            break;
+        }
+        let identifier = item?;
+        let valid_to = VALIDITIES.may_load(deps.storage, (&identifier, &name, &value))?;
+        if valid_to.map_or(false, |valid_to| valid_to > env.block.time) {
+            identifiers.push(identifier);
+        }
+    }
+    Ok(IdentifiersResponse { identifiers })
+}
+
+fn query_attribute(deps: Deps, identifier: Addr, name: String) -> StdResult<AttributeResponse> {
+    let loaded_attribute = ATTRIBUTES.may_load(deps.storage, (&identifier, &name))?;
+    match loaded_attribute {
+        Some(v) => Ok(AttributeResponse { values: v.values }),
+        None => Ok(AttributeResponse { values: vec![] }),
+    }
+}
+
+fn query_valid_to(
+    deps: Deps,
+    identifier: Addr,
+    name: String,
+    value: String,
+) -> StdResult<ValidToResponse> {
+    let loaded_attribute = VALIDITIES.may_load(deps.storage, (&identifier, &name, &value))?;
+    match loaded_attribute {
+        Some(v) => Ok(ValidToResponse { valid_to: v }),
+        None => Ok(ValidToResponse {
+            valid_to: Timestamp::from_seconds(0),
+        }),
+    }
+}
+
+/// dry runs execute on top of the contract storage, the writes are dropped with the storage.
+/// hook submessages are not run.
+fn query_can_execute(
+    deps: Deps,
+    env: Env,
+    sender: Addr,
+    funds: Vec<Coin>,
+    msg: ExecuteMsg,
+) -> StdResult<CanExecuteResponse> {
+    let mut storage = DryRunStorage::new(deps.storage);
+    let deps_mut = DepsMut {
+        storage: &mut storage,
+        api: deps.api,
+        querier: deps.querier,
+    };
+    let info = MessageInfo { sender, funds };
+
+    Ok(match execute(deps_mut, env, info, msg) {
+        Ok(_) => CanExecuteResponse {
+            allowed: true,
+            failed_check: None,
+            error: None,
+        },
+        Err(err) => CanExecuteResponse {
+            allowed: false,
+            failed_check: Some(failed_check(&err)),
+            error: Some(err.to_string()),
+        },
+    })
+}
+
+fn failed_check(err: &ContractError) -> FailedCheck {
+    match err {
+        ContractError::Unauthorized {} => FailedCheck::Unauthorized,
+        ContractError::Deactivated { .. } => FailedCheck::Deactivated,
+        ContractError::Paused {} => FailedCheck::Paused,
+        ContractError::InvalidKeyPair { .. } => FailedCheck::InvalidKeyPair,
+        ContractError::InsufficientFee { .. } => FailedCheck::InsufficientFee,
+        ContractError::InsufficientDeposit { .. } => FailedCheck::InsufficientDeposit,
+        _ => FailedCheck::Other,
+    }
+}
+
+/// builds the DID document from the controller, the verification methods, the delegates, the
+/// services and the attribute values valid at the block.
+/// the account key of the controller is the default `#controller` verification method.
+fn resolve(deps: Deps, env: &Env, identifier: Addr) -> StdResult<DidDocument> {
+    let network = CONFIG.load(deps.storage)?.network;
+    let network = network.as_deref();
+    let controller = load_controller(deps.storage, &env.block, &identifier)?
+        .unwrap_or_else(|| identifier.clone());
+
+    let mut attributes = vec![];
+    for item in ATTRIBUTES
+        .prefix(&identifier)
+        .range(deps.storage, None, None, Order::Ascending)
+    {
+        let (name, attribute) = item?;
+        for value in attribute.values {
+            let valid_to = VALIDITIES.may_load(deps.storage, (&identifier, &name, &value))?;
+            if let Some(valid_to) = valid_to.filter(|valid_to| valid_to > &env.block.time) {
+                attributes.push(DocumentAttribute {
+                    name: name.clone(),
+                    value,
+                    valid_to,
+                });
+            }
+        }
+    }
+
+    let mut verification_methods = vec![];
+    for item in
+        VERIFICATION_METHODS
+            .prefix(&identifier)
+            .range(deps.storage, None, None, Order::Ascending)
+    {
+        let (id, method) = item?;
+        if method.valid_to > env.block.time {
+            let relationships =
+                key_relationships(deps, env, &identifier, &id, &method, &Relationship::ALL)?;
+            verification_methods.push(method_info(
+                network,
+                &identifier,
+                &id,
+                method,
+                relationships,
+            ));
+        }
+    }
+    // the account key of the controller, unless a method took its fragment
+    if !VERIFICATION_METHODS.has(deps.storage, (&identifier, CONTROLLER_FRAGMENT)) {
+        if let Some((key_type, key)) = query_account_key(&deps.querier, &controller) {
+            // a lease hands the identifier to its fallback when it ends
+            let valid_to = leases()
+                .may_load(deps.storage, &identifier)?
+                .map(|lease| lease.expires)
+                .filter(|expires| expires > &env.block.time);
+            let relationships = Relationship::ALL
+                .iter()
+                .filter(|relationship| **relationship != Relationship::KeyAgreement)
+                .map(|relationship| RelationshipValidity {
+                    relationship: *relationship,
+                    valid_to,
+                })
+                .collect();
+            verification_methods.insert(
+                0,
+                VerificationMethodInfo {
+                    id: format!(
+                        "{}#{}",
+                        format_did(network, &identifier),
+                        CONTROLLER_FRAGMENT
+                    ),
+                    key_type,
+                    controller: format_did(network, &controller),
+                    public_key_multibase: to_multibase(key_type, &key),
+                    relationships,
+                    valid_to,
+                },
+            );
+        }
+    }
+
+    let also_known_as = load_aliases(deps, &identifier)?
+        .into_iter()
+        .filter(|(_, verified)| *verified)
+        .map(|(alias, _)| format_did(network, &alias))
+        .collect();
+
+    Ok(DidDocument {
+        also_known_as,
+        delegates: load_delegates(deps, env, network, &identifier, &Relationship::ALL)?,
+        deactivated: DEACTIVATED.has(deps.storage, &identifier),
+        services: load_services(deps, network, &identifier)?,
+        id: format_did(network, &identifier),
+        controller: format_did(network, &controller),
+        verification_methods,
+        attributes,
+    })
+}
+
+/// metadata of the resolved document, deactivated documents have no next update
+fn document_metadata(
+    deps: Deps,
+    env: &Env,
+    identifier: &Addr,
+    document: &DidDocument,
+) -> StdResult<DidDocumentMetadata> {
+    let registration = IDENTIFIERS.may_load(deps.storage, identifier)?;
+    let lease_end = leases()
+        .may_load(deps.storage, identifier)?
+        .map(|lease| lease.expires);
+    let method_ends = document.verification_methods.iter().flat_map(|method| {
+        method.valid_to.into_iter().chain(
+            method
+                .relationships
+                .iter()
+                .filter_map(|relationship| relationship.valid_to),
+        )
+    });
+    let delegate_ends = document.delegates.iter().flat_map(|delegate| {
+        delegate
+            .relationships
+            .iter()
+            .filter_map(|relationship| relationship.valid_to)
+    });
+    let next_update = document
+        .attributes
+        .iter()
+        .map(|attribute| attribute.valid_to)
+        .chain(method_ends)
+        .chain(delegate_ends)
+        .chain(lease_end)
+        // ended leases and expired values waiting to be pruned change nothing anymore
+        .filter(|valid_to| valid_to > &env.block.time)
+        .min()
+        .filter(|_| !document.deactivated);
+
+    Ok(DidDocumentMetadata {
+        created: registration.as_ref().map(|registration| registration.time),
+        created_height: registration.map(|registration| registration.height),
+        updated: UPDATED.may_load(deps.storage, identifier)?,
+        deactivated: document.deactivated,
+        version_id: VERSIONS.may_load(deps.storage, identifier)?.unwrap_or(0),
+        next_update,
+    })
+}
+
+fn method_info(
+    network: Option<&str>,
+    identifier: &Addr,
+    id: &str,
+    method: VerificationMethod,
+    relationships: Vec<RelationshipValidity>,
+) -> VerificationMethodInfo {
+    VerificationMethodInfo {
+        id: format!("{}#{}", format_did(network, identifier), id),
+        key_type: method.key_type,
+        controller: format_did(network, &method.controller),
+        public_key_multibase: to_multibase(method.key_type, &method.public_key),
+        relationships,
+        valid_to: Some(method.valid_to),
+    }
+}
+
+/// relationships of the key still valid at the block, none outlives the key
+fn key_relationships(
+    deps: Deps,
+    env: &Env,
+    identifier: &Addr,
+    id: &str,
+    method: &VerificationMethod,
+    relationships: &[Relationship],
+) -> StdResult<Vec<RelationshipValidity>> {
+    let mut valid = vec![];
+    for relationship in relationships {
+        let valid_to =
+            KEY_RELATIONSHIPS.may_load(deps.storage, (identifier, relationship.as_str(), id))?;
+        if let Some(valid_to) = valid_to.filter(|valid_to| valid_to > &env.block.time) {
+            valid.push(RelationshipValidity {
+                relationship: *relationship,
+                valid_to: Some(valid_to.min(method.valid_to)),
+            });
+        }
+    }
+    Ok(valid)
+}
+
+/// delegates with the relationships still valid at the block, ordered by delegate
+fn delegate_relationships(
+    deps: Deps,
+    env: &Env,
+    identifier: &Addr,
+    relationships: &[Relationship],
+) -> StdResult<BTreeMap<Addr, Vec<RelationshipValidity>>> {
+    let mut delegates: BTreeMap<Addr, Vec<RelationshipValidity>> = BTreeMap::new();
+    for relationship in relationships {
+        for item in DELEGATES.prefix((identifier, relationship.as_str())).range(
+            deps.storage,
+            None,
+            None,
+            Order::Ascending,
+        ) {
+            let (delegate, valid_to) = item?;
+            if valid_to > env.block.time {
+                delegates
+                    .entry(delegate)
+                    .or_default()
+                    .push(RelationshipValidity {
+                        relationship: *relationship,
+                        valid_to: Some(valid_to),
+                    });
+            }
+        }
+    }
+    Ok(delegates)
+}
+
+/// delegates for documents, by DID
+fn load_delegates(
+    deps: Deps,
+    env: &Env,
+    network: Option<&str>,
+    identifier: &Addr,
+    relationships: &[Relationship],
+) -> StdResult<Vec<DelegateInfo>> {
+    Ok(
+        delegate_relationships(deps, env, identifier, relationships)?
+            .into_iter()
+            .map(|(delegate, relationships)| DelegateInfo {
+                delegate: format_did(network, &delegate),
+                relationships,
+            })
+            .collect(),
+    )
+}
+
+/// keys holding the relationship at the block, with its validity
+fn relationship_keys(
+    deps: Deps,
+    env: &Env,
+    identifier: &Addr,
+    relationship: Relationship,
+) -> StdResult<Vec<(String, VerificationMethod, Vec<RelationshipValidity>)>> {
+    let mut keys = vec![];
+    for item in KEY_RELATIONSHIPS
+        .prefix((identifier, relationship.as_str()))
+        .keys(deps.storage, None, None, Order::Ascending)
+    {
+        let id = item?;
+        let method = match VERIFICATION_METHODS.may_load(deps.storage, (identifier, &id))? {
+            Some(method) if method.valid_to > env.block.time => method,
+            _ => continue,
+        };
+        let relationships =
+            key_relationships(deps, env, identifier, &id, &method, &[relationship])?;
+        if !relationships.is_empty() {
+            keys.push((id, method, relationships));
+        }
+    }
+    Ok(keys)
+}
+
+fn query_keys_for_relationship(
+    deps: Deps,
+    env: Env,
+    identifier: Addr,
+    relationship: Relationship,
+) -> StdResult<KeysForRelationshipResponse> {
+    let network = CONFIG.load(deps.storage)?.network;
+    let keys = relationship_keys(deps, &env, &identifier, relationship)?
+        .into_iter()
+        .map(|(id, method, relationships)| {
+            method_info(network.as_deref(), &identifier, &id, method, relationships)
+        })
+        .collect();
+
+    Ok(KeysForRelationshipResponse {
+        keys,
+        delegates: load_delegates(deps, &env, network.as_deref(), &identifier, &[relationship])?,
+    })
+}
+
+fn query_verify_signature(
+    deps: Deps,
+    env: Env,
+    identifier: Addr,
+    message: Binary,
+    signature: Binary,
+    relationship: Relationship,
+) -> StdResult<VerifySignatureResponse> {
+    let verified = |method| {
+        Ok(VerifySignatureResponse {
+            method: Some(method),
+        })
+    };
+    // deactivated identifiers have no valid keys
+    if DEACTIVATED.has(deps.storage, &identifier) {
+        return Ok(VerifySignatureResponse { method: None });
+    }
+
+    let network = CONFIG.load(deps.storage)?.network;
+    for (id, method, _) in relationship_keys(deps, &env, &identifier, relationship)? {
+        if verify_signature(
+            deps.api,
+            method.key_type,
+            &method.public_key,
+            &message,
+            &signature,
+        ) {
+            return verified(SignatureMethod::Key {
+                id: format!("{}#{}", format_did(network.as_deref(), &identifier), id),
+            });
+        }
+    }
+
+    // purposes of ERC1056 keys, sigAuth keys also authenticate
+    let purposes: &[&str] = match relationship {
+        Relationship::Authentication => &["sigAuth"],
+        Relationship::AssertionMethod => &["veriKey", "sigAuth"],
+        _ => &[],
+    };
+    let attributes = ATTRIBUTES.prefix(&identifier).range(
+        deps.storage,
+        Some(Bound::inclusive("did/pub/")),
+        None,
+        Order::Ascending,
+    );
+    for item in attributes {
+        let (name, attribute) = item?;
+        if !name.starts_with("did/pub/") {
+            break;
+        }
+        for value in attribute.values {
+            let (key_type, purpose, key) = match decode_key_attribute(&name, &value) {
+                Some(decoded) => decoded,
+                None => continue,
+            };
+            let valid_to = VALIDITIES.may_load(deps.storage, (&identifier, &name, &value))?;
+            let valid = valid_to.map_or(false, |valid_to| valid_to > env.block.time);
+            if valid
+                && purposes.contains(&purpose)
+                && verify_signature(deps.api, key_type, &key, &message, &signature)
+            {
+                return verified(SignatureMethod::Attribute { name, value });
+            }
+        }
+    }
+
+    // accounts sign but hold no key to agree on
+    if relationship != Relationship::KeyAgreement {
+        let controller = load_controller(deps.storage, &env.block, &identifier)?
+            .unwrap_or_else(|| identifier.clone());
+        if signed_by_account(deps.api, &controller, &message, &signature) {
+            return verified(SignatureMethod::Controller {
+                did: format_did(network.as_deref(), &controller),
+            });
+        }
+    }
+
+    for delegate in delegate_relationships(deps, &env, &identifier, &[relationship])?.keys() {
+        if signed_by_account(deps.api, delegate, &message, &signature) {
+            return verified(SignatureMethod::Delegate {
+                did: format_did(network.as_deref(), delegate),
+            });
+        }
+    }
+
+    Ok(VerifySignatureResponse { method: None })
+}
+
+fn query_dereference(deps: Deps, env: Env, did_url: String) -> StdResult<DereferenceResponse> {
+    let failed = |error| {
+        Ok(DereferenceResponse {
+            content: None,
+            error: Some(error),
+        })
+    };
+    let network = CONFIG.load(deps.storage)?.network;
+    let url = match parse_did_url(network.as_deref(), &did_url) {
+        Ok(url) => url,
+        Err(reason) => return failed(DereferenceError::InvalidDidUrl { reason }),
+    };
+    let identifier = match deps.api.addr_validate(url.identifier.as_str()) {
+        Ok(identifier) => identifier,
+        Err(_) => {
+            return failed(DereferenceError::InvalidDidUrl {
+                reason: format!("invalid identifier {}", url.identifier),
+            })
+        }
+    };
+    if !url.path.is_empty() {
+        return failed(DereferenceError::UnsupportedPath { path: url.path });
+    }
+    let unsupported = url
+        .query
+        .iter()
+        .find(|(name, _)| name != "service" && name != "relativeRef");
+    if let Some((name, _)) = unsupported {
+        return failed(DereferenceError::UnsupportedParameter { name: name.clone() });
+    }
+    let relative_ref = url.param("relativeRef");
+    if relative_ref.is_some() && url.param("service").is_none() {
+        return failed(DereferenceError::InvalidDidUrl {
+            reason: String::from("relativeRef without service"),
+        });
+    }
+
+    let document = resolve(deps, &env, identifier)?;
+    let content = match (url.param("service"), &url.fragment) {
+        (Some(service), _) => {
+            let id = format!("{}#{}", document.id, service);
+            let endpoint = match document.services.into_iter().find(|s| s.id == id) {
+                Some(found) => found.service_endpoint,
+                None => {
+                    return failed(DereferenceError::UnknownService {
+                        service: service.to_string(),
+                    })
+                }
+            };
+            // the first endpoint of a set, sets are never empty
+            let mut endpoint = match endpoint {
+                ServiceEndpoint::Uri(uri) => uri,
+                ServiceEndpoint::Set(uris) => uris.into_iter().next().unwrap_or_default(),
+                ServiceEndpoint::Map(map) => map.uri,
+            };
+            if let Some(relative_ref) = relative_ref {
+                if relative_ref.starts_with('/') {
+                    endpoint.truncate(endpoint.trim_end_matches('/').len());
+                }
+                endpoint.push_str(relative_ref);
+            }
+            if let Some(fragment) = &url.fragment {
+                endpoint = format!("{}#{}", endpoint, fragment);
+            }
+            DereferencedContent::Url(endpoint)
+        }
+        (None, Some(fragment)) => {
+            let id = format!("{}#{}", document.id, fragment);
+            if let Some(method) = document
+                .verification_methods
+                .into_iter()
+                .find(|m| m.id == id)
+            {
+                DereferencedContent::VerificationMethod(method)
+            } else if let Some(service) = document.services.into_iter().find(|s| s.id == id) {
+                DereferencedContent::Service(service)
+            } else {
+                return failed(DereferenceError::UnknownFragment {
+                    fragment: fragment.clone(),
+                });
+            }
+        }
+        (None, None) => DereferencedContent::Document(Box::new(document)),
+    };
+
+    Ok(DereferenceResponse {
+        content: Some(content),
+        error: None,
+    })
+}
+
+/// aliases the identifier asserted, with whether the other side asserted them as well
+fn load_aliases(deps: Deps, identifier: &Addr) -> StdResult<Vec<(Addr, bool)>> {
+    ALIASES
+        .prefix(identifier)
+        .keys(deps.storage, None, None, Order::Ascending)
+        .map(|alias| {
+            let alias = alias?;
+            let verified = ALIASES.has(deps.storage, (&alias, identifier));
+            Ok((alias, verified))
+        })
+        .collect()
+}
+
+fn query_aliases(deps: Deps, identifier: Addr) -> StdResult<AliasesResponse> {
+    let network = CONFIG.load(deps.storage)?.network;
+    let aliases = load_aliases(deps, &identifier)?
+        .into_iter()
+        .map(|(alias, verified)| AliasInfo {
+            alias: format_did(network.as_deref(), &alias),
+            verified,
+        })
+        .collect();
+    Ok(AliasesResponse {
+        aliases,
+        nonce: NONCE.may_load(deps.storage, &identifier)?.unwrap_or(0),
+    })
+}
+
+/// services of the identifier with their ids expanded to DID#fragment
+fn load_services(deps: Deps, network: Option<&str>, identifier: &Addr) -> StdResult<Vec<Service>> {
+    let did = format_did(network, identifier);
+    SERVICES
+        .prefix(identifier)
+        .range(deps.storage, None, None, Order::Ascending)
+        .map(|item| {
+            let (id, service) = item?;
+            Ok(Service {
+                id: format!("{}#{}", did, id),
+                ..service
+            })
+        })
+        .collect()
+}
+
+fn only_batch(deps: Deps, len: usize) -> StdResult<()> {
+    let config = CONFIG.load(deps.storage)?;
+    let max_batch = config.max_batch.unwrap_or(DEFAULT_MAX_BATCH);
+    if len > max_batch as usize {
+        return Err(StdError::generic_err(format!(
+            "Batch of {} exceeds the maximum of {}",
+            len, max_batch
+        )));
+    }
+    Ok(())
+}
+
+fn query_controllers(
+    deps: Deps,
+    env: Env,
+    identifiers: Vec<Addr>,
+) -> StdResult<ControllersResponse> {
+    only_batch(deps, identifiers.len())?;
+    let controllers = identifiers
+        .into_iter()
+        .map(|identifier| {
+            let found = IDENTIFIERS.has(deps.storage, &identifier);
+            let controller = query_controller(deps, env.clone(), identifier.clone())?.controller;
+            Ok(ControllerItem {
+                identifier,
+                controller,
+                found,
+            })
+        })
+        .collect::<StdResult<Vec<ControllerItem>>>()?;
+    Ok(ControllersResponse { controllers })
+}
+
+fn query_attributes(deps: Deps, keys: Vec<(Addr, String)>) -> StdResult<AttributesResponse> {
+    only_batch(deps, keys.len())?;
+    let attributes = keys
+        .into_iter()
+        .map(|(identifier, name)| {
+            let loaded_attribute = ATTRIBUTES.may_load(deps.storage, (&identifier, &name))?;
+            Ok(AttributeItem {
+                found: loaded_attribute.is_some(),
+                values: loaded_attribute.map(|a| a.values).unwrap_or_default(),
+                identifier,
+                name,
+            })
+        })
+        .collect::<StdResult<Vec<AttributeItem>>>()?;
+    Ok(AttributesResponse { attributes })
+}
+
+fn query_valid_tos(deps: Deps, keys: Vec<(Addr, String, String)>) -> StdResult<ValidTosResponse> {
+    only_batch(deps, keys.len())?;
+    let valid_tos = keys
+        .into_iter()
+        .map(|(identifier, name, value)| {
+            let loaded_valid_to =
+                VALIDITIES.may_load(deps.storage, (&identifier, &name, &value))?;
+            Ok(ValidToItem {
+                found: loaded_valid_to.is_some(),
+                valid_to: loaded_valid_to.unwrap_or_else(|| Timestamp::from_seconds(0)),
+                identifier,
+                name,
+                value,
+            })
+        })
+        .collect::<StdResult<Vec<ValidToItem>>>()?;
+    Ok(ValidTosResponse { valid_tos })
+}
+
+fn query_resolve_many(
+    deps: Deps,
+    env: Env,
+    identifiers: Vec<Addr>,
+) -> StdResult<ResolveManyResponse> {
+    only_batch(deps, identifiers.len())?;
+    let documents = identifiers
+        .into_iter()
+        .map(|identifier| {
+            let document = resolve(deps, &env, identifier.clone())?;
+            Ok(DocumentItem {
+                found: IDENTIFIERS.has(deps.storage, &identifier),
+                did_document_metadata: document_metadata(deps, &env, &identifier, &document)?,
+                document,
+                identifier,
+            })
+        })
+        .collect::<StdResult<Vec<DocumentItem>>>()?;
+    Ok(ResolveManyResponse { documents })
+}
+
+fn query_changed(deps: Deps, identifier: Addr) -> StdResult<ChangedResponse> {
+    let loaded_changed = CHANGED.may_load(deps.storage, &identifier)?;
+    match loaded_changed {
+        Some(v) => Ok(ChangedResponse { block: v }),
+        None => Ok(ChangedResponse { block: 0 }),
+    }
+}
+
+fn query_version(deps: Deps, identifier: Addr) -> StdResult<VersionResponse> {
+    let version = VERSIONS.may_load(deps.storage, &identifier)?.unwrap_or(0);
+    Ok(VersionResponse { version })
+}
+
+fn query_hooks(deps: Deps, identifier: Addr) -> StdResult<HooksResponse> {
+    let hooks = HOOKS
+        .prefix(&identifier)
+        .range(deps.storage, None, None, Order::Ascending)
+        .map(|item| item.map(|(hook, on_failure)| HookResponse { hook, on_failure }))
+        .collect::<StdResult<_>>()?;
+    Ok(HooksResponse { hooks })
+}
+
+fn query_global_hooks(deps: Deps) -> StdResult<HooksResponse> {
+    let hooks = GLOBAL_HOOKS
+        .range(deps.storage, None, None, Order::Ascending)
+        .map(|item| item.map(|(hook, on_failure)| HookResponse { hook, on_failure }))
+        .collect::<StdResult<_>>()?;
+    Ok(HooksResponse { hooks })
+}
+
+fn query_deactivated(deps: Deps, identifier: Addr) -> StdResult<DeactivatedResponse> {
+    let deactivated = DEACTIVATED.has(deps.storage, &identifier);
+    Ok(DeactivatedResponse { deactivated })
+}
+
+fn query_controller_policy(deps: Deps, identifier: Addr) -> StdResult<ControllerPolicyResponse> {
+    let policy = CONTROLLER_POLICIES.may_load(deps.storage, &identifier)?;
+    Ok(ControllerPolicyResponse { policy })
+}
+
+fn query_pending_change(deps: Deps, identifier: Addr) -> StdResult<PendingChangeResponse> {
+    let loaded_pending = PENDING_CHANGES.may_load(deps.storage, &identifier)?;
+    Ok(PendingChangeResponse {
+        pending: loaded_pending.map(|v| PendingChangeInfo {
+            change: v.change,
+            executable_at: v.executable_at,
+        }),
+    })
+}
+
+fn query_commitment(deps: Deps, identifier: Addr) -> StdResult<CommitmentResponse> {
+    let next_hash = COMMITMENTS.may_load(deps.storage, &identifier)?;
+    Ok(CommitmentResponse { next_hash })
+}
+
+fn query_lease(deps: Deps, identifier: Addr) -> StdResult<LeaseResponse> {
+    let lease = leases().may_load(deps.storage, &identifier)?;
+    Ok(LeaseResponse { lease })
+}
+
+fn query_guardians(deps: Deps, identifier: Addr) -> StdResult<GuardiansResponse> {
+    let loaded_guardians = GUARDIANS.may_load(deps.storage, &identifier)?;
+    match loaded_guardians {
+        Some(v) => Ok(GuardiansResponse {
+            guardians: v.guardians,
+            threshold: v.threshold,
+            delay: v.delay,
+        }),
+        None => Ok(GuardiansResponse {
+            guardians: vec![],
+            threshold: 0,
+            delay: 0,
+        }),
+    }
+}
+
+fn query_recovery(deps: Deps, identifier: Addr) -> StdResult<RecoveryResponse> {
+    let loaded_recovery = RECOVERIES.may_load(deps.storage, &identifier)?;
+    Ok(RecoveryResponse {
+        recovery: loaded_recovery.map(|v| RecoveryInfo {
+            new_controller: v.new_controller,
+            approvals: v.approvals,
+            executable_at: v.executable_at,
+        }),
+    })
+}
+
+fn query_config(deps: Deps) -> StdResult<ConfigResponse> {
+    let config = CONFIG.load(deps.storage)?;
+    Ok(ConfigResponse {
+        owner: config.owner,
+        pending_owner: config.pending_owner,
+        paused: config.paused,
+        paused_operations: config.paused_operations,
+        indexed_prefixes: config.indexed_prefixes,
+        max_batch: config.max_batch.unwrap_or(DEFAULT_MAX_BATCH),
+        max_services: config.max_services.unwrap_or(DEFAULT_MAX_SERVICES),
+        network: config.network,
+    })
+}
+
+fn query_fees(deps: Deps) -> StdResult<FeesResponse> {
+    let config = CONFIG.load(deps.storage)?;
+    let collected = COLLECTED_FEES
+        .range(deps.storage, None, None, Order::Ascending)
+        .map(|item| item.map(|(denom, amount)| Coin { denom, amount }))
+        .collect::<StdResult<_>>()?;
+    Ok(FeesResponse {
+        fees: config.fees,
+        collected,
+    })
+}
+
+fn query_deposits(deps: Deps, identifier: Addr) -> StdResult<DepositsResponse> {
+    let deposits = DEPOSITS
+        .sub_prefix(&identifier)
+        .range(deps.storage, None, None, Order::Ascending)
+        .map(|item| {
+            item.map(|((name, value), amount)| DepositResponse {
+                name,
+                value,
+                amount,
+            })
+        })
+        .collect::<StdResult<Vec<_>>>()?;
+
+    let mut total = vec![];
+    for deposit in &deposits {
+        add_coin(&mut total, deposit.amount.clone());
+    }
+    Ok(DepositsResponse { deposits, total })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::auth::{encode_field, ACCOUNT_QUERY_PATH};
+    use crate::helper::{alias_message, controller_hash, get_attribute_value};
+    use crate::msg::{DidChangedExecuteMsg, DidChangedHookMsg, Jwk, ServiceEndpointMap};
+    use crate::state::Stats;
+    use cosmwasm_std::testing::{
+        mock_dependencies_with_balance, mock_env, mock_info, MockApi, MockQuerier, MockStorage,
+    };
+    use cosmwasm_std::{
+        coins, from_binary, from_slice, Api, CanonicalAddr, ContractResult, CosmosMsg, Empty,
+        OwnedDeps, Querier, QuerierResult, QueryRequest, RecoverPubkeyError, ReplyOn, SystemResult,
+        VerificationError, WasmMsg,
+    };
+    use cw_storage_plus::Map;
+    use std::marker::PhantomData;
+
+    #[test]
+    fn proper_initialization() {
+        let mut deps = mock_dependencies_with_balance(&coins(2, "token"));
+
+        let msg = InstantiateMsg {
+            owner: None,
+            fees: vec![],
+            deposit_per_byte: None,
+            network: None,
+        };
+        let info = mock_info("creator", &coins(1000, "earth"));
+
+        // we can just call .unwrap() to assert this was a success
+        let res = instantiate(deps.as_mut(), mock_env(), info, msg).unwrap();
+        assert_eq!(0, res.messages.len());
+    }
+
+    #[test]
+    fn controller() {
+        let mut deps = mock_dependencies_with_balance(&coins(2, "token"));
+        let msg = InstantiateMsg {
+            owner: None,
+            fees: vec![],
+            deposit_per_byte: None,
+            network: None,
+        };
+        let info = mock_info("creator", &coins(2, "token"));
+        let _res = instantiate(deps.as_mut(), mock_env(), info, msg).unwrap();
+
+        let identifier = String::from("identifier0001");
+
+        let res = query(
+            deps.as_ref(),
+            mock_env(),
+            QueryMsg::Controller {
+                identifier: Addr::unchecked(&identifier),
+            },
+        )
+        .unwrap();
+        let value: ControllerResponse = from_binary(&res).unwrap();
+        assert_eq!(identifier, value.controller);
+
+        let res = query(
+            deps.as_ref(),
+            mock_env(),
+            QueryMsg::Changed {
+                identifier: Addr::unchecked(&identifier),
+            },
+        )
+        .unwrap();
+        let value: ChangedResponse = from_binary(&res).unwrap();
+        assert_eq!(0, value.block);
+    }
+
+    #[test]
+    fn change_controller() {
+        let mut deps = mock_dependencies_with_balance(&coins(2, "token"));
+        let msg = InstantiateMsg {
+            owner: None,
+            fees: vec![],
+            deposit_per_byte: None,
+            network: None,
+        };
+        let info = mock_info("creator", &coins(2, "token"));
+        let _res = instantiate(deps.as_mut(), mock_env(), info, msg).unwrap();
+
+        let identifier1 = String::from("identifier0001");
+        let controller1 = String::from("addr0001");
+
+        // only the original identifier address can change the controller at the first time
+        let auth_info = mock_info("identifier0001", &coins(2, "token"));
+
+        let msg = ExecuteMsg::ChangeController {
+            identifier: Addr::unchecked(&identifier1),
+            new_controller: Addr::unchecked(&controller1),
+            next_hash: None,
+            lease: None,
+            expected_version: None,
+        };
+
+        let _res = execute(deps.as_mut(), mock_env(), auth_info, msg).unwrap();
+
+        let res = query(
+            deps.as_ref(),
+            mock_env(),
+            QueryMsg::Controller {
+                identifier: Addr::unchecked(&identifier1),
+            },
+        )
+        .unwrap();
+        let value: ControllerResponse = from_binary(&res).unwrap();
+        assert_eq!(controller1, value.controller);
+
+        // only the controller address can change the controller
+        let auth_info = mock_info("addr0001", &coins(2, "token"));
+        let controller2 = String::from("addr0002");
+
+        let msg = ExecuteMsg::ChangeController {
+            identifier: Addr::unchecked(&identifier1),
+            new_controller: Addr::unchecked(&controller2),
+            next_hash: None,
+            lease: None,
+            expected_version: None,
+        };
+
+        let _res = execute(deps.as_mut(), mock_env(), auth_info, msg).unwrap();
+
+        let res = query(
+            deps.as_ref(),
+            mock_env(),
+            QueryMsg::Controller {
+                identifier: Addr::unchecked(&identifier1),
+            },
+        )
+        .unwrap();
+        let value: ControllerResponse = from_binary(&res).unwrap();
+        assert_eq!(controller2, value.controller);
+
+        let res = query(
+            deps.as_ref(),
+            mock_env(),
+            QueryMsg::Changed {
+                identifier: Addr::unchecked(&identifier1),
+            },
+        )
+        .unwrap();
+        let value: ChangedResponse = from_binary(&res).unwrap();
+        assert_ne!(0, value.block);
+    }
+
+    #[test]
+    fn change_controller_by_attacker() {
+        let mut deps = mock_dependencies_with_balance(&coins(2, "token"));
+        let msg = InstantiateMsg {
+            owner: None,
+            fees: vec![],
+            deposit_per_byte: None,
+            network: None,
+        };
+        let info = mock_info("creator", &coins(2, "token"));
+        let _res = instantiate(deps.as_mut(), mock_env(), info, msg).unwrap();
+
+        let identifier1 = String::from("identifier0001");
+        let controller1 = String::from("addr0001");
+
+        // only the original identifier address can change the controller at the first time
+        let auth_info = mock_info("attacker", &coins(2, "token"));
+
+        let msg = ExecuteMsg::ChangeController {
+            identifier: Addr::unchecked(&identifier1),
+            new_controller: Addr::unchecked(&controller1),
+            next_hash: None,
+            lease: None,
+            expected_version: None,
+        };
+
+        let err = execute(deps.as_mut(), mock_env(), auth_info, msg).unwrap_err();
+        assert_eq!(err, ContractError::Unauthorized {});
+
+        let res = query(
+            deps.as_ref(),
+            mock_env(),
+            QueryMsg::Controller {
+                identifier: Addr::unchecked(&identifier1),
+            },
+        )
+        .unwrap();
+        let value: ControllerResponse = from_binary(&res).unwrap();
+        assert_eq!(identifier1, value.controller);
+
+        // only the original identifier address can change the controller at the first time
+        let auth_info = mock_info("identifier0001", &coins(2, "token"));
+
+        let msg = ExecuteMsg::ChangeController {
+            identifier: Addr::unchecked(&identifier1),
+            new_controller: Addr::unchecked(&controller1),
+            next_hash: None,
+            lease: None,
+            expected_version: None,
+        };
+
+        let _res = execute(deps.as_mut(), mock_env(), auth_info, msg).unwrap();
+
+        let res = query(
+            deps.as_ref(),
+            mock_env(),
+            QueryMsg::Controller {
+                identifier: Addr::unchecked(&identifier1),
+            },
+        )
+        .unwrap();
+        let value: ControllerResponse = from_binary(&res).unwrap();
+        assert_eq!(controller1, value.controller);
+
+        let auth_info = mock_info("attacker", &coins(2, "token"));
+
+        let msg = ExecuteMsg::ChangeController {
+            identifier: Addr::unchecked(&identifier1),
+            new_controller: Addr::unchecked(&identifier1),
+            next_hash: None,
+            lease: None,
+            expected_version: None,
+        };
+
+        let err = execute(deps.as_mut(), mock_env(), auth_info, msg).unwrap_err();
+        assert_eq!(err, ContractError::Unauthorized {});
+
+        let res = query(
+            deps.as_ref(),
+            mock_env(),
+            QueryMsg::Controller {
+                identifier: Addr::unchecked(&identifier1),
+            },
+        )
+        .unwrap();
+        let value: ControllerResponse = from_binary(&res).unwrap();
+        assert_eq!(controller1, value.controller);
+    }
+
+    #[test]
+    fn set_attribute() {
+        let mut deps = mock_dependencies_with_balance(&coins(2, "token"));
+        let msg = InstantiateMsg {
+            owner: None,
+            fees: vec![],
+            deposit_per_byte: None,
+            network: None,
+        };
+        let info = mock_info("creator", &coins(2, "token"));
+        let _res = instantiate(deps.as_mut(), mock_env(), info, msg).unwrap();
+
+        let identifier1 = String::from("identifier0001");
+
+        // only the original identifier address can change the controller at the first time
+        let auth_info = mock_info("identifier0001", &coins(2, "token"));
+
+        let msg = ExecuteMsg::SetAttribute {
+            identifier: Addr::unchecked(&identifier1),
+            name: String::from("identifier_name"),
+            value: String::from("abc"),
+            validity: 0,
+            expected_version: None,
+        };
+
+        let res = execute(deps.as_mut(), mock_env(), auth_info.clone(), msg).unwrap();
+
+        // check name attribute
+        let name = get_attribute_value(res.clone(), "name");
+
+        assert_eq!(name, "identifier_name");
+
+        // check value attribute
+        let value = get_attribute_value(res.clone(), "value");
+
+        assert_eq!(value, "abc");
+
+        // set attribute again
+        let msg = ExecuteMsg::SetAttribute {
+            identifier: Addr::unchecked(&identifier1),
+            name: String::from("identifier_name"),
+            value: String::from("def"),
+            validity: 0,
+            expected_version: None,
+        };
+
+        let res = execute(deps.as_mut(), mock_env(), auth_info.clone(), msg).unwrap();
+
+        // check name attribute
+        let name = get_attribute_value(res.clone(), "name");
+        assert_eq!(name, "identifier_name");
+
+        // check value attribute
+        let value = get_attribute_value(res.clone(), "value");
+        assert_eq!(value, "def");
+
+        let res = query(
+            deps.as_ref(),
+            mock_env(),
+            QueryMsg::Attribute {
+                identifier: Addr::unchecked(&identifier1),
+                name: String::from("identifier_name"),
+            },
+        )
+        .unwrap();
+
+        let value: AttributeResponse = from_binary(&res).unwrap();
+        assert_eq!(value.values, ["abc", "def"]);
+
+        // check validTo
+        let res = query(
+            deps.as_ref(),
+            mock_env(),
+            QueryMsg::ValidTo {
+                identifier: Addr::unchecked(&identifier1),
+                name: String::from("identifier_name"),
+                value: String::from("abc"),
+            },
+        )
+        .unwrap();
+
+        let value: ValidToResponse = from_binary(&res).unwrap();
+        assert_ne!(value.valid_to.seconds(), 0);
+
+        // set attribute again
+        let msg = ExecuteMsg::SetAttribute {
+            identifier: Addr::unchecked(&identifier1),
+            name: String::from("identifier_name"),
+            value: String::from("abc"),
+            validity: 0,
+            expected_version: None,
+        };
+
+        let res = execute(deps.as_mut(), mock_env(), auth_info.clone(), msg).unwrap();
+
+        // check name attribute
+        let name = get_attribute_value(res.clone(), "name");
+        assert_eq!(name, "identifier_name");
+
+        // check value attribute
+        let value = get_attribute_value(res.clone(), "value");
+        assert_eq!(value, "abc");
+
+        let res = query(
+            deps.as_ref(),
+            mock_env(),
+            QueryMsg::Attribute {
+                identifier: Addr::unchecked(&identifier1),
+                name: String::from("identifier_name"),
+            },
+        )
+        .unwrap();
+
+        let value: AttributeResponse = from_binary(&res).unwrap();
+        assert_eq!(value.values, ["abc", "def"]);
+
+        // check validTo
+        let res = query(
+            deps.as_ref(),
+            mock_env(),
+            QueryMsg::ValidTo {
+                identifier: Addr::unchecked(&identifier1),
+                name: String::from("identifier_name"),
+                value: String::from("def"),
+            },
+        )
+        .unwrap();
+
+        let value: ValidToResponse = from_binary(&res).unwrap();
+        assert_ne!(value.valid_to.seconds(), 0);
+    }
+
+    #[test]
+    fn set_attribute_by_attacker() {
+        let mut deps = mock_dependencies_with_balance(&coins(2, "token"));
+        let msg = InstantiateMsg {
+            owner: None,
+            fees: vec![],
+            deposit_per_byte: None,
+            network: None,
+        };
+        let info = mock_info("creator", &coins(2, "token"));
+        let _res = instantiate(deps.as_mut(), mock_env(), info, msg).unwrap();
+
+        let identifier1 = String::from("identifier0001");
+
+        // only the original identifier address can change the controller at the first time
+        let auth_info = mock_info("attacker", &coins(2, "token"));
+
+        let msg = ExecuteMsg::SetAttribute {
+            identifier: Addr::unchecked(&identifier1),
+            name: String::from("identifier_name"),
+            value: String::from("abc"),
+            validity: 0,
+            expected_version: None,
+        };
+
+        let err = execute(deps.as_mut(), mock_env(), auth_info, msg).unwrap_err();
+        assert_eq!(err, ContractError::Unauthorized {});
+    }
+
+    #[test]
+    fn revoke_attribute() {
+        let mut deps = mock_dependencies_with_balance(&coins(2, "token"));
+        let msg = InstantiateMsg {
+            owner: None,
+            fees: vec![],
+            deposit_per_byte: None,
+            network: None,
+        };
+        let info = mock_info("creator", &coins(2, "token"));
+        let _res = instantiate(deps.as_mut(), mock_env(), info, msg).unwrap();
+
+        let identifier1 = String::from("identifier0001");
+
+        // only the original identifier address can change the controller at the first time
+        let auth_info = mock_info("identifier0001", &coins(2, "token"));
+
+        let msg = ExecuteMsg::SetAttribute {
+            identifier: Addr::unchecked(&identifier1),
+            name: String::from("identifier_name"),
+            value: String::from("abc"),
+            validity: 0,
+            expected_version: None,
+        };
+
+        let res = execute(deps.as_mut(), mock_env(), auth_info, msg).unwrap();
+
+        // check name attribute
+        let name = get_attribute_value(res.clone(), "name");
+
+        assert_eq!(name, "identifier_name");
+
+        // check value attribute
+        let value = get_attribute_value(res.clone(), "value");
+
+        assert_eq!(value, "abc");
+
+        //revoke_attribute test
+        let msg = ExecuteMsg::RevokeAttribute {
+            identifier: Addr::unchecked(&identifier1),
+            name: String::from("identifier_name"),
+            value: String::from("abc"),
+            expected_version: None,
+        };
+
+        // only the original identifier address can change the controller at the first time
+        let auth_info = mock_info("identifier0001", &coins(2, "token"));
+        let res = execute(deps.as_mut(), mock_env(), auth_info, msg).unwrap();
+
+        // check name attribute
+        let name = get_attribute_value(res.clone(), "name");
+
+        assert_eq!(name, "identifier_name");
+
+        // check value attribute
+        let value = get_attribute_value(res.clone(), "value");
+
+        assert_eq!(value, "abc");
+
+        // check validity attribute
+        let validity = get_attribute_value(res.clone(), "validTo");
+
+        assert_eq!(validity, "0");
+
+        // check validTo
+        let res = query(
+            deps.as_ref(),
+            mock_env(),
+            QueryMsg::ValidTo {
+                identifier: Addr::unchecked(&identifier1),
+                name: String::from("identifier_name"),
+                value: String::from("abc"),
+            },
+        )
+        .unwrap();
+
+        let value: ValidToResponse = from_binary(&res).unwrap();
+        assert_eq!(value.valid_to.seconds(), 0);
+
+        //revoke_attribute test again with wrong value
+        let msg = ExecuteMsg::RevokeAttribute {
+            identifier: Addr::unchecked(&identifier1),
+            name: String::from("identifier_name"),
+            value: String::from("xyz"),
+            expected_version: None,
+        };
+
+        let auth_info = mock_info("identifier0001", &coins(2, "token"));
+        let err = execute(deps.as_mut(), mock_env(), auth_info, msg).unwrap_err();
+        assert_eq!(
+            err,
+            ContractError::InvalidKeyPair {
+                identifier: Addr::unchecked(&identifier1),
+                name: String::from("identifier_name"),
+                value: String::from("xyz"),
+            }
+        );
+    }
+
+    #[test]
+    fn revoke_attribute_by_attacker() {
+        let mut deps = mock_dependencies_with_balance(&coins(2, "token"));
+        let msg = InstantiateMsg {
+            owner: None,
+            fees: vec![],
+            deposit_per_byte: None,
+            network: None,
+        };
+        let info = mock_info("creator", &coins(2, "token"));
+        let _res = instantiate(deps.as_mut(), mock_env(), info, msg).unwrap();
+
+        let identifier1 = String::from("identifier0001");
+
+        // only the original identifier address can change the controller at the first time
+        let auth_info = mock_info("identifier0001", &coins(2, "token"));
+
+        let msg = ExecuteMsg::SetAttribute {
+            identifier: Addr::unchecked(&identifier1),
+            name: String::from("identifier_name"),
+            value: String::from("abc"),
+            validity: 0,
+            expected_version: None,
+        };
+
+        let res = execute(deps.as_mut(), mock_env(), auth_info, msg).unwrap();
+
+        // check name attribute
+        let name = get_attribute_value(res.clone(), "name");
+
+        assert_eq!(name, "identifier_name");
+
+        // check value attribute
+        let value = get_attribute_value(res.clone(), "value");
+
+        assert_eq!(value, "abc");
+
+        //revoke_attribute test
+        let msg = ExecuteMsg::RevokeAttribute {
+            identifier: Addr::unchecked(&identifier1),
+            name: String::from("identifier_name"),
+            value: String::from("xyz"),
+            expected_version: None,
+        };
 
         // only the original identifier address can change the controller at the first time
+        let auth_info = mock_info("attacker", &coins(2, "token"));
+        let err = execute(deps.as_mut(), mock_env(), auth_info, msg).unwrap_err();
+        assert_eq!(err, ContractError::Unauthorized {});
+    }
+
+    #[test]
+    fn hooks() {
+        let mut deps = mock_dependencies_with_balance(&coins(2, "token"));
+        let msg = InstantiateMsg {
+            owner: None,
+            fees: vec![],
+            deposit_per_byte: None,
+            network: None,
+        };
+        let info = mock_info("creator", &coins(2, "token"));
+        let _res = instantiate(deps.as_mut(), mock_env(), info, msg).unwrap();
+
+        let identifier1 = String::from("identifier0001");
+
+        // only the controller can register hooks for the identifier
+        let msg = ExecuteMsg::AddHook {
+            identifier: Addr::unchecked(&identifier1),
+            hook: Addr::unchecked("hook0001"),
+            on_failure: HookFailure::Abort,
+            expected_version: None,
+        };
+        let auth_info = mock_info("attacker", &[]);
+        let err = execute(deps.as_mut(), mock_env(), auth_info, msg.clone()).unwrap_err();
+        assert_eq!(err, ContractError::Unauthorized {});
+
+        let auth_info = mock_info("identifier0001", &[]);
+        let _res = execute(deps.as_mut(), mock_env(), auth_info.clone(), msg.clone()).unwrap();
+
+        let err = execute(deps.as_mut(), mock_env(), auth_info.clone(), msg).unwrap_err();
+        assert_eq!(
+            err,
+            ContractError::HookAlreadyRegistered {
+                hook: Addr::unchecked("hook0001")
+            }
+        );
+
+        // only the admin can register global hooks
+        let msg = ExecuteMsg::AddGlobalHook {
+            hook: Addr::unchecked("hook0002"),
+            on_failure: HookFailure::Ignore,
+        };
+        let err = execute(deps.as_mut(), mock_env(), auth_info.clone(), msg.clone()).unwrap_err();
+        assert_eq!(err, ContractError::Unauthorized {});
+
+        let admin_info = mock_info("creator", &[]);
+        let _res = execute(deps.as_mut(), mock_env(), admin_info, msg).unwrap();
+
+        let res = query(
+            deps.as_ref(),
+            mock_env(),
+            QueryMsg::Hooks {
+                identifier: Addr::unchecked(&identifier1),
+            },
+        )
+        .unwrap();
+        let value: HooksResponse = from_binary(&res).unwrap();
+        assert_eq!(
+            value.hooks,
+            vec![HookResponse {
+                hook: Addr::unchecked("hook0001"),
+                on_failure: HookFailure::Abort,
+            }]
+        );
+
+        let res = query(deps.as_ref(), mock_env(), QueryMsg::GlobalHooks {}).unwrap();
+        let value: HooksResponse = from_binary(&res).unwrap();
+        assert_eq!(
+            value.hooks,
+            vec![HookResponse {
+                hook: Addr::unchecked("hook0002"),
+                on_failure: HookFailure::Ignore,
+            }]
+        );
+
+        // every hook is notified with a submessage
+        let msg = ExecuteMsg::ChangeController {
+            identifier: Addr::unchecked(&identifier1),
+            new_controller: Addr::unchecked("addr0001"),
+            next_hash: None,
+            lease: None,
+            expected_version: None,
+        };
+        let res = execute(deps.as_mut(), mock_env(), auth_info.clone(), msg).unwrap();
+        assert_eq!(2, res.messages.len());
+        assert_eq!(ReplyOn::Never, res.messages[0].reply_on);
+        assert_eq!(ReplyOn::Error, res.messages[1].reply_on);
+        assert_eq!(HOOK_REPLY_ID, res.messages[1].id);
+
+        match &res.messages[0].msg {
+            CosmosMsg::Wasm(WasmMsg::Execute {
+                contract_addr, msg, ..
+            }) => {
+                assert_eq!(contract_addr, "hook0001");
+                let hook_msg: DidChangedExecuteMsg = from_binary(msg).unwrap();
+                assert_eq!(
+                    hook_msg,
+                    DidChangedExecuteMsg::DidChangedHook(DidChangedHookMsg {
+                        identifier: Addr::unchecked(&identifier1),
+                        change: DidChange::ChangeController {
+                            controller: Addr::unchecked("addr0001"),
+                        },
+                    })
+                );
+            }
+            _ => panic!("unexpected message"),
+        }
+
+        // the new controller manages the hooks
+        let msg = ExecuteMsg::RemoveHook {
+            identifier: Addr::unchecked(&identifier1),
+            hook: Addr::unchecked("hook0001"),
+            expected_version: None,
+        };
+        let err = execute(deps.as_mut(), mock_env(), auth_info, msg.clone()).unwrap_err();
+        assert_eq!(err, ContractError::Unauthorized {});
+
+        let auth_info = mock_info("addr0001", &[]);
+        let _res = execute(deps.as_mut(), mock_env(), auth_info.clone(), msg.clone()).unwrap();
+
+        let err = execute(deps.as_mut(), mock_env(), auth_info.clone(), msg).unwrap_err();
+        assert_eq!(
+            err,
+            ContractError::HookNotRegistered {
+                hook: Addr::unchecked("hook0001")
+            }
+        );
+
+        let msg = ExecuteMsg::SetAttribute {
+            identifier: Addr::unchecked(&identifier1),
+            name: String::from("identifier_name"),
+            value: String::from("abc"),
+            validity: 0,
+            expected_version: None,
+        };
+        let res = execute(deps.as_mut(), mock_env(), auth_info, msg).unwrap();
+        assert_eq!(1, res.messages.len());
+    }
+
+    #[test]
+    fn hook_failure_is_ignored() {
+        let mut deps = mock_dependencies_with_balance(&coins(2, "token"));
+
+        let msg = Reply {
+            id: HOOK_REPLY_ID,
+            result: SubMsgResult::Err(String::from("hook failed")),
+        };
+        let res = reply(deps.as_mut(), mock_env(), msg).unwrap();
+        let error = get_attribute_value(res, "error");
+        assert_eq!(error, "hook failed");
+
+        let msg = Reply {
+            id: 100,
+            result: SubMsgResult::Err(String::from("hook failed")),
+        };
+        let err = reply(deps.as_mut(), mock_env(), msg).unwrap_err();
+        assert_eq!(err, ContractError::UnknownReplyId { id: 100 });
+    }
+
+    #[test]
+    fn pause() {
+        let mut deps = mock_dependencies_with_balance(&coins(2, "token"));
+        let msg = InstantiateMsg {
+            owner: None,
+            fees: vec![],
+            deposit_per_byte: None,
+            network: None,
+        };
+        let info = mock_info("creator", &coins(2, "token"));
+        let _res = instantiate(deps.as_mut(), mock_env(), info, msg).unwrap();
+
+        let identifier1 = String::from("identifier0001");
         let auth_info = mock_info("identifier0001", &coins(2, "token"));
+        let owner_info = mock_info("creator", &[]);
+
+        // only the owner can pause the contract
+        let msg = ExecuteMsg::UpdateConfig {
+            paused: Some(true),
+            paused_operations: None,
+            fees: None,
+            deposit_per_byte: None,
+            indexed_prefixes: None,
+            max_batch: None,
+            max_services: None,
+        };
+        let err = execute(deps.as_mut(), mock_env(), auth_info.clone(), msg.clone()).unwrap_err();
+        assert_eq!(err, ContractError::Unauthorized {});
+
+        let _res = execute(deps.as_mut(), mock_env(), owner_info.clone(), msg).unwrap();
+
+        let set_attribute = ExecuteMsg::SetAttribute {
+            identifier: Addr::unchecked(&identifier1),
+            name: String::from("identifier_name"),
+            value: String::from("abc"),
+            validity: 0,
+            expected_version: None,
+        };
+        let err = execute(
+            deps.as_mut(),
+            mock_env(),
+            auth_info.clone(),
+            set_attribute.clone(),
+        )
+        .unwrap_err();
+        assert_eq!(err, ContractError::Paused {});
+
+        // pause a single operation
+        let msg = ExecuteMsg::UpdateConfig {
+            paused: Some(false),
+            paused_operations: Some(vec![Operation::ChangeController]),
+            fees: None,
+            deposit_per_byte: None,
+            indexed_prefixes: None,
+            max_batch: None,
+            max_services: None,
+        };
+        let _res = execute(deps.as_mut(), mock_env(), owner_info, msg).unwrap();
+
+        let res = query(deps.as_ref(), mock_env(), QueryMsg::Config {}).unwrap();
+        let value: ConfigResponse = from_binary(&res).unwrap();
+        assert!(!value.paused);
+        assert_eq!(value.paused_operations, vec![Operation::ChangeController]);
+
+        let _res = execute(deps.as_mut(), mock_env(), auth_info.clone(), set_attribute).unwrap();
+
+        let msg = ExecuteMsg::ChangeController {
+            identifier: Addr::unchecked(&identifier1),
+            new_controller: Addr::unchecked("addr0001"),
+            next_hash: None,
+            lease: None,
+            expected_version: None,
+        };
+        let err = execute(deps.as_mut(), mock_env(), auth_info, msg).unwrap_err();
+        assert_eq!(err, ContractError::Paused {});
+    }
+
+    #[test]
+    fn transfer_ownership() {
+        let mut deps = mock_dependencies_with_balance(&coins(2, "token"));
+        let msg = InstantiateMsg {
+            owner: Some(Addr::unchecked("owner0001")),
+            fees: vec![],
+            deposit_per_byte: None,
+            network: None,
+        };
+        let info = mock_info("creator", &coins(2, "token"));
+        let _res = instantiate(deps.as_mut(), mock_env(), info, msg).unwrap();
+
+        let msg = ExecuteMsg::TransferOwnership {
+            new_owner: Addr::unchecked("owner0002"),
+        };
+        let err = execute(
+            deps.as_mut(),
+            mock_env(),
+            mock_info("creator", &[]),
+            msg.clone(),
+        )
+        .unwrap_err();
+        assert_eq!(err, ContractError::Unauthorized {});
+
+        let _res = execute(deps.as_mut(), mock_env(), mock_info("owner0001", &[]), msg).unwrap();
+
+        let res = query(deps.as_ref(), mock_env(), QueryMsg::Config {}).unwrap();
+        let value: ConfigResponse = from_binary(&res).unwrap();
+        assert_eq!(value.owner, Addr::unchecked("owner0001"));
+        assert_eq!(value.pending_owner, Some(Addr::unchecked("owner0002")));
+
+        // only the pending owner can accept
+        let msg = ExecuteMsg::AcceptOwnership {};
+        let err = execute(
+            deps.as_mut(),
+            mock_env(),
+            mock_info("attacker", &[]),
+            msg.clone(),
+        )
+        .unwrap_err();
+        assert_eq!(err, ContractError::Unauthorized {});
+
+        let _res = execute(deps.as_mut(), mock_env(), mock_info("owner0002", &[]), msg).unwrap();
+
+        let res = query(deps.as_ref(), mock_env(), QueryMsg::Config {}).unwrap();
+        let value: ConfigResponse = from_binary(&res).unwrap();
+        assert_eq!(value.owner, Addr::unchecked("owner0002"));
+        assert_eq!(value.pending_owner, None);
+    }
+
+    #[test]
+    fn fees() {
+        let mut deps = mock_dependencies_with_balance(&coins(2, "token"));
+        let msg = InstantiateMsg {
+            owner: None,
+            fees: vec![OperationFee {
+                operation: Operation::SetAttribute,
+                fee: Coin::new(100, "ugrano"),
+            }],
+            deposit_per_byte: None,
+            network: None,
+        };
+        let info = mock_info("creator", &coins(2, "token"));
+        let _res = instantiate(deps.as_mut(), mock_env(), info, msg).unwrap();
+
+        let identifier1 = String::from("identifier0001");
+        let set_attribute = ExecuteMsg::SetAttribute {
+            identifier: Addr::unchecked(&identifier1),
+            name: String::from("identifier_name"),
+            value: String::from("abc"),
+            validity: 0,
+            expected_version: None,
+        };
+
+        let auth_info = mock_info("identifier0001", &coins(99, "ugrano"));
+        let err = execute(deps.as_mut(), mock_env(), auth_info, set_attribute.clone()).unwrap_err();
+        assert_eq!(
+            err,
+            ContractError::InsufficientFee {
+                required: Coin::new(100, "ugrano")
+            }
+        );
+
+        let auth_info = mock_info("identifier0001", &coins(100, "ugrano"));
+        let _res = execute(deps.as_mut(), mock_env(), auth_info, set_attribute).unwrap();
+
+        // operations without a fee stay free
+        let msg = ExecuteMsg::RevokeAttribute {
+            identifier: Addr::unchecked(&identifier1),
+            name: String::from("identifier_name"),
+            value: String::from("abc"),
+            expected_version: None,
+        };
+        let auth_info = mock_info("identifier0001", &[]);
+        let _res = execute(deps.as_mut(), mock_env(), auth_info, msg).unwrap();
+
+        let res = query(deps.as_ref(), mock_env(), QueryMsg::Fees {}).unwrap();
+        let value: FeesResponse = from_binary(&res).unwrap();
+        assert_eq!(value.collected, vec![Coin::new(100, "ugrano")]);
+
+        // only the owner can withdraw
+        let msg = ExecuteMsg::WithdrawFees { recipient: None };
+        let err = execute(
+            deps.as_mut(),
+            mock_env(),
+            mock_info("attacker", &[]),
+            msg.clone(),
+        )
+        .unwrap_err();
+        assert_eq!(err, ContractError::Unauthorized {});
+
+        let res = execute(
+            deps.as_mut(),
+            mock_env(),
+            mock_info("creator", &[]),
+            msg.clone(),
+        )
+        .unwrap();
+        assert_eq!(
+            res.messages[0].msg,
+            CosmosMsg::Bank(BankMsg::Send {
+                to_address: String::from("creator"),
+                amount: coins(100, "ugrano"),
+            })
+        );
+
+        let err = execute(deps.as_mut(), mock_env(), mock_info("creator", &[]), msg).unwrap_err();
+        assert_eq!(err, ContractError::NoFees {});
+
+        // fees are configurable by the owner
+        let msg = ExecuteMsg::UpdateConfig {
+            paused: None,
+            paused_operations: None,
+            fees: Some(vec![
+                OperationFee {
+                    operation: Operation::ChangeController,
+                    fee: Coin::new(1, "ugrano"),
+                },
+                OperationFee {
+                    operation: Operation::ChangeController,
+                    fee: Coin::new(2, "ugrano"),
+                },
+            ]),
+            deposit_per_byte: None,
+            indexed_prefixes: None,
+            max_batch: None,
+            max_services: None,
+        };
+        let err = execute(deps.as_mut(), mock_env(), mock_info("creator", &[]), msg).unwrap_err();
+        assert_eq!(
+            err,
+            ContractError::DuplicateFee {
+                operation: Operation::ChangeController
+            }
+        );
+    }
+
+    #[test]
+    fn deposits() {
+        let mut deps = mock_dependencies_with_balance(&coins(2, "token"));
+        let msg = InstantiateMsg {
+            owner: None,
+            fees: vec![OperationFee {
+                operation: Operation::SetAttribute,
+                fee: Coin::new(100, "ugrano"),
+            }],
+            deposit_per_byte: Some(Coin::new(10, "ugrano")),
+            network: None,
+        };
+        let info = mock_info("creator", &coins(2, "token"));
+        let _res = instantiate(deps.as_mut(), mock_env(), info, msg).unwrap();
+
+        let identifier1 = String::from("identifier0001");
+
+        // "service" + "github" are 13 bytes
+        let set_attribute = ExecuteMsg::SetAttribute {
+            identifier: Addr::unchecked(&identifier1),
+            name: String::from("service"),
+            value: String::from("github"),
+            validity: 1000,
+            expected_version: None,
+        };
+        let auth_info = mock_info("identifier0001", &coins(230, "ugrano"));
+        let _res = execute(deps.as_mut(), mock_env(), auth_info, set_attribute.clone()).unwrap();
+
+        // extending the validity locks no new deposit
+        let auth_info = mock_info("identifier0001", &coins(100, "ugrano"));
+        let _res = execute(deps.as_mut(), mock_env(), auth_info, set_attribute).unwrap();
+
+        let res = query(
+            deps.as_ref(),
+            mock_env(),
+            QueryMsg::Deposits {
+                identifier: Addr::unchecked(&identifier1),
+            },
+        )
+        .unwrap();
+        let value: DepositsResponse = from_binary(&res).unwrap();
+        assert_eq!(
+            value.deposits,
+            vec![DepositResponse {
+                name: String::from("service"),
+                value: String::from("github"),
+                amount: Coin::new(130, "ugrano"),
+            }]
+        );
+        assert_eq!(value.total, coins(130, "ugrano"));
+
+        // valid values are not pruned
+        let prune_attribute = ExecuteMsg::PruneAttribute {
+            identifier: Addr::unchecked(&identifier1),
+            name: String::from("service"),
+            expected_version: None,
+        };
+        let auth_info = mock_info("identifier0001", &[]);
+        let err = execute(
+            deps.as_mut(),
+            mock_env(),
+            auth_info.clone(),
+            prune_attribute.clone(),
+        )
+        .unwrap_err();
+        assert_eq!(err, ContractError::NothingToPrune {});
+
+        let msg = ExecuteMsg::RevokeAttribute {
+            identifier: Addr::unchecked(&identifier1),
+            name: String::from("service"),
+            value: String::from("github"),
+            expected_version: None,
+        };
+        let _res = execute(deps.as_mut(), mock_env(), auth_info.clone(), msg).unwrap();
+
+        let res = execute(deps.as_mut(), mock_env(), auth_info, prune_attribute).unwrap();
+        assert_eq!(
+            res.messages[0].msg,
+            CosmosMsg::Bank(BankMsg::Send {
+                to_address: identifier1.clone(),
+                amount: coins(130, "ugrano"),
+            })
+        );
+
+        let res = query(
+            deps.as_ref(),
+            mock_env(),
+            QueryMsg::Attribute {
+                identifier: Addr::unchecked(&identifier1),
+                name: String::from("service"),
+            },
+        )
+        .unwrap();
+        let value: AttributeResponse = from_binary(&res).unwrap();
+        assert!(value.values.is_empty());
+
+        let res = query(
+            deps.as_ref(),
+            mock_env(),
+            QueryMsg::Deposits {
+                identifier: Addr::unchecked(&identifier1),
+            },
+        )
+        .unwrap();
+        let value: DepositsResponse = from_binary(&res).unwrap();
+        assert!(value.deposits.is_empty());
+
+        // the fees stay withdrawable apart from the deposits
+        let res = query(deps.as_ref(), mock_env(), QueryMsg::Fees {}).unwrap();
+        let value: FeesResponse = from_binary(&res).unwrap();
+        assert_eq!(value.collected, coins(200, "ugrano"));
+
+        // the deposit is due on top of the fee
+        let set_attribute = ExecuteMsg::SetAttribute {
+            identifier: Addr::unchecked(&identifier1),
+            name: String::from("service"),
+            value: String::from("github"),
+            validity: 1000,
+            expected_version: None,
+        };
+        let auth_info = mock_info("identifier0001", &coins(229, "ugrano"));
+        let err = execute(deps.as_mut(), mock_env(), auth_info, set_attribute.clone()).unwrap_err();
+        assert_eq!(
+            err,
+            ContractError::InsufficientDeposit {
+                required: Coin::new(130, "ugrano")
+            }
+        );
+
+        // deposits of a deactivated identifier are refunded by pruning its values
+        let auth_info = mock_info("identifier0001", &coins(230, "ugrano"));
+        let _res = execute(deps.as_mut(), mock_env(), auth_info, set_attribute).unwrap();
+        // "website" + "example" are 14 bytes
+        let msg = ExecuteMsg::SetAttribute {
+            identifier: Addr::unchecked(&identifier1),
+            name: String::from("website"),
+            value: String::from("example"),
+            validity: 10,
+            expected_version: None,
+        };
+        let auth_info = mock_info("identifier0001", &coins(240, "ugrano"));
+        let _res = execute(deps.as_mut(), mock_env(), auth_info, msg).unwrap();
+        let msg = SudoMsg::ForceDeactivate {
+            identifier: Addr::unchecked(&identifier1),
+            proposal_id: 1,
+        };
+        let _res = sudo(deps.as_mut(), mock_env(), msg).unwrap();
+        let prune_attribute = ExecuteMsg::PruneAttribute {
+            identifier: Addr::unchecked(&identifier1),
+            name: String::from("service"),
+            expected_version: None,
+        };
+        let res = execute(
+            deps.as_mut(),
+            mock_env(),
+            mock_info("identifier0001", &[]),
+            prune_attribute,
+        )
+        .unwrap();
+        assert_eq!(
+            res.messages[0].msg,
+            CosmosMsg::Bank(BankMsg::Send {
+                to_address: identifier1.clone(),
+                amount: coins(130, "ugrano"),
+            })
+        );
+
+        // and so are the deposits of values that expired
+        let mut env = mock_env();
+        env.block.time = env.block.time.plus_seconds(10);
+        let prune_attribute = ExecuteMsg::PruneAttribute {
+            identifier: Addr::unchecked(&identifier1),
+            name: String::from("website"),
+            expected_version: None,
+        };
+        let res = execute(
+            deps.as_mut(),
+            env.clone(),
+            mock_info("identifier0001", &[]),
+            prune_attribute,
+        )
+        .unwrap();
+        assert_eq!(
+            res.messages[0].msg,
+            CosmosMsg::Bank(BankMsg::Send {
+                to_address: identifier1.clone(),
+                amount: coins(140, "ugrano"),
+            })
+        );
+        let res = query(
+            deps.as_ref(),
+            env.clone(),
+            QueryMsg::Deposits {
+                identifier: Addr::unchecked(&identifier1),
+            },
+        )
+        .unwrap();
+        let value: DepositsResponse = from_binary(&res).unwrap();
+        assert!(value.deposits.is_empty());
+
+        // pruning is the only change left to a deactivated identifier
+        let deactivated = ContractError::Deactivated {
+            identifier: Addr::unchecked(&identifier1),
+        };
+        let msg = ExecuteMsg::SetAttribute {
+            identifier: Addr::unchecked(&identifier1),
+            name: String::from("website"),
+            value: String::from("example"),
+            validity: 10,
+            expected_version: None,
+        };
+        let auth_info = mock_info("identifier0001", &coins(240, "ugrano"));
+        let err = execute(deps.as_mut(), env.clone(), auth_info, msg).unwrap_err();
+        assert_eq!(err, deactivated);
+        let msg = ExecuteMsg::RevokeAttribute {
+            identifier: Addr::unchecked(&identifier1),
+            name: String::from("website"),
+            value: String::from("example"),
+            expected_version: None,
+        };
+        let auth_info = mock_info("identifier0001", &[]);
+        let err = execute(deps.as_mut(), env.clone(), auth_info.clone(), msg).unwrap_err();
+        assert_eq!(err, deactivated);
+        let msg = ExecuteMsg::ChangeController {
+            identifier: Addr::unchecked(&identifier1),
+            new_controller: Addr::unchecked("addr0001"),
+            next_hash: None,
+            lease: None,
+            expected_version: None,
+        };
+        let err = execute(deps.as_mut(), env, auth_info, msg).unwrap_err();
+        assert_eq!(err, deactivated);
+    }
+
+    #[test]
+    fn migrate_grandfathers_attributes() {
+        let mut deps = mock_dependencies_with_balance(&coins(2, "token"));
+        set_contract_version(deps.as_mut().storage, CONTRACT_NAME, "0.0.1").unwrap();
+
+        let identifier1 = Addr::unchecked("identifier0001");
+        ATTRIBUTES
+            .save(
+                deps.as_mut().storage,
+                (&identifier1, "service"),
+                &Attribute {
+                    values: vec![String::from("github")],
+                },
+            )
+            .unwrap();
+        // controllers stored before the controller index
+        Map::<&Addr, Addr>::new("controller")
+            .save(
+                deps.as_mut().storage,
+                &identifier1,
+                &Addr::unchecked("addr0001"),
+            )
+            .unwrap();
+
+        Map::<&Addr, Addr>::new("controller")
+            .save(
+                deps.as_mut().storage,
+                &Addr::unchecked("identifier0002"),
+                &Addr::unchecked("addr0001"),
+            )
+            .unwrap();
+
+        let msg = MigrateMsg {
+            owner: None,
+            limit: None,
+        };
+        let err = migrate(deps.as_mut(), mock_env(), msg).unwrap_err();
+        assert_eq!(err, ContractError::MissingOwner {});
+
+        // the backfill is resumed by migrating again, writes wait for it
+        let msg = MigrateMsg {
+            owner: Some(Addr::unchecked("owner0001")),
+            limit: Some(1),
+        };
+        let res = migrate(deps.as_mut(), mock_env(), msg).unwrap();
+        assert_eq!(get_attribute_value(res, "backfillDone"), "false");
+        let msg = ExecuteMsg::PruneAttribute {
+            identifier: identifier1.clone(),
+            name: String::from("service"),
+            expected_version: None,
+        };
+        let err = execute(deps.as_mut(), mock_env(), mock_info("addr0001", &[]), msg).unwrap_err();
+        assert_eq!(err, ContractError::MigrationInProgress {});
+
+        let msg = MigrateMsg {
+            owner: None,
+            limit: None,
+        };
+        let res = migrate(deps.as_mut(), mock_env(), msg.clone()).unwrap();
+        assert_eq!(get_attribute_value(res, "backfillDone"), "true");
+        // migrating the current version again has nothing to backfill
+        let res = migrate(deps.as_mut(), mock_env(), msg).unwrap();
+        assert_eq!(get_attribute_value(res, "backfillDone"), "true");
+
+        let res = query(deps.as_ref(), mock_env(), QueryMsg::Config {}).unwrap();
+        let value: ConfigResponse = from_binary(&res).unwrap();
+        assert_eq!(value.owner, Addr::unchecked("owner0001"));
+
+        let res = query(
+            deps.as_ref(),
+            mock_env(),
+            QueryMsg::IdentifiersByController {
+                controller: Addr::unchecked("addr0001"),
+                start_after: None,
+                limit: None,
+            },
+        )
+        .unwrap();
+        let value: IdentifiersResponse = from_binary(&res).unwrap();
+        assert_eq!(
+            value.identifiers,
+            vec![identifier1.clone(), Addr::unchecked("identifier0002")]
+        );
+
+        let res = query(deps.as_ref(), mock_env(), QueryMsg::Stats {}).unwrap();
+        let value: StatsResponse = from_binary(&res).unwrap();
+        assert_eq!(value.attributes, 1);
+        assert_eq!(value.active_values, 1);
+
+        // existing values are pruned without a refund
+        let msg = ExecuteMsg::PruneAttribute {
+            identifier: identifier1.clone(),
+            name: String::from("service"),
+            expected_version: None,
+        };
+        let res = execute(deps.as_mut(), mock_env(), mock_info("addr0001", &[]), msg).unwrap();
+        assert!(res.messages.is_empty());
+    }
+
+    #[test]
+    fn migrate_resumes_backfill() {
+        let mut deps = mock_dependencies_with_balance(&coins(2, "token"));
+        set_contract_version(deps.as_mut().storage, CONTRACT_NAME, "0.1.0").unwrap();
+
+        let identifier1 = Addr::unchecked("identifier0001");
+        let identifier2 = Addr::unchecked("identifier0002");
+        let identifier3 = Addr::unchecked("identifier0003");
+        let identifier4 = Addr::unchecked("identifier0004");
+        let storage = deps.as_mut().storage;
+        // controllers stored before the controller index
+        for identifier in [&identifier1, &identifier2] {
+            Map::<&Addr, Addr>::new("controller")
+                .save(storage, identifier, &Addr::unchecked("addr0001"))
+                .unwrap();
+        }
+        for identifier in [&identifier1, &identifier3] {
+            CHANGED.save(storage, identifier, &1).unwrap();
+        }
+        let attributes = [
+            (&identifier1, "service", vec!["github", "gitlab"]),
+            (&identifier2, "name", vec!["alice"]),
+        ];
+        for (identifier, name, values) in attributes {
+            let values = values.into_iter().map(String::from).collect();
+            ATTRIBUTES
+                .save(storage, (identifier, name), &Attribute { values })
+                .unwrap();
+        }
+        // revoked values are not active
+        VALIDITIES
+            .save(
+                storage,
+                (&identifier1, "service", "gitlab"),
+                &Timestamp::from_seconds(0),
+            )
+            .unwrap();
+        for identifier in [&identifier3, &identifier4] {
+            DEACTIVATED.save(storage, identifier, &true).unwrap();
+        }
+
+        // every migration handles one entry and records where the next one resumes
+        let expected = vec![
+            Backfill::Controllers {
+                start_after: Some(identifier1.clone()),
+            },
+            Backfill::Controllers {
+                start_after: Some(identifier2.clone()),
+            },
+            Backfill::Identifiers {
+                start_after: Some(identifier1.clone()),
+            },
+            Backfill::Identifiers {
+                start_after: Some(identifier3.clone()),
+            },
+            Backfill::Attributes {
+                start_after: Some((identifier1.clone(), String::from("service"))),
+                attributes: 1,
+                active_values: 1,
+            },
+            Backfill::Attributes {
+                start_after: Some((identifier2.clone(), String::from("name"))),
+                attributes: 2,
+                active_values: 2,
+            },
+            Backfill::Deactivated {
+                start_after: Some(identifier3),
+                deactivated: 1,
+            },
+            Backfill::Deactivated {
+                start_after: Some(identifier4),
+                deactivated: 2,
+            },
+        ];
+        let msg = MigrateMsg {
+            owner: Some(Addr::unchecked("owner0001")),
+            limit: Some(1),
+        };
+        for step in expected {
+            let res = migrate(deps.as_mut(), mock_env(), msg.clone()).unwrap();
+            assert_eq!(get_attribute_value(res, "backfillDone"), "false");
+            assert_eq!(BACKFILL.load(&deps.storage).unwrap(), step);
+        }
+
+        let res = migrate(deps.as_mut(), mock_env(), msg).unwrap();
+        assert_eq!(get_attribute_value(res, "backfillDone"), "true");
+        assert_eq!(BACKFILL.may_load(&deps.storage).unwrap(), None);
+        assert_eq!(
+            STATS.load(&deps.storage).unwrap(),
+            Stats {
+                identifiers: 2,
+                attributes: 2,
+                active_values: 2,
+                deactivated: 2,
+                controller_changes: 0,
+            }
+        );
+
+        let res = query(
+            deps.as_ref(),
+            mock_env(),
+            QueryMsg::IdentifiersByController {
+                controller: Addr::unchecked("addr0001"),
+                start_after: None,
+                limit: None,
+            },
+        )
+        .unwrap();
+        let value: IdentifiersResponse = from_binary(&res).unwrap();
+        assert_eq!(value.identifiers, vec![identifier1, identifier2]);
+    }
+
+    #[test]
+    fn sudo_recovery() {
+        let mut deps = mock_dependencies_with_balance(&coins(2, "token"));
+        let msg = InstantiateMsg {
+            owner: None,
+            fees: vec![],
+            deposit_per_byte: None,
+            network: None,
+        };
+        let info = mock_info("creator", &coins(2, "token"));
+        let _res = instantiate(deps.as_mut(), mock_env(), info, msg).unwrap();
+
+        let identifier1 = String::from("identifier0001");
+        let auth_info = mock_info("identifier0001", &[]);
+
+        let msg = ExecuteMsg::SetAttribute {
+            identifier: Addr::unchecked(&identifier1),
+            name: String::from("service"),
+            value: String::from("github"),
+            validity: 1000,
+            expected_version: None,
+        };
+        let _res = execute(deps.as_mut(), mock_env(), auth_info.clone(), msg).unwrap();
+
+        // governance hands the identifier to a new controller
+        let msg = SudoMsg::ForceChangeController {
+            identifier: Addr::unchecked(&identifier1),
+            new_controller: Addr::unchecked("addr0001"),
+            proposal_id: 7,
+        };
+        let res = sudo(deps.as_mut(), mock_env(), msg).unwrap();
+        assert_eq!(res.events[0].ty, "did_governance");
+        assert!(res.events[0]
+            .attributes
+            .iter()
+            .any(|a| a.key == "proposalId" && a.value == "7"));
+
+        let res = query(
+            deps.as_ref(),
+            mock_env(),
+            QueryMsg::Controller {
+                identifier: Addr::unchecked(&identifier1),
+            },
+        )
+        .unwrap();
+        let value: ControllerResponse = from_binary(&res).unwrap();
+        assert_eq!(value.controller, Addr::unchecked("addr0001"));
+
+        let msg = SudoMsg::ForceRevokeAttribute {
+            identifier: Addr::unchecked(&identifier1),
+            name: String::from("service"),
+            value: String::from("github"),
+            proposal_id: 8,
+        };
+        let _res = sudo(deps.as_mut(), mock_env(), msg).unwrap();
+
+        let res = query(
+            deps.as_ref(),
+            mock_env(),
+            QueryMsg::ValidTo {
+                identifier: Addr::unchecked(&identifier1),
+                name: String::from("service"),
+                value: String::from("github"),
+            },
+        )
+        .unwrap();
+        let value: ValidToResponse = from_binary(&res).unwrap();
+        assert_eq!(value.valid_to.seconds(), 0);
+
+        // a deactivated identifier cannot be modified anymore
+        let msg = SudoMsg::ForceDeactivate {
+            identifier: Addr::unchecked(&identifier1),
+            proposal_id: 9,
+        };
+        let _res = sudo(deps.as_mut(), mock_env(), msg).unwrap();
+
+        let res = query(
+            deps.as_ref(),
+            mock_env(),
+            QueryMsg::Deactivated {
+                identifier: Addr::unchecked(&identifier1),
+            },
+        )
+        .unwrap();
+        let value: DeactivatedResponse = from_binary(&res).unwrap();
+        assert!(value.deactivated);
+
+        let msg = ExecuteMsg::ChangeController {
+            identifier: Addr::unchecked(&identifier1),
+            new_controller: Addr::unchecked("addr0002"),
+            next_hash: None,
+            lease: None,
+            expected_version: None,
+        };
+        let err = execute(deps.as_mut(), mock_env(), mock_info("addr0001", &[]), msg).unwrap_err();
+        assert_eq!(
+            err,
+            ContractError::Deactivated {
+                identifier: Addr::unchecked(&identifier1)
+            }
+        );
+    }
+
+    #[test]
+    fn social_recovery() {
+        let mut deps = mock_dependencies_with_balance(&coins(2, "token"));
+        let msg = InstantiateMsg {
+            owner: None,
+            fees: vec![],
+            deposit_per_byte: None,
+            network: None,
+        };
+        let info = mock_info("creator", &coins(2, "token"));
+        let _res = instantiate(deps.as_mut(), mock_env(), info, msg).unwrap();
+
+        let identifier1 = String::from("identifier0001");
+        let auth_info = mock_info("identifier0001", &[]);
+
+        let msg = ExecuteMsg::SetGuardians {
+            identifier: Addr::unchecked(&identifier1),
+            guardians: vec![
+                Addr::unchecked("guardian0001"),
+                Addr::unchecked("guardian0002"),
+                Addr::unchecked("guardian0003"),
+            ],
+            threshold: 4,
+            delay: 100,
+            expected_version: None,
+        };
+        let err = execute(deps.as_mut(), mock_env(), auth_info.clone(), msg).unwrap_err();
+        assert_eq!(
+            err,
+            ContractError::InvalidThreshold {
+                threshold: 4,
+                guardians: 3
+            }
+        );
+
+        // the recovery delay is bounded so approving never overflows
+        let msg = ExecuteMsg::SetGuardians {
+            identifier: Addr::unchecked(&identifier1),
+            guardians: vec![Addr::unchecked("guardian0001")],
+            threshold: 1,
+            delay: u64::MAX,
+            expected_version: None,
+        };
+        let err = execute(deps.as_mut(), mock_env(), auth_info.clone(), msg).unwrap_err();
+        assert_eq!(
+            err,
+            ContractError::DelayTooLong {
+                max: Delay::MAX_SECONDS
+            }
+        );
+
+        let msg = ExecuteMsg::SetGuardians {
+            identifier: Addr::unchecked(&identifier1),
+            guardians: vec![
+                Addr::unchecked("guardian0001"),
+                Addr::unchecked("guardian0002"),
+                Addr::unchecked("guardian0003"),
+            ],
+            threshold: 2,
+            delay: 100,
+            expected_version: None,
+        };
+        let _res = execute(deps.as_mut(), mock_env(), auth_info.clone(), msg).unwrap();
+
+        // only guardians can start a recovery
+        let msg = ExecuteMsg::StartRecovery {
+            identifier: Addr::unchecked(&identifier1),
+            new_controller: Addr::unchecked("addr0001"),
+            expected_version: None,
+        };
+        let err = execute(
+            deps.as_mut(),
+            mock_env(),
+            mock_info("attacker", &[]),
+            msg.clone(),
+        )
+        .unwrap_err();
+        assert_eq!(err, ContractError::Unauthorized {});
+
+        let _res = execute(
+            deps.as_mut(),
+            mock_env(),
+            mock_info("guardian0001", &[]),
+            msg,
+        )
+        .unwrap();
+
+        // the controller can cancel the recovery
+        let msg = ExecuteMsg::CancelRecovery {
+            identifier: Addr::unchecked(&identifier1),
+            expected_version: None,
+        };
+        let _res = execute(deps.as_mut(), mock_env(), auth_info, msg).unwrap();
+
+        let res = query(
+            deps.as_ref(),
+            mock_env(),
+            QueryMsg::Recovery {
+                identifier: Addr::unchecked(&identifier1),
+            },
+        )
+        .unwrap();
+        let value: RecoveryResponse = from_binary(&res).unwrap();
+        assert_eq!(value.recovery, None);
+
+        let msg = ExecuteMsg::StartRecovery {
+            identifier: Addr::unchecked(&identifier1),
+            new_controller: Addr::unchecked("addr0001"),
+            expected_version: None,
+        };
+        let _res = execute(
+            deps.as_mut(),
+            mock_env(),
+            mock_info("guardian0001", &[]),
+            msg,
+        )
+        .unwrap();
+
+        let execute_recovery = ExecuteMsg::ExecuteRecovery {
+            identifier: Addr::unchecked(&identifier1),
+            expected_version: None,
+        };
+        let err = execute(
+            deps.as_mut(),
+            mock_env(),
+            mock_info("guardian0001", &[]),
+            execute_recovery.clone(),
+        )
+        .unwrap_err();
+        assert_eq!(
+            err,
+            ContractError::RecoveryNotReady {
+                identifier: Addr::unchecked(&identifier1)
+            }
+        );
+
+        let msg = ExecuteMsg::ApproveRecovery {
+            identifier: Addr::unchecked(&identifier1),
+            expected_version: None,
+        };
+        let _res = execute(
+            deps.as_mut(),
+            mock_env(),
+            mock_info("guardian0002", &[]),
+            msg,
+        )
+        .unwrap();
+
+        let res = query(
+            deps.as_ref(),
+            mock_env(),
+            QueryMsg::Recovery {
+                identifier: Addr::unchecked(&identifier1),
+            },
+        )
+        .unwrap();
+        let value: RecoveryResponse = from_binary(&res).unwrap();
+        assert_eq!(
+            value.recovery,
+            Some(RecoveryInfo {
+                new_controller: Addr::unchecked("addr0001"),
+                approvals: vec![
+                    Addr::unchecked("guardian0001"),
+                    Addr::unchecked("guardian0002")
+                ],
+                executable_at: Some(mock_env().block.time.plus_seconds(100)),
+            })
+        );
+
+        // the timelock has to pass
+        let err = execute(
+            deps.as_mut(),
+            mock_env(),
+            mock_info("guardian0001", &[]),
+            execute_recovery.clone(),
+        )
+        .unwrap_err();
+        assert_eq!(
+            err,
+            ContractError::RecoveryNotReady {
+                identifier: Addr::unchecked(&identifier1)
+            }
+        );
+
+        let mut env = mock_env();
+        env.block.time = env.block.time.plus_seconds(100);
+        env.block.height += 20;
+        let _res = execute(
+            deps.as_mut(),
+            env.clone(),
+            mock_info("guardian0003", &[]),
+            execute_recovery,
+        )
+        .unwrap();
+
+        let res = query(
+            deps.as_ref(),
+            mock_env(),
+            QueryMsg::Controller {
+                identifier: Addr::unchecked(&identifier1),
+            },
+        )
+        .unwrap();
+        let value: ControllerResponse = from_binary(&res).unwrap();
+        assert_eq!(value.controller, Addr::unchecked("addr0001"));
+
+        let res = query(
+            deps.as_ref(),
+            mock_env(),
+            QueryMsg::Changed {
+                identifier: Addr::unchecked(&identifier1),
+            },
+        )
+        .unwrap();
+        let value: ChangedResponse = from_binary(&res).unwrap();
+        assert_eq!(value.block, env.block.height);
+    }
+
+    #[test]
+    fn timelocked_change_controller() {
+        let mut deps = mock_dependencies_with_balance(&coins(2, "token"));
+        let msg = InstantiateMsg {
+            owner: None,
+            fees: vec![],
+            deposit_per_byte: None,
+            network: None,
+        };
+        let info = mock_info("creator", &coins(2, "token"));
+        let _res = instantiate(deps.as_mut(), mock_env(), info, msg).unwrap();
+
+        let identifier1 = String::from("identifier0001");
+        let auth_info = mock_info("identifier0001", &[]);
+
+        let policy = ControllerPolicy {
+            delay: Delay::Height(10),
+            veto: Some(Addr::unchecked("veto0001")),
+        };
+        let msg = ExecuteMsg::SetControllerPolicy {
+            identifier: Addr::unchecked(&identifier1),
+            policy: Some(policy.clone()),
+            expected_version: None,
+        };
+        let _res = execute(deps.as_mut(), mock_env(), auth_info.clone(), msg).unwrap();
+
+        // the change is only scheduled
+        let change_controller = ExecuteMsg::ChangeController {
+            identifier: Addr::unchecked(&identifier1),
+            new_controller: Addr::unchecked("attacker"),
+            next_hash: None,
+            lease: None,
+            expected_version: None,
+        };
+        let res = execute(
+            deps.as_mut(),
+            mock_env(),
+            auth_info.clone(),
+            change_controller.clone(),
+        )
+        .unwrap();
+        assert_eq!(get_attribute_value(res, "executeMsg"), "scheduleChange");
+
+        let res = query(
+            deps.as_ref(),
+            mock_env(),
+            QueryMsg::Controller {
+                identifier: Addr::unchecked(&identifier1),
+            },
+        )
+        .unwrap();
+        let value: ControllerResponse = from_binary(&res).unwrap();
+        assert_eq!(value.controller, Addr::unchecked(&identifier1));
+
+        let res = query(
+            deps.as_ref(),
+            mock_env(),
+            QueryMsg::PendingChange {
+                identifier: Addr::unchecked(&identifier1),
+            },
+        )
+        .unwrap();
+        let value: PendingChangeResponse = from_binary(&res).unwrap();
+        assert_eq!(
+            value.pending,
+            Some(PendingChangeInfo {
+                change: ScheduledChange::ChangeController {
+                    new_controller: Addr::unchecked("attacker"),
+                    next_hash: None,
+                    lease: None,
+                },
+                executable_at: Deadline::AtHeight(mock_env().block.height + 10),
+            })
+        );
+
+        // the veto key cancels it
+        let msg = ExecuteMsg::CancelPendingChange {
+            identifier: Addr::unchecked(&identifier1),
+            expected_version: None,
+        };
+        let err = execute(
+            deps.as_mut(),
+            mock_env(),
+            mock_info("attacker", &[]),
+            msg.clone(),
+        )
+        .unwrap_err();
+        assert_eq!(err, ContractError::Unauthorized {});
+        let _res = execute(deps.as_mut(), mock_env(), mock_info("veto0001", &[]), msg).unwrap();
+
+        let change_controller = ExecuteMsg::ChangeController {
+            identifier: Addr::unchecked(&identifier1),
+            new_controller: Addr::unchecked("addr0001"),
+            next_hash: None,
+            lease: None,
+            expected_version: None,
+        };
+        let _res = execute(
+            deps.as_mut(),
+            mock_env(),
+            auth_info.clone(),
+            change_controller,
+        )
+        .unwrap();
+
+        // removing the policy waits as well
+        let msg = ExecuteMsg::SetControllerPolicy {
+            identifier: Addr::unchecked(&identifier1),
+            policy: None,
+            expected_version: None,
+        };
+        let err = execute(deps.as_mut(), mock_env(), auth_info, msg).unwrap_err();
+        assert_eq!(
+            err,
+            ContractError::PendingChangeExists {
+                identifier: Addr::unchecked(&identifier1)
+            }
+        );
+
+        let execute_change = ExecuteMsg::ExecutePendingChange {
+            identifier: Addr::unchecked(&identifier1),
+            expected_version: None,
+        };
+        let err = execute(
+            deps.as_mut(),
+            mock_env(),
+            mock_info("anyone", &[]),
+            execute_change.clone(),
+        )
+        .unwrap_err();
+        assert_eq!(
+            err,
+            ContractError::PendingChangeNotReady {
+                identifier: Addr::unchecked(&identifier1)
+            }
+        );
+
+        let mut env = mock_env();
+        env.block.height += 10;
+        let _res = execute(deps.as_mut(), env, mock_info("anyone", &[]), execute_change).unwrap();
+
+        let res = query(
+            deps.as_ref(),
+            mock_env(),
+            QueryMsg::Controller {
+                identifier: Addr::unchecked(&identifier1),
+            },
+        )
+        .unwrap();
+        let value: ControllerResponse = from_binary(&res).unwrap();
+        assert_eq!(value.controller, Addr::unchecked("addr0001"));
+
+        let res = query(
+            deps.as_ref(),
+            mock_env(),
+            QueryMsg::ControllerPolicy {
+                identifier: Addr::unchecked(&identifier1),
+            },
+        )
+        .unwrap();
+        let value: ControllerPolicyResponse = from_binary(&res).unwrap();
+        assert_eq!(value.policy, Some(policy));
+    }
+
+    #[test]
+    fn pre_rotation() {
+        let mut deps = mock_dependencies_with_balance(&coins(2, "token"));
+        let msg = InstantiateMsg {
+            owner: None,
+            fees: vec![],
+            deposit_per_byte: None,
+            network: None,
+        };
+        let info = mock_info("creator", &coins(2, "token"));
+        let _res = instantiate(deps.as_mut(), mock_env(), info, msg).unwrap();
+
+        let identifier1 = String::from("identifier0001");
+        let auth_info = mock_info("identifier0001", &[]);
+
+        let msg = ExecuteMsg::CommitNextController {
+            identifier: Addr::unchecked(&identifier1),
+            next_hash: controller_hash(&Addr::unchecked("addr0001")),
+            expected_version: None,
+        };
+        let _res = execute(deps.as_mut(), mock_env(), auth_info.clone(), msg.clone()).unwrap();
+
+        // the commitment cannot be replaced without a rotation
+        let err = execute(deps.as_mut(), mock_env(), auth_info.clone(), msg).unwrap_err();
+        assert_eq!(
+            err,
+            ContractError::CommitmentExists {
+                identifier: Addr::unchecked(&identifier1)
+            }
+        );
+
+        let msg = ExecuteMsg::ChangeController {
+            identifier: Addr::unchecked(&identifier1),
+            new_controller: Addr::unchecked("attacker"),
+            next_hash: Some(controller_hash(&Addr::unchecked("attacker"))),
+            lease: None,
+            expected_version: None,
+        };
+        let err = execute(deps.as_mut(), mock_env(), auth_info.clone(), msg).unwrap_err();
+        assert_eq!(err, ContractError::CommitmentMismatch {});
+
+        // each rotation commits to the next controller
+        let msg = ExecuteMsg::ChangeController {
+            identifier: Addr::unchecked(&identifier1),
+            new_controller: Addr::unchecked("addr0001"),
+            next_hash: None,
+            lease: None,
+            expected_version: None,
+        };
+        let err = execute(deps.as_mut(), mock_env(), auth_info.clone(), msg).unwrap_err();
+        assert_eq!(err, ContractError::MissingCommitment {});
+
+        let msg = ExecuteMsg::ChangeController {
+            identifier: Addr::unchecked(&identifier1),
+            new_controller: Addr::unchecked("addr0001"),
+            next_hash: Some(controller_hash(&Addr::unchecked("addr0002"))),
+            lease: None,
+            expected_version: None,
+        };
+        let _res = execute(deps.as_mut(), mock_env(), auth_info, msg).unwrap();
+
+        let res = query(
+            deps.as_ref(),
+            mock_env(),
+            QueryMsg::Commitment {
+                identifier: Addr::unchecked(&identifier1),
+            },
+        )
+        .unwrap();
+        let value: CommitmentResponse = from_binary(&res).unwrap();
+        assert_eq!(
+            value.next_hash,
+            Some(controller_hash(&Addr::unchecked("addr0002")))
+        );
+    }
+
+    #[test]
+    fn controller_lease() {
+        let mut deps = mock_dependencies_with_balance(&coins(2, "token"));
+        let msg = InstantiateMsg {
+            owner: None,
+            fees: vec![],
+            deposit_per_byte: None,
+            network: None,
+        };
+        let info = mock_info("creator", &coins(2, "token"));
+        let _res = instantiate(deps.as_mut(), mock_env(), info, msg).unwrap();
+
+        let identifier1 = String::from("identifier0001");
+        let auth_info = mock_info("identifier0001", &[]);
+        let lessee_info = mock_info("addr0001", &[]);
+        let expires = mock_env().block.time.plus_seconds(100);
+
+        // a lease has to end in the future
+        let msg = ExecuteMsg::ChangeController {
+            identifier: Addr::unchecked(&identifier1),
+            new_controller: Addr::unchecked("addr0001"),
+            next_hash: None,
+            lease: Some(Lease {
+                expires: mock_env().block.time,
+                fallback: None,
+            }),
+            expected_version: None,
+        };
+        let err = execute(deps.as_mut(), mock_env(), auth_info.clone(), msg).unwrap_err();
+        assert_eq!(
+            err,
+            ContractError::LeaseEnded {
+                expires: mock_env().block.time
+            }
+        );
+
+        let msg = ExecuteMsg::ChangeController {
+            identifier: Addr::unchecked(&identifier1),
+            new_controller: Addr::unchecked("addr0001"),
+            next_hash: None,
+            lease: Some(Lease {
+                expires,
+                fallback: None,
+            }),
+            expected_version: None,
+        };
+        let _res = execute(deps.as_mut(), mock_env(), auth_info.clone(), msg).unwrap();
+
+        let msg = ExecuteMsg::SetAttribute {
+            identifier: Addr::unchecked(&identifier1),
+            name: String::from("service"),
+            value: String::from("https://example.com"),
+            validity: 1000,
+            expected_version: None,
+        };
+        let _res = execute(deps.as_mut(), mock_env(), lessee_info.clone(), msg.clone()).unwrap();
+
+        // control reverts to the identifier once the lease ends
+        let mut env = mock_env();
+        env.block.time = expires;
+
+        let res = query(
+            deps.as_ref(),
+            env.clone(),
+            QueryMsg::Controller {
+                identifier: Addr::unchecked(&identifier1),
+            },
+        )
+        .unwrap();
+        let value: ControllerResponse = from_binary(&res).unwrap();
+        assert_eq!(value.controller, Addr::unchecked(&identifier1));
+
+        let err = execute(deps.as_mut(), env.clone(), lessee_info, msg).unwrap_err();
+        assert_eq!(err, ContractError::Unauthorized {});
+
+        // or to the fallback
+        let msg = ExecuteMsg::ChangeController {
+            identifier: Addr::unchecked(&identifier1),
+            new_controller: Addr::unchecked("addr0001"),
+            next_hash: None,
+            lease: Some(Lease {
+                expires: expires.plus_seconds(100),
+                fallback: Some(Addr::unchecked("addr0002")),
+            }),
+            expected_version: None,
+        };
+        let _res = execute(deps.as_mut(), env.clone(), auth_info, msg).unwrap();
+
+        env.block.time = expires.plus_seconds(100);
+        let res = query(
+            deps.as_ref(),
+            env.clone(),
+            QueryMsg::Controller {
+                identifier: Addr::unchecked(&identifier1),
+            },
+        )
+        .unwrap();
+        let value: ControllerResponse = from_binary(&res).unwrap();
+        assert_eq!(value.controller, Addr::unchecked("addr0002"));
+
+        // a change without a lease ends it
+        let msg = ExecuteMsg::ChangeController {
+            identifier: Addr::unchecked(&identifier1),
+            new_controller: Addr::unchecked("addr0003"),
+            next_hash: None,
+            lease: None,
+            expected_version: None,
+        };
+        let _res = execute(deps.as_mut(), env.clone(), mock_info("addr0002", &[]), msg).unwrap();
+
+        let res = query(
+            deps.as_ref(),
+            env,
+            QueryMsg::Lease {
+                identifier: Addr::unchecked(&identifier1),
+            },
+        )
+        .unwrap();
+        let value: LeaseResponse = from_binary(&res).unwrap();
+        assert_eq!(value.lease, None);
+    }
+
+    #[test]
+    fn identifiers_by_controller() {
+        let mut deps = mock_dependencies_with_balance(&coins(2, "token"));
+        let msg = InstantiateMsg {
+            owner: None,
+            fees: vec![],
+            deposit_per_byte: None,
+            network: None,
+        };
+        let info = mock_info("creator", &coins(2, "token"));
+        let _res = instantiate(deps.as_mut(), mock_env(), info, msg).unwrap();
+
+        for identifier in ["identifier0002", "identifier0001", "identifier0003"] {
+            let msg = ExecuteMsg::ChangeController {
+                identifier: Addr::unchecked(identifier),
+                new_controller: Addr::unchecked("addr0001"),
+                next_hash: None,
+                lease: None,
+                expected_version: None,
+            };
+            let _res = execute(deps.as_mut(), mock_env(), mock_info(identifier, &[]), msg).unwrap();
+        }
+
+        // moving control away removes the identifier from the index
+        let msg = ExecuteMsg::ChangeController {
+            identifier: Addr::unchecked("identifier0003"),
+            new_controller: Addr::unchecked("addr0002"),
+            next_hash: None,
+            lease: None,
+            expected_version: None,
+        };
+        let _res = execute(deps.as_mut(), mock_env(), mock_info("addr0001", &[]), msg).unwrap();
+
+        let res = query(
+            deps.as_ref(),
+            mock_env(),
+            QueryMsg::IdentifiersByController {
+                controller: Addr::unchecked("addr0001"),
+                start_after: None,
+                limit: Some(1),
+            },
+        )
+        .unwrap();
+        let value: IdentifiersResponse = from_binary(&res).unwrap();
+        assert_eq!(value.identifiers, vec![Addr::unchecked("identifier0001")]);
+
+        let res = query(
+            deps.as_ref(),
+            mock_env(),
+            QueryMsg::IdentifiersByController {
+                controller: Addr::unchecked("addr0001"),
+                start_after: Some(Addr::unchecked("identifier0001")),
+                limit: None,
+            },
+        )
+        .unwrap();
+        let value: IdentifiersResponse = from_binary(&res).unwrap();
+        assert_eq!(value.identifiers, vec![Addr::unchecked("identifier0002")]);
+
+        let res = query(
+            deps.as_ref(),
+            mock_env(),
+            QueryMsg::IdentifiersByController {
+                controller: Addr::unchecked("addr0002"),
+                start_after: None,
+                limit: None,
+            },
+        )
+        .unwrap();
+        let value: IdentifiersResponse = from_binary(&res).unwrap();
+        assert_eq!(value.identifiers, vec![Addr::unchecked("identifier0003")]);
+
+        // a leased identifier is listed under the lessee until the lease ends, then under the
+        // fallback it reverts to
+        let msg = ExecuteMsg::ChangeController {
+            identifier: Addr::unchecked("identifier0004"),
+            new_controller: Addr::unchecked("addr0003"),
+            next_hash: None,
+            lease: Some(Lease {
+                expires: mock_env().block.time.plus_seconds(100),
+                fallback: Some(Addr::unchecked("addr0002")),
+            }),
+            expected_version: None,
+        };
+        let _res = execute(
+            deps.as_mut(),
+            mock_env(),
+            mock_info("identifier0004", &[]),
+            msg,
+        )
+        .unwrap();
+        let identifiers = |deps: Deps, env: Env, controller: &str| {
+            let res = query(
+                deps,
+                env,
+                QueryMsg::IdentifiersByController {
+                    controller: Addr::unchecked(controller),
+                    start_after: None,
+                    limit: None,
+                },
+            )
+            .unwrap();
+            from_binary::<IdentifiersResponse>(&res)
+                .unwrap()
+                .identifiers
+        };
+        assert_eq!(
+            identifiers(deps.as_ref(), mock_env(), "addr0003"),
+            vec![Addr::unchecked("identifier0004")]
+        );
+        assert_eq!(
+            identifiers(deps.as_ref(), mock_env(), "addr0002"),
+            vec![Addr::unchecked("identifier0003")]
+        );
+
+        let mut env = mock_env();
+        env.block.time = env.block.time.plus_seconds(100);
+        assert!(identifiers(deps.as_ref(), env.clone(), "addr0003").is_empty());
+        assert_eq!(
+            identifiers(deps.as_ref(), env, "addr0002"),
+            vec![
+                Addr::unchecked("identifier0003"),
+                Addr::unchecked("identifier0004")
+            ]
+        );
+    }
+
+    #[test]
+    fn identifiers_by_attribute() {
+        let mut deps = mock_dependencies_with_balance(&coins(2, "token"));
+        let msg = InstantiateMsg {
+            owner: None,
+            fees: vec![],
+            deposit_per_byte: None,
+            network: None,
+        };
+        let info = mock_info("creator", &coins(2, "token"));
+        let _res = instantiate(deps.as_mut(), mock_env(), info, msg).unwrap();
+
+        let msg = ExecuteMsg::UpdateConfig {
+            paused: None,
+            paused_operations: None,
+            fees: None,
+            deposit_per_byte: None,
+            indexed_prefixes: Some(vec![String::from("did/pub/")]),
+            max_batch: None,
+            max_services: None,
+        };
+        let _res = execute(deps.as_mut(), mock_env(), mock_info("creator", &[]), msg).unwrap();
+
+        let name = String::from("did/pub/Ed25519/veriKey/base64");
+        let value = String::from("HnkWKBaV2BhmQwgEHvYoRy4ppDRNvwCYUDHvM4BOIsQ=");
+        for (identifier, name, validity) in [
+            ("identifier0002", name.clone(), 1000),
+            ("identifier0001", name.clone(), 1000),
+            ("identifier0003", name.clone(), 100),
+            // names without an indexed prefix are not indexed
+            ("identifier0004", String::from("service"), 1000),
+        ] {
+            let msg = ExecuteMsg::SetAttribute {
+                identifier: Addr::unchecked(identifier),
+                name,
+                value: value.clone(),
+                validity,
+                expected_version: None,
+            };
+            let _res = execute(deps.as_mut(), mock_env(), mock_info(identifier, &[]), msg).unwrap();
+        }
+
+        let msg = ExecuteMsg::RevokeAttribute {
+            identifier: Addr::unchecked("identifier0002"),
+            name: name.clone(),
+            value: value.clone(),
+            expected_version: None,
+        };
+        let _res = execute(
+            deps.as_mut(),
+            mock_env(),
+            mock_info("identifier0002", &[]),
+            msg,
+        )
+        .unwrap();
+
+        let res = query(
+            deps.as_ref(),
+            mock_env(),
+            QueryMsg::IdentifiersByAttribute {
+                name: name.clone(),
+                value: value.clone(),
+                start_after: None,
+                limit: None,
+            },
+        )
+        .unwrap();
+        let value_res: IdentifiersResponse = from_binary(&res).unwrap();
+        assert_eq!(
+            value_res.identifiers,
+            vec![
+                Addr::unchecked("identifier0001"),
+                Addr::unchecked("identifier0003")
+            ]
+        );
+
+        // expired values are left out and pruning removes them from the index
+        let mut env = mock_env();
+        env.block.time = env.block.time.plus_seconds(100);
+        let res = query(
+            deps.as_ref(),
+            env.clone(),
+            QueryMsg::IdentifiersByAttribute {
+                name: name.clone(),
+                value: value.clone(),
+                start_after: None,
+                limit: None,
+            },
+        )
+        .unwrap();
+        let value_res: IdentifiersResponse = from_binary(&res).unwrap();
+        assert_eq!(
+            value_res.identifiers,
+            vec![Addr::unchecked("identifier0001")]
+        );
+
+        let msg = ExecuteMsg::PruneAttribute {
+            identifier: Addr::unchecked("identifier0003"),
+            name: name.clone(),
+            expected_version: None,
+        };
+        let _res = execute(deps.as_mut(), env, mock_info("identifier0003", &[]), msg).unwrap();
+        assert!(!ATTRIBUTE_INDEX.has(
+            deps.as_ref().storage,
+            (&name, &value, &Addr::unchecked("identifier0003"))
+        ));
+
+        let res = query(
+            deps.as_ref(),
+            mock_env(),
+            QueryMsg::IdentifiersByAttribute {
+                name: String::from("service"),
+                value,
+                start_after: None,
+                limit: None,
+            },
+        )
+        .unwrap();
+        let value_res: IdentifiersResponse = from_binary(&res).unwrap();
+        assert!(value_res.identifiers.is_empty());
+    }
+
+    #[test]
+    fn registry_stats() {
+        let mut deps = mock_dependencies_with_balance(&coins(2, "token"));
+        let msg = InstantiateMsg {
+            owner: None,
+            fees: vec![],
+            deposit_per_byte: None,
+            network: None,
+        };
+        let info = mock_info("creator", &coins(2, "token"));
+        let _res = instantiate(deps.as_mut(), mock_env(), info, msg).unwrap();
+
+        for (identifier, value) in [
+            ("identifier0002", "github"),
+            ("identifier0001", "github"),
+            ("identifier0001", "twitter"),
+        ] {
+            let msg = ExecuteMsg::SetAttribute {
+                identifier: Addr::unchecked(identifier),
+                name: String::from("service"),
+                value: String::from(value),
+                validity: 1000,
+                expected_version: None,
+            };
+            let _res = execute(deps.as_mut(), mock_env(), mock_info(identifier, &[]), msg).unwrap();
+        }
+
+        // a value revoked twice is not counted twice
+        let msg = ExecuteMsg::RevokeAttribute {
+            identifier: Addr::unchecked("identifier0001"),
+            name: String::from("service"),
+            value: String::from("github"),
+            expected_version: None,
+        };
+        let auth_info = mock_info("identifier0001", &[]);
+        let _res = execute(deps.as_mut(), mock_env(), auth_info.clone(), msg.clone()).unwrap();
+        let _res = execute(deps.as_mut(), mock_env(), auth_info.clone(), msg).unwrap();
+
+        let msg = ExecuteMsg::PruneAttribute {
+            identifier: Addr::unchecked("identifier0001"),
+            name: String::from("service"),
+            expected_version: None,
+        };
+        let _res = execute(deps.as_mut(), mock_env(), auth_info, msg).unwrap();
+
+        let mut env = mock_env();
+        env.block.height += 1;
+        let msg = ExecuteMsg::ChangeController {
+            identifier: Addr::unchecked("identifier0003"),
+            new_controller: Addr::unchecked("addr0001"),
+            next_hash: None,
+            lease: None,
+            expected_version: None,
+        };
+        let _res = execute(
+            deps.as_mut(),
+            env.clone(),
+            mock_info("identifier0003", &[]),
+            msg,
+        )
+        .unwrap();
+
+        let msg = SudoMsg::ForceDeactivate {
+            identifier: Addr::unchecked("identifier0002"),
+            proposal_id: 1,
+        };
+        let _res = sudo(deps.as_mut(), mock_env(), msg).unwrap();
+
+        let res = query(deps.as_ref(), mock_env(), QueryMsg::Stats {}).unwrap();
+        let value: StatsResponse = from_binary(&res).unwrap();
+        assert_eq!(
+            value,
+            StatsResponse {
+                identifiers: 3,
+                attributes: 2,
+                active_values: 2,
+                deactivated: 1,
+                controller_changes: 1,
+            }
+        );
+
+        let res = query(
+            deps.as_ref(),
+            mock_env(),
+            QueryMsg::AllIdentifiers {
+                start_after: Some(Addr::unchecked("identifier0001")),
+                limit: None,
+            },
+        )
+        .unwrap();
+        let value: AllIdentifiersResponse = from_binary(&res).unwrap();
+        assert_eq!(
+            value.identifiers,
+            vec![
+                IdentifierInfo {
+                    identifier: Addr::unchecked("identifier0002"),
+                    created_height: mock_env().block.height,
+                    created_time: mock_env().block.time,
+                },
+                IdentifierInfo {
+                    identifier: Addr::unchecked("identifier0003"),
+                    created_height: env.block.height,
+                    created_time: env.block.time,
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn batch_queries() {
+        let mut deps = mock_dependencies_with_balance(&coins(2, "token"));
+        let msg = InstantiateMsg {
+            owner: None,
+            fees: vec![],
+            deposit_per_byte: None,
+            network: None,
+        };
+        let info = mock_info("creator", &coins(2, "token"));
+        let _res = instantiate(deps.as_mut(), mock_env(), info, msg).unwrap();
+
+        let identifier1 = Addr::unchecked("identifier0001");
+        let unknown = Addr::unchecked("identifier0002");
+        let auth_info = mock_info("identifier0001", &[]);
+
+        for (value, validity) in [("github", 1000), ("twitter", 0)] {
+            let msg = ExecuteMsg::SetAttribute {
+                identifier: identifier1.clone(),
+                name: String::from("service"),
+                value: String::from(value),
+                validity,
+                expected_version: None,
+            };
+            let _res = execute(deps.as_mut(), mock_env(), auth_info.clone(), msg).unwrap();
+        }
+
+        let msg = ExecuteMsg::ChangeController {
+            identifier: identifier1.clone(),
+            new_controller: Addr::unchecked("addr0001"),
+            next_hash: None,
+            lease: None,
+            expected_version: None,
+        };
+        let _res = execute(deps.as_mut(), mock_env(), auth_info, msg).unwrap();
+
+        let res = query(
+            deps.as_ref(),
+            mock_env(),
+            QueryMsg::Controllers {
+                identifiers: vec![unknown.clone(), identifier1.clone()],
+            },
+        )
+        .unwrap();
+        let value: ControllersResponse = from_binary(&res).unwrap();
+        assert_eq!(
+            value.controllers,
+            vec![
+                ControllerItem {
+                    identifier: unknown.clone(),
+                    controller: unknown.clone(),
+                    found: false,
+                },
+                ControllerItem {
+                    identifier: identifier1.clone(),
+                    controller: Addr::unchecked("addr0001"),
+                    found: true,
+                },
+            ]
+        );
+
+        let res = query(
+            deps.as_ref(),
+            mock_env(),
+            QueryMsg::Attributes {
+                keys: vec![
+                    (identifier1.clone(), String::from("service")),
+                    (unknown.clone(), String::from("service")),
+                ],
+            },
+        )
+        .unwrap();
+        let value: AttributesResponse = from_binary(&res).unwrap();
+        assert_eq!(
+            value.attributes[0].values,
+            vec![String::from("github"), String::from("twitter")]
+        );
+        assert!(value.attributes[0].found);
+        assert!(!value.attributes[1].found);
+
+        let res = query(
+            deps.as_ref(),
+            mock_env(),
+            QueryMsg::ValidTos {
+                keys: vec![
+                    (
+                        identifier1.clone(),
+                        String::from("service"),
+                        String::from("github"),
+                    ),
+                    (
+                        identifier1.clone(),
+                        String::from("service"),
+                        String::from("gitlab"),
+                    ),
+                ],
+            },
+        )
+        .unwrap();
+        let value: ValidTosResponse = from_binary(&res).unwrap();
+        assert_eq!(
+            value.valid_tos[0].valid_to,
+            mock_env().block.time.plus_seconds(1000)
+        );
+        assert!(value.valid_tos[0].found);
+        assert!(!value.valid_tos[1].found);
+
+        // expired values are left out of the document
+        let res = query(
+            deps.as_ref(),
+            mock_env(),
+            QueryMsg::ResolveMany {
+                identifiers: vec![identifier1.clone(), unknown.clone()],
+            },
+        )
+        .unwrap();
+        let value: ResolveManyResponse = from_binary(&res).unwrap();
+        assert_eq!(
+            value.documents[0].document,
+            DidDocument {
+                id: format!("did:grano:cosmos-testnet-14002:{}", identifier1),
+                controller: String::from("did:grano:cosmos-testnet-14002:addr0001"),
+                also_known_as: vec![],
+                attributes: vec![DocumentAttribute {
+                    name: String::from("service"),
+                    value: String::from("github"),
+                    valid_to: mock_env().block.time.plus_seconds(1000),
+                }],
+                verification_methods: vec![],
+                delegates: vec![],
+                services: vec![],
+                deactivated: false,
+            }
+        );
+        assert!(!value.documents[1].found);
+
+        let msg = ExecuteMsg::UpdateConfig {
+            paused: None,
+            paused_operations: None,
+            fees: None,
+            deposit_per_byte: None,
+            indexed_prefixes: None,
+            max_batch: Some(1),
+            max_services: None,
+        };
+        let _res = execute(deps.as_mut(), mock_env(), mock_info("creator", &[]), msg).unwrap();
+
+        let err = query(
+            deps.as_ref(),
+            mock_env(),
+            QueryMsg::Controllers {
+                identifiers: vec![unknown, identifier1],
+            },
+        )
+        .unwrap_err();
+        assert_eq!(
+            err,
+            StdError::generic_err("Batch of 2 exceeds the maximum of 1")
+        );
+    }
+
+    #[test]
+    fn can_execute() {
+        let mut deps = mock_dependencies_with_balance(&coins(2, "token"));
+        let msg = InstantiateMsg {
+            owner: None,
+            fees: vec![OperationFee {
+                operation: Operation::SetAttribute,
+                fee: Coin::new(10, "ugrano"),
+            }],
+            deposit_per_byte: None,
+            network: None,
+        };
+        let info = mock_info("creator", &coins(2, "token"));
+        let _res = instantiate(deps.as_mut(), mock_env(), info, msg).unwrap();
+
+        let identifier1 = Addr::unchecked("identifier0001");
+        let set_attribute = ExecuteMsg::SetAttribute {
+            identifier: identifier1.clone(),
+            name: String::from("service"),
+            value: String::from("github"),
+            validity: 1000,
+            expected_version: None,
+        };
+        let can_execute = |deps: Deps, sender: &str, funds: Vec<Coin>, msg: &ExecuteMsg| {
+            let res = query(
+                deps,
+                mock_env(),
+                QueryMsg::CanExecute {
+                    sender: Addr::unchecked(sender),
+                    funds,
+                    msg: Box::new(msg.clone()),
+                },
+            )
+            .unwrap();
+            from_binary::<CanExecuteResponse>(&res).unwrap()
+        };
+
+        let value = can_execute(deps.as_ref(), "identifier0001", vec![], &set_attribute);
+        assert_eq!(value.failed_check, Some(FailedCheck::InsufficientFee));
+
+        let value = can_execute(
+            deps.as_ref(),
+            "attacker",
+            coins(10, "ugrano"),
+            &set_attribute,
+        );
+        assert_eq!(value.failed_check, Some(FailedCheck::Unauthorized));
+
+        let value = can_execute(
+            deps.as_ref(),
+            "identifier0001",
+            coins(10, "ugrano"),
+            &set_attribute,
+        );
+        assert_eq!(
+            value,
+            CanExecuteResponse {
+                allowed: true,
+                failed_check: None,
+                error: None,
+            }
+        );
+
+        // the dry run writes nothing
+        let res = query(
+            deps.as_ref(),
+            mock_env(),
+            QueryMsg::Attribute {
+                identifier: identifier1.clone(),
+                name: String::from("service"),
+            },
+        )
+        .unwrap();
+        let value: AttributeResponse = from_binary(&res).unwrap();
+        assert!(value.values.is_empty());
+
+        let revoke_attribute = ExecuteMsg::RevokeAttribute {
+            identifier: identifier1.clone(),
+            name: String::from("service"),
+            value: String::from("github"),
+            expected_version: None,
+        };
+        let value = can_execute(deps.as_ref(), "identifier0001", vec![], &revoke_attribute);
+        assert_eq!(value.failed_check, Some(FailedCheck::InvalidKeyPair));
+
+        let msg = ExecuteMsg::UpdateConfig {
+            paused: None,
+            paused_operations: Some(vec![Operation::RevokeAttribute]),
+            fees: None,
+            deposit_per_byte: None,
+            indexed_prefixes: None,
+            max_batch: None,
+            max_services: None,
+        };
+        let _res = execute(deps.as_mut(), mock_env(), mock_info("creator", &[]), msg).unwrap();
+        let value = can_execute(deps.as_ref(), "identifier0001", vec![], &revoke_attribute);
+        assert_eq!(value.failed_check, Some(FailedCheck::Paused));
+
+        let msg = SudoMsg::ForceDeactivate {
+            identifier: identifier1,
+            proposal_id: 1,
+        };
+        let _res = sudo(deps.as_mut(), mock_env(), msg).unwrap();
+        let value = can_execute(
+            deps.as_ref(),
+            "identifier0001",
+            coins(10, "ugrano"),
+            &set_attribute,
+        );
+        assert_eq!(value.failed_check, Some(FailedCheck::Deactivated));
+    }
+
+    #[test]
+    fn expected_version() {
+        let mut deps = mock_dependencies_with_balance(&coins(2, "token"));
+        let msg = InstantiateMsg {
+            owner: None,
+            fees: vec![],
+            deposit_per_byte: None,
+            network: None,
+        };
+        let info = mock_info("creator", &coins(2, "token"));
+        let _res = instantiate(deps.as_mut(), mock_env(), info, msg).unwrap();
+
+        let identifier1 = Addr::unchecked("identifier0001");
+        let auth_info = mock_info("identifier0001", &[]);
+
+        let msg = ExecuteMsg::SetAttribute {
+            identifier: identifier1.clone(),
+            name: String::from("service"),
+            value: String::from("github"),
+            validity: 1000,
+            expected_version: Some(0),
+        };
+        let res = execute(deps.as_mut(), mock_env(), auth_info.clone(), msg.clone()).unwrap();
+        assert_eq!(get_attribute_value(res, "version"), "1");
+
+        // a write built against an old version is rejected
+        let err = execute(deps.as_mut(), mock_env(), auth_info.clone(), msg).unwrap_err();
+        assert_eq!(
+            err,
+            ContractError::VersionMismatch {
+                expected: 0,
+                current: 1
+            }
+        );
+
+        // every write to the identifier moves the version on
+        let msg = ExecuteMsg::AddHook {
+            identifier: identifier1.clone(),
+            hook: Addr::unchecked("hook0001"),
+            on_failure: HookFailure::Ignore,
+            expected_version: Some(1),
+        };
+        let res = execute(deps.as_mut(), mock_env(), auth_info.clone(), msg).unwrap();
+        assert_eq!(get_attribute_value(res, "version"), "2");
+
+        let msg = ExecuteMsg::ChangeController {
+            identifier: identifier1.clone(),
+            new_controller: Addr::unchecked("addr0001"),
+            next_hash: None,
+            lease: None,
+            expected_version: Some(1),
+        };
+        let err = execute(deps.as_mut(), mock_env(), auth_info.clone(), msg).unwrap_err();
+        assert_eq!(
+            err,
+            ContractError::VersionMismatch {
+                expected: 1,
+                current: 2
+            }
+        );
+
+        let msg = ExecuteMsg::ChangeController {
+            identifier: identifier1.clone(),
+            new_controller: Addr::unchecked("addr0001"),
+            next_hash: None,
+            lease: None,
+            expected_version: Some(2),
+        };
+        let res = execute(deps.as_mut(), mock_env(), auth_info, msg).unwrap();
+        assert_eq!(get_attribute_value(res, "version"), "3");
+
+        let res = query(
+            deps.as_ref(),
+            mock_env(),
+            QueryMsg::Version {
+                identifier: identifier1,
+            },
+        )
+        .unwrap();
+        let value: VersionResponse = from_binary(&res).unwrap();
+        assert_eq!(value.version, 3);
+    }
+
+    #[test]
+    fn expected_version_of_every_write() {
+        // checks that a write against the previous version is rejected and that the write
+        // against the current one moves the version on
+        fn write_at(
+            deps: &mut OwnedDeps<MockStorage, MockApi, MockQuerier>,
+            env: &Env,
+            info: &MessageInfo,
+            msg: impl Fn(Option<u64>) -> ExecuteMsg,
+            version: u64,
+        ) {
+            let err = execute(
+                deps.as_mut(),
+                env.clone(),
+                info.clone(),
+                msg(Some(version - 1)),
+            )
+            .unwrap_err();
+            assert_eq!(
+                err,
+                ContractError::VersionMismatch {
+                    expected: version - 1,
+                    current: version
+                }
+            );
+            let res =
+                execute(deps.as_mut(), env.clone(), info.clone(), msg(Some(version))).unwrap();
+            assert_eq!(
+                get_attribute_value(res, "version"),
+                (version + 1).to_string()
+            );
+        }
+
+        let mut deps = mock_dependencies_with_balance(&coins(2, "token"));
+        let msg = InstantiateMsg {
+            owner: None,
+            fees: vec![],
+            deposit_per_byte: None,
+            network: None,
+        };
+        let info = mock_info("creator", &coins(2, "token"));
+        let _res = instantiate(deps.as_mut(), mock_env(), info, msg).unwrap();
+
+        let identifier1 = Addr::unchecked("identifier0001");
+        let auth_info = mock_info("identifier0001", &[]);
+        let env = mock_env();
+
+        // hooks
+        let msg = ExecuteMsg::SetAttribute {
+            identifier: identifier1.clone(),
+            name: String::from("service"),
+            value: String::from("github"),
+            validity: 1000,
+            expected_version: None,
+        };
+        let _res = execute(deps.as_mut(), env.clone(), auth_info.clone(), msg).unwrap();
+        let add_hook = |expected_version| ExecuteMsg::AddHook {
+            identifier: identifier1.clone(),
+            hook: Addr::unchecked("hook0001"),
+            on_failure: HookFailure::Ignore,
+            expected_version,
+        };
+        write_at(&mut deps, &env, &auth_info, add_hook, 1);
+        let remove_hook = |expected_version| ExecuteMsg::RemoveHook {
+            identifier: identifier1.clone(),
+            hook: Addr::unchecked("hook0001"),
+            expected_version,
+        };
+        write_at(&mut deps, &env, &auth_info, remove_hook, 2);
+
+        // guardians and recoveries
+        let set_guardians = |expected_version| ExecuteMsg::SetGuardians {
+            identifier: identifier1.clone(),
+            guardians: vec![
+                Addr::unchecked("guardian0001"),
+                Addr::unchecked("guardian0002"),
+            ],
+            threshold: 2,
+            delay: 100,
+            expected_version,
+        };
+        write_at(&mut deps, &env, &auth_info, set_guardians, 3);
+        let start_recovery = |expected_version| ExecuteMsg::StartRecovery {
+            identifier: identifier1.clone(),
+            new_controller: Addr::unchecked("addr0002"),
+            expected_version,
+        };
+        let guardian_info = mock_info("guardian0001", &[]);
+        write_at(&mut deps, &env, &guardian_info, start_recovery, 4);
+        let approve_recovery = |expected_version| ExecuteMsg::ApproveRecovery {
+            identifier: identifier1.clone(),
+            expected_version,
+        };
+        let guardian_info = mock_info("guardian0002", &[]);
+        write_at(&mut deps, &env, &guardian_info, approve_recovery, 5);
+        let cancel_recovery = |expected_version| ExecuteMsg::CancelRecovery {
+            identifier: identifier1.clone(),
+            expected_version,
+        };
+        write_at(&mut deps, &env, &auth_info, cancel_recovery, 6);
+
+        // timelocked changes
+        let set_policy = |expected_version| ExecuteMsg::SetControllerPolicy {
+            identifier: identifier1.clone(),
+            policy: Some(ControllerPolicy {
+                delay: Delay::Height(10),
+                veto: None,
+            }),
+            expected_version,
+        };
+        write_at(&mut deps, &env, &auth_info, set_policy, 7);
+        let remove_policy = |expected_version| ExecuteMsg::SetControllerPolicy {
+            identifier: identifier1.clone(),
+            policy: None,
+            expected_version,
+        };
+        write_at(&mut deps, &env, &auth_info, remove_policy, 8);
+        let cancel_change = |expected_version| ExecuteMsg::CancelPendingChange {
+            identifier: identifier1.clone(),
+            expected_version,
+        };
+        write_at(&mut deps, &env, &auth_info, cancel_change, 9);
+
+        let mut later = mock_env();
+        later.block.height += 10;
+        let execute_change = |expected_version| ExecuteMsg::ExecutePendingChange {
+            identifier: identifier1.clone(),
+            expected_version,
+        };
+        write_at(&mut deps, &env, &auth_info, set_guardians, 10);
+        write_at(&mut deps, &later, &auth_info, execute_change, 11);
+        write_at(&mut deps, &env, &auth_info, remove_policy, 12);
+        write_at(&mut deps, &later, &auth_info, execute_change, 13);
+
+        let commit = |expected_version| ExecuteMsg::CommitNextController {
+            identifier: identifier1.clone(),
+            next_hash: controller_hash(&Addr::unchecked("addr0002")),
+            expected_version,
+        };
+        write_at(&mut deps, &env, &auth_info, commit, 14);
+
+        let res = query(
+            deps.as_ref(),
+            mock_env(),
+            QueryMsg::Version {
+                identifier: identifier1,
+            },
+        )
+        .unwrap();
+        let value: VersionResponse = from_binary(&res).unwrap();
+        assert_eq!(value.version, 15);
+    }
+
+    #[test]
+    fn verification_methods() {
+        let mut deps = mock_dependencies_with_balance(&coins(2, "token"));
+        let msg = InstantiateMsg {
+            owner: None,
+            fees: vec![],
+            deposit_per_byte: None,
+            network: None,
+        };
+        let info = mock_info("creator", &coins(2, "token"));
+        let _res = instantiate(deps.as_mut(), mock_env(), info, msg).unwrap();
+
+        let identifier1 = Addr::unchecked("identifier0001");
+        let auth_info = mock_info("identifier0001", &[]);
+        let did_key = "z6MkhaXgBZDvotDkL5257faiztiGiC2QtKLGpbnnEGta2doK";
+
+        let msg = ExecuteMsg::AddVerificationMethod {
+            identifier: identifier1.clone(),
+            id: String::from("key-1"),
+            key_type: KeyType::Ed25519,
+            controller: None,
+            public_key: PublicKey::Multibase(String::from(did_key)),
+            relationships: vec![Relationship::Authentication],
+            validity: 1000,
+            expected_version: None,
+        };
+        let res = execute(deps.as_mut(), mock_env(), auth_info.clone(), msg.clone()).unwrap();
+        assert_eq!(get_attribute_value(res, "version"), "1");
+
+        let err = execute(deps.as_mut(), mock_env(), auth_info.clone(), msg).unwrap_err();
+        assert_eq!(
+            err,
+            ContractError::VerificationMethodExists {
+                id: String::from("key-1")
+            }
+        );
+
+        // JWK P-256 key, stored uncompressed
+        let msg = ExecuteMsg::AddVerificationMethod {
+            identifier: identifier1.clone(),
+            id: String::from("key-2"),
+            key_type: KeyType::P256,
+            controller: Some(Addr::unchecked("addr0001")),
+            public_key: PublicKey::Jwk(Jwk {
+                kty: String::from("EC"),
+                crv: String::from("P-256"),
+                x: String::from("f83OJ3D2xF1Bg8vub9tLe1gHMzV76e8Tus9uPHvRVEU"),
+                y: Some(String::from("x_FEzRu9m36HLN_tue659LNpXW6pCyStikYjKIWI5a0")),
+            }),
+            relationships: vec![Relationship::AssertionMethod],
+            validity: 1000,
+            expected_version: None,
+        };
+        let _res = execute(deps.as_mut(), mock_env(), auth_info.clone(), msg).unwrap();
+
+        // coordinates have to be 32 bytes each, even when they add up to a full key
+        for (key_type, kty, crv, x, y, reason) in [
+            (
+                KeyType::P256,
+                "EC",
+                "P-256",
+                "ERERERERERERERERERERERERERE",
+                Some("IiIiIiIiIiIiIiIiIiIiIiIiIiIiIiIiIiIiIiIiIiIiIiIiIiIiIiIiIiI"),
+                "invalid JWK x of 20 bytes",
+            ),
+            (
+                KeyType::P256,
+                "EC",
+                "P-256",
+                "IiIiIiIiIiIiIiIiIiIiIiIiIiIiIiIiIiIiIiIiIiI",
+                Some("IiIiIiIiIiIiIiIiIiIiIiIiIiIiIiIiIiIiIiIiIiIiIiIiIiIiIiIiIiI"),
+                "invalid JWK y of 44 bytes",
+            ),
+            (
+                KeyType::Ed25519,
+                "OKP",
+                "Ed25519",
+                "MzMzMzMzMzMzMzMzMzMzMzMzMzMzMzMzMzMzMzMzMw",
+                None,
+                "invalid JWK x of 31 bytes",
+            ),
+        ] {
+            let msg = ExecuteMsg::AddVerificationMethod {
+                identifier: identifier1.clone(),
+                id: String::from("key-jwk"),
+                key_type,
+                controller: None,
+                public_key: PublicKey::Jwk(Jwk {
+                    kty: String::from(kty),
+                    crv: String::from(crv),
+                    x: String::from(x),
+                    y: y.map(String::from),
+                }),
+                relationships: vec![Relationship::AssertionMethod],
+                validity: 1000,
+                expected_version: None,
+            };
+            let err = execute(deps.as_mut(), mock_env(), auth_info.clone(), msg).unwrap_err();
+            assert_eq!(
+                err,
+                ContractError::InvalidPublicKey {
+                    reason: String::from(reason)
+                }
+            );
+        }
+
+        let secp256k1_key = format!("0x02{}", "11".repeat(32));
+        let msg = ExecuteMsg::AddVerificationMethod {
+            identifier: identifier1.clone(),
+            id: String::from("key-3"),
+            key_type: KeyType::Secp256k1,
+            controller: None,
+            public_key: PublicKey::Hex(secp256k1_key.clone()),
+            relationships: vec![Relationship::Authentication],
+            validity: 1000,
+            expected_version: None,
+        };
+        let _res = execute(deps.as_mut(), mock_env(), auth_info.clone(), msg).unwrap();
+
+        let msg = ExecuteMsg::AddVerificationMethod {
+            identifier: identifier1.clone(),
+            id: String::from("key-4"),
+            key_type: KeyType::Secp256k1,
+            controller: None,
+            public_key: PublicKey::Hex(String::from("0x021111")),
+            relationships: vec![Relationship::Authentication],
+            validity: 1000,
+            expected_version: None,
+        };
+        let err = execute(deps.as_mut(), mock_env(), auth_info.clone(), msg).unwrap_err();
+        assert_eq!(
+            err,
+            ContractError::InvalidPublicKey {
+                reason: String::from("invalid Secp256k1 key of 3 bytes")
+            }
+        );
+
+        let msg = ExecuteMsg::AddVerificationMethod {
+            identifier: identifier1.clone(),
+            id: String::from("key-4"),
+            key_type: KeyType::X25519,
+            controller: None,
+            public_key: PublicKey::Hex("22".repeat(32)),
+            relationships: vec![Relationship::Authentication],
+            validity: 1000,
+            expected_version: None,
+        };
+        let err = execute(deps.as_mut(), mock_env(), auth_info.clone(), msg).unwrap_err();
+        assert_eq!(
+            err,
+            ContractError::InvalidRelationship {
+                key_type: KeyType::X25519,
+                relationship: Relationship::Authentication
+            }
+        );
+
+        let msg = ExecuteMsg::AddVerificationMethod {
+            identifier: identifier1.clone(),
+            id: String::from("key#4"),
+            key_type: KeyType::X25519,
+            controller: None,
+            public_key: PublicKey::Hex("22".repeat(32)),
+            relationships: vec![Relationship::KeyAgreement],
+            validity: 1000,
+            expected_version: None,
+        };
+        let err = execute(deps.as_mut(), mock_env(), auth_info.clone(), msg).unwrap_err();
+        assert_eq!(
+            err,
+            ContractError::InvalidVerificationMethodId {
+                id: String::from("key#4")
+            }
+        );
+
+        let msg = ExecuteMsg::RemoveVerificationMethod {
+            identifier: identifier1.clone(),
+            id: String::from("key-3"),
+            expected_version: None,
+        };
+        let _res = execute(deps.as_mut(), mock_env(), auth_info.clone(), msg.clone()).unwrap();
+        let err = execute(deps.as_mut(), mock_env(), auth_info, msg).unwrap_err();
+        assert_eq!(
+            err,
+            ContractError::VerificationMethodNotFound {
+                id: String::from("key-3")
+            }
+        );
+
+        let res = query(
+            deps.as_ref(),
+            mock_env(),
+            QueryMsg::Resolve {
+                identifier: identifier1.clone(),
+            },
+        )
+        .unwrap();
+        let value: ResolveResponse = from_binary(&res).unwrap();
+        let methods = value.document.verification_methods;
+        assert_eq!(methods.len(), 2);
+        assert_eq!(
+            methods[0].id,
+            "did:grano:cosmos-testnet-14002:identifier0001#key-1"
+        );
+        assert_eq!(
+            methods[0].controller,
+            format!("did:grano:cosmos-testnet-14002:{}", identifier1)
+        );
+        assert_eq!(methods[0].public_key_multibase, did_key);
+        assert_eq!(
+            methods[1].id,
+            "did:grano:cosmos-testnet-14002:identifier0001#key-2"
+        );
+        assert_eq!(
+            methods[1].controller,
+            "did:grano:cosmos-testnet-14002:addr0001"
+        );
+        let key = PublicKey::Multibase(methods[1].public_key_multibase.clone());
+        assert_eq!(decode_public_key(KeyType::P256, &key).unwrap().len(), 65);
+
+        // expired methods drop out of the document
+        let mut env = mock_env();
+        env.block.time = env.block.time.plus_seconds(1000);
+        let res = query(
+            deps.as_ref(),
+            env,
+            QueryMsg::Resolve {
+                identifier: identifier1,
+            },
+        )
+        .unwrap();
+        let value: ResolveResponse = from_binary(&res).unwrap();
+        assert!(value.document.verification_methods.is_empty());
+    }
+
+    #[test]
+    fn services() {
+        let mut deps = mock_dependencies_with_balance(&coins(2, "token"));
+        let msg = InstantiateMsg {
+            owner: None,
+            fees: vec![],
+            deposit_per_byte: None,
+            network: None,
+        };
+        let info = mock_info("creator", &coins(2, "token"));
+        let _res = instantiate(deps.as_mut(), mock_env(), info, msg).unwrap();
+
+        let identifier1 = Addr::unchecked("identifier0001");
+        let auth_info = mock_info("identifier0001", &[]);
+
+        let linked_domains = Service {
+            id: String::from("linked-domain"),
+            service_type: String::from("LinkedDomains"),
+            service_endpoint: ServiceEndpoint::Set(vec![
+                String::from("https://bar.example.com"),
+                String::from("https://foo.example.com"),
+            ]),
+            accept: vec![],
+            routing_keys: vec![],
+        };
+        // endpoints serialize as plain strings, arrays and maps
+        assert_eq!(
+            to_binary(&linked_domains.service_endpoint).unwrap(),
+            Binary::from(br#"["https://bar.example.com","https://foo.example.com"]"#)
+        );
+        assert_eq!(
+            to_binary(&ServiceEndpoint::Uri(String::from("https://example.com"))).unwrap(),
+            Binary::from(br#""https://example.com""#)
+        );
+        let msg = ExecuteMsg::AddService {
+            identifier: identifier1.clone(),
+            service: linked_domains.clone(),
+            expected_version: None,
+        };
+        let res = execute(deps.as_mut(), mock_env(), auth_info.clone(), msg.clone()).unwrap();
+        assert_eq!(get_attribute_value(res, "version"), "1");
+
+        let err = execute(deps.as_mut(), mock_env(), auth_info.clone(), msg).unwrap_err();
+        assert_eq!(
+            err,
+            ContractError::ServiceExists {
+                id: String::from("linked-domain")
+            }
+        );
+
+        // map endpoints as DIDCommMessaging uses them
+        let msg: ExecuteMsg = from_slice(
+            br#"{"add_service": {
+                "identifier": "identifier0001",
+                "service": {
+                    "id": "didcomm",
+                    "type": "DIDCommMessaging",
+                    "service_endpoint": {
+                        "uri": "https://example.com/path",
+                        "accept": ["didcomm/v2"],
+                        "routing_keys": ["did:example:somemediator#somekey"]
+                    }
+                }
+            }}"#,
+        )
+        .unwrap();
+        let _res = execute(deps.as_mut(), mock_env(), auth_info.clone(), msg).unwrap();
+
+        let mut invalid = Service {
+            id: String::from("hub"),
+            ..linked_domains.clone()
+        };
+        invalid.service_endpoint = ServiceEndpoint::Uri(String::from("https:/example.com"));
+        let msg = ExecuteMsg::AddService {
+            identifier: identifier1.clone(),
+            service: invalid,
+            expected_version: None,
+        };
+        let err = execute(deps.as_mut(), mock_env(), auth_info.clone(), msg).unwrap_err();
+        assert_eq!(
+            err,
+            ContractError::InvalidService {
+                reason: String::from("invalid endpoint https:/example.com")
+            }
+        );
+
+        let msg = ExecuteMsg::UpdateConfig {
+            paused: None,
+            paused_operations: None,
+            fees: None,
+            deposit_per_byte: None,
+            indexed_prefixes: None,
+            max_batch: None,
+            max_services: Some(2),
+        };
+        let _res = execute(deps.as_mut(), mock_env(), mock_info("creator", &[]), msg).unwrap();
+        let msg = ExecuteMsg::AddService {
+            identifier: identifier1.clone(),
+            service: Service {
+                id: String::from("hub"),
+                ..linked_domains
+            },
+            expected_version: None,
+        };
+        let err = execute(deps.as_mut(), mock_env(), auth_info.clone(), msg).unwrap_err();
+        assert_eq!(err, ContractError::TooManyServices { max: 2 });
+
+        let res = query(
+            deps.as_ref(),
+            mock_env(),
+            QueryMsg::Services {
+                identifier: identifier1.clone(),
+            },
+        )
+        .unwrap();
+        let value: ServicesResponse = from_binary(&res).unwrap();
+        assert_eq!(value.services.len(), 2);
+        assert_eq!(
+            value.services[0].id,
+            "did:grano:cosmos-testnet-14002:identifier0001#didcomm"
+        );
+        assert_eq!(
+            value.services[0].service_endpoint,
+            ServiceEndpoint::Map(ServiceEndpointMap {
+                uri: String::from("https://example.com/path"),
+                accept: vec![String::from("didcomm/v2")],
+                routing_keys: vec![String::from("did:example:somemediator#somekey")],
+            })
+        );
+        assert_eq!(
+            value.services[1].id,
+            "did:grano:cosmos-testnet-14002:identifier0001#linked-domain"
+        );
+
+        let msg = ExecuteMsg::RemoveService {
+            identifier: identifier1.clone(),
+            id: String::from("didcomm"),
+            expected_version: None,
+        };
+        let _res = execute(deps.as_mut(), mock_env(), auth_info.clone(), msg.clone()).unwrap();
+        let err = execute(deps.as_mut(), mock_env(), auth_info, msg).unwrap_err();
+        assert_eq!(
+            err,
+            ContractError::ServiceNotFound {
+                id: String::from("didcomm")
+            }
+        );
+
+        let res = query(
+            deps.as_ref(),
+            mock_env(),
+            QueryMsg::Resolve {
+                identifier: identifier1,
+            },
+        )
+        .unwrap();
+        let value: ResolveResponse = from_binary(&res).unwrap();
+        assert_eq!(value.document.services.len(), 1);
+        assert_eq!(value.document.services[0].service_type, "LinkedDomains");
+    }
+
+    #[test]
+    fn verification_relationships() {
+        let mut deps = mock_dependencies_with_balance(&coins(2, "token"));
+        let msg = InstantiateMsg {
+            owner: None,
+            fees: vec![],
+            deposit_per_byte: None,
+            network: None,
+        };
+        let info = mock_info("creator", &coins(2, "token"));
+        let _res = instantiate(deps.as_mut(), mock_env(), info, msg).unwrap();
+
+        let identifier1 = Addr::unchecked("identifier0001");
+        let auth_info = mock_info("identifier0001", &[]);
+        let keys_for = |deps: Deps, env: Env, relationship: Relationship| {
+            let res = query(
+                deps,
+                env,
+                QueryMsg::KeysForRelationship {
+                    identifier: Addr::unchecked("identifier0001"),
+                    relationship,
+                },
+            )
+            .unwrap();
+            from_binary::<KeysForRelationshipResponse>(&res).unwrap()
+        };
+
+        let msg = ExecuteMsg::AddVerificationMethod {
+            identifier: identifier1.clone(),
+            id: String::from("key-1"),
+            key_type: KeyType::Ed25519,
+            controller: None,
+            public_key: PublicKey::Hex("11".repeat(32)),
+            relationships: vec![Relationship::Authentication],
+            validity: 1000,
+            expected_version: None,
+        };
+        let _res = execute(deps.as_mut(), mock_env(), auth_info.clone(), msg).unwrap();
+
+        // the grant outliving the key ends with it
+        let msg = ExecuteMsg::GrantRelationship {
+            identifier: identifier1.clone(),
+            target: RelationshipTarget::Key {
+                id: String::from("key-1"),
+            },
+            relationship: Relationship::AssertionMethod,
+            validity: 5000,
+            expected_version: None,
+        };
+        let res = execute(deps.as_mut(), mock_env(), auth_info.clone(), msg).unwrap();
+        assert_eq!(get_attribute_value(res, "relationship"), "assertion_method");
+
+        let msg = ExecuteMsg::GrantRelationship {
+            identifier: identifier1.clone(),
+            target: RelationshipTarget::Delegate {
+                address: Addr::unchecked("issuer0001"),
+            },
+            relationship: Relationship::AssertionMethod,
+            validity: 500,
+            expected_version: None,
+        };
+        let res = execute(deps.as_mut(), mock_env(), auth_info.clone(), msg).unwrap();
+        assert_eq!(get_attribute_value(res, "delegate"), "issuer0001");
+
+        let msg = ExecuteMsg::GrantRelationship {
+            identifier: identifier1.clone(),
+            target: RelationshipTarget::Delegate {
+                address: Addr::unchecked("issuer0001"),
+            },
+            relationship: Relationship::KeyAgreement,
+            validity: 500,
+            expected_version: None,
+        };
+        let err = execute(deps.as_mut(), mock_env(), auth_info.clone(), msg).unwrap_err();
+        assert_eq!(
+            err,
+            ContractError::InvalidDelegateRelationship {
+                relationship: Relationship::KeyAgreement
+            }
+        );
+
+        let msg = ExecuteMsg::GrantRelationship {
+            identifier: identifier1.clone(),
+            target: RelationshipTarget::Key {
+                id: String::from("key-2"),
+            },
+            relationship: Relationship::AssertionMethod,
+            validity: 500,
+            expected_version: None,
+        };
+        let err = execute(deps.as_mut(), mock_env(), auth_info.clone(), msg).unwrap_err();
+        assert_eq!(
+            err,
+            ContractError::VerificationMethodNotFound {
+                id: String::from("key-2")
+            }
+        );
+
+        let value = keys_for(deps.as_ref(), mock_env(), Relationship::AssertionMethod);
+        assert_eq!(value.keys.len(), 1);
+        assert_eq!(
+            value.keys[0].id,
+            "did:grano:cosmos-testnet-14002:identifier0001#key-1"
+        );
+        assert_eq!(
+            value.keys[0].relationships,
+            vec![RelationshipValidity {
+                relationship: Relationship::AssertionMethod,
+                valid_to: Some(mock_env().block.time.plus_seconds(1000)),
+            }]
+        );
+        assert_eq!(
+            value.delegates,
+            vec![DelegateInfo {
+                delegate: String::from("did:grano:cosmos-testnet-14002:issuer0001"),
+                relationships: vec![RelationshipValidity {
+                    relationship: Relationship::AssertionMethod,
+                    valid_to: Some(mock_env().block.time.plus_seconds(500)),
+                }],
+            }]
+        );
+        let value = keys_for(
+            deps.as_ref(),
+            mock_env(),
+            Relationship::CapabilityInvocation,
+        );
+        assert!(value.keys.is_empty() && value.delegates.is_empty());
+
+        // the delegate runs out before the key
+        let mut env = mock_env();
+        env.block.time = env.block.time.plus_seconds(500);
+        let value = keys_for(deps.as_ref(), env, Relationship::AssertionMethod);
+        assert_eq!(value.keys.len(), 1);
+        assert!(value.delegates.is_empty());
+
+        let msg = ExecuteMsg::RevokeRelationship {
+            identifier: identifier1.clone(),
+            target: RelationshipTarget::Key {
+                id: String::from("key-1"),
+            },
+            relationship: Relationship::AssertionMethod,
+            expected_version: None,
+        };
+        let _res = execute(deps.as_mut(), mock_env(), auth_info, msg).unwrap();
+        let value = keys_for(deps.as_ref(), mock_env(), Relationship::AssertionMethod);
+        assert!(value.keys.is_empty());
+
+        let res = query(
+            deps.as_ref(),
+            mock_env(),
+            QueryMsg::Resolve {
+                identifier: identifier1,
+            },
+        )
+        .unwrap();
+        let value: ResolveResponse = from_binary(&res).unwrap();
+        assert_eq!(
+            value.document.verification_methods[0].relationships,
+            vec![RelationshipValidity {
+                relationship: Relationship::Authentication,
+                valid_to: Some(mock_env().block.time.plus_seconds(1000)),
+            }]
+        );
+        assert_eq!(value.document.delegates.len(), 1);
+    }
+
+    #[test]
+    fn verify_signature() {
+        use k256::ecdsa::signature::Signer;
+
+        let mut deps = mock_dependencies_with_balance(&coins(2, "token"));
+        let msg = InstantiateMsg {
+            owner: None,
+            fees: vec![],
+            deposit_per_byte: None,
+            network: None,
+        };
+        let info = mock_info("creator", &coins(2, "token"));
+        let _res = instantiate(deps.as_mut(), mock_env(), info, msg).unwrap();
+
+        let identifier1 = Addr::unchecked("identifier0001");
+        let auth_info = mock_info("identifier0001", &[]);
+        let message = Binary::from(b"credential".to_vec());
+        let verify = |deps: Deps, signature: Vec<u8>, relationship: Relationship| {
+            let res = query(
+                deps,
+                mock_env(),
+                QueryMsg::VerifySignature {
+                    identifier: Addr::unchecked("identifier0001"),
+                    message: Binary::from(b"credential".to_vec()),
+                    signature: Binary::from(signature),
+                    relationship,
+                },
+            )
+            .unwrap();
+            from_binary::<VerifySignatureResponse>(&res).unwrap().method
+        };
+
+        let ed25519_key = ed25519_zebra::SigningKey::from([7u8; 32]);
+        let public_key = ed25519_zebra::VerificationKey::from(&ed25519_key);
+        let msg = ExecuteMsg::AddVerificationMethod {
+            identifier: identifier1.clone(),
+            id: String::from("key-1"),
+            key_type: KeyType::Ed25519,
+            controller: None,
+            public_key: PublicKey::Multibase(format!(
+                "m{}",
+                Binary::from(public_key.as_ref()).to_base64()
+            )),
+            relationships: vec![Relationship::Authentication],
+            validity: 1000,
+            expected_version: None,
+        };
+        let _res = execute(deps.as_mut(), mock_env(), auth_info.clone(), msg).unwrap();
+
+        let secp256k1_key = k256::ecdsa::SigningKey::from_bytes(&[9u8; 32]).unwrap();
+        let public_key = secp256k1_key.verifying_key().to_bytes();
+        let msg = ExecuteMsg::SetAttribute {
+            identifier: identifier1,
+            name: String::from("did/pub/Secp256k1/veriKey/hex"),
+            value: public_key.iter().map(|b| format!("{:02x}", b)).collect(),
+            validity: 1000,
+            expected_version: None,
+        };
+        let _res = execute(deps.as_mut(), mock_env(), auth_info, msg).unwrap();
+
+        let signature = <[u8; 64]>::from(ed25519_key.sign(&message)).to_vec();
+        assert_eq!(
+            verify(
+                deps.as_ref(),
+                signature.clone(),
+                Relationship::Authentication
+            ),
+            Some(SignatureMethod::Key {
+                id: String::from("did:grano:cosmos-testnet-14002:identifier0001#key-1")
+            })
+        );
+        // the key is not granted for assertions
+        assert_eq!(
+            verify(deps.as_ref(), signature, Relationship::AssertionMethod),
+            None
+        );
+
+        let signature: k256::ecdsa::Signature = secp256k1_key.sign(&message);
+        let signature = signature.as_ref().to_vec();
+        assert_eq!(
+            verify(
+                deps.as_ref(),
+                signature.clone(),
+                Relationship::AssertionMethod
+            ),
+            Some(SignatureMethod::Attribute {
+                name: String::from("did/pub/Secp256k1/veriKey/hex"),
+                value: public_key.iter().map(|b| format!("{:02x}", b)).collect(),
+            })
+        );
+        // veriKey keys do not authenticate
+        assert_eq!(
+            verify(
+                deps.as_ref(),
+                signature.clone(),
+                Relationship::Authentication
+            ),
+            None
+        );
+
+        let mut tampered = signature;
+        tampered[10] ^= 1;
+        assert_eq!(
+            verify(deps.as_ref(), tampered, Relationship::AssertionMethod),
+            None
+        );
+    }
+
+    /// answers auth Account queries for the accounts with a public key
+    struct AuthQuerier {
+        base: MockQuerier,
+        accounts: Vec<(Addr, &'static str, Vec<u8>)>,
+    }
+
+    impl Querier for AuthQuerier {
+        fn raw_query(&self, bin_request: &[u8]) -> QuerierResult {
+            let request: QueryRequest<Empty> = from_slice(bin_request).unwrap();
+            match request {
+                QueryRequest::Stargate { path, data } if path == ACCOUNT_QUERY_PATH => {
+                    let account = self
+                        .accounts
+                        .iter()
+                        .find(|(address, ..)| encode_field(1, address.as_bytes()) == data.0);
+                    match account {
+                        Some((address, type_url, key)) => {
+                            let public_key = [
+                                encode_field(1, type_url.as_bytes()),
+                                encode_field(2, &encode_field(1, key)),
+                            ]
+                            .concat();
+                            let base_account = [
+                                encode_field(1, address.as_bytes()),
+                                encode_field(2, &public_key),
+                            ]
+                            .concat();
+                            let account = [
+                                encode_field(1, b"/cosmos.auth.v1beta1.BaseAccount"),
+                                encode_field(2, &base_account),
+                            ]
+                            .concat();
+                            SystemResult::Ok(ContractResult::Ok(Binary::from(encode_field(
+                                1, &account,
+                            ))))
+                        }
+                        None => SystemResult::Ok(ContractResult::Err(format!(
+                            "account {:?} not found",
+                            data
+                        ))),
+                    }
+                }
+                _ => self.base.raw_query(bin_request),
+            }
+        }
+    }
+
+    #[test]
+    fn controller_verification_method() {
+        let key = [vec![2], vec![5u8; 32]].concat();
+        let mut deps = OwnedDeps {
+            storage: MockStorage::default(),
+            api: MockApi::default(),
+            querier: AuthQuerier {
+                base: MockQuerier::default(),
+                accounts: vec![
+                    (
+                        Addr::unchecked("identifier0001"),
+                        "/cosmos.crypto.secp256k1.PubKey",
+                        key.clone(),
+                    ),
+                    (
+                        Addr::unchecked("addr0001"),
+                        "/cosmos.crypto.secp256k1.PubKey",
+                        key.clone(),
+                    ),
+                ],
+            },
+            custom_query_type: PhantomData,
+        };
+        let msg = InstantiateMsg {
+            owner: None,
+            fees: vec![],
+            deposit_per_byte: None,
+            network: None,
+        };
+        let info = mock_info("creator", &coins(2, "token"));
+        let _res = instantiate(deps.as_mut(), mock_env(), info, msg).unwrap();
+
+        let identifier1 = Addr::unchecked("identifier0001");
+        let resolve_methods = |deps: Deps, env: Env| {
+            let res = query(
+                deps,
+                env,
+                QueryMsg::Resolve {
+                    identifier: Addr::unchecked("identifier0001"),
+                },
+            )
+            .unwrap();
+            from_binary::<ResolveResponse>(&res)
+                .unwrap()
+                .document
+                .verification_methods
+        };
+
+        // the account key resolves without any transaction to the registry
+        let methods = resolve_methods(deps.as_ref(), mock_env());
+        assert_eq!(methods.len(), 1);
+        assert_eq!(
+            methods[0].id,
+            "did:grano:cosmos-testnet-14002:identifier0001#controller"
+        );
+        assert_eq!(methods[0].key_type, KeyType::Secp256k1);
+        assert_eq!(
+            methods[0].controller,
+            format!("did:grano:cosmos-testnet-14002:{}", identifier1)
+        );
+        assert_eq!(
+            methods[0].public_key_multibase,
+            to_multibase(KeyType::Secp256k1, &key)
+        );
+        assert_eq!(methods[0].relationships.len(), 4);
+        assert_eq!(methods[0].valid_to, None);
 
+        // the key of a lessee is valid until the lease ends
+        let expires = mock_env().block.time.plus_seconds(100);
         let msg = ExecuteMsg::ChangeController {
-            identifier: Addr::unchecked(&identifier1),
-            new_controller: Addr::unchecked(&controller1),
+            identifier: identifier1.clone(),
+            new_controller: Addr::unchecked("addr0001"),
+            next_hash: None,
+            lease: Some(Lease {
+                expires,
+                fallback: None,
+            }),
+            expected_version: None,
+        };
+        let _res = execute(
+            deps.as_mut(),
+            mock_env(),
+            mock_info("identifier0001", &[]),
+            msg,
+        )
+        .unwrap();
+        let methods = resolve_methods(deps.as_ref(), mock_env());
+        assert_eq!(
+            methods[0].controller,
+            "did:grano:cosmos-testnet-14002:addr0001"
+        );
+        assert_eq!(methods[0].valid_to, Some(expires));
+        assert!(methods[0]
+            .relationships
+            .iter()
+            .all(|relationship| relationship.valid_to == Some(expires)));
+        let next_update = |deps: Deps, env: Env| {
+            let res = query(
+                deps,
+                env,
+                QueryMsg::Metadata {
+                    identifier: Addr::unchecked("identifier0001"),
+                },
+            )
+            .unwrap();
+            from_binary::<MetadataResponse>(&res)
+                .unwrap()
+                .did_document_metadata
+                .next_update
+        };
+        assert_eq!(next_update(deps.as_ref(), mock_env()), Some(expires));
+
+        // then the key of the fallback is in effect without an end
+        let mut env = mock_env();
+        env.block.time = expires;
+        let methods = resolve_methods(deps.as_ref(), env.clone());
+        assert_eq!(
+            methods[0].controller,
+            format!("did:grano:cosmos-testnet-14002:{}", identifier1)
+        );
+        assert_eq!(methods[0].valid_to, None);
+        assert!(methods[0]
+            .relationships
+            .iter()
+            .all(|relationship| relationship.valid_to.is_none()));
+        assert_eq!(next_update(deps.as_ref(), env.clone()), None);
+
+        // the new controller has no public key yet
+        let msg = ExecuteMsg::ChangeController {
+            identifier: identifier1,
+            new_controller: Addr::unchecked("addr0002"),
+            next_hash: None,
+            lease: None,
+            expected_version: None,
+        };
+        let _res = execute(
+            deps.as_mut(),
+            env.clone(),
+            mock_info("identifier0001", &[]),
+            msg,
+        )
+        .unwrap();
+        assert!(resolve_methods(deps.as_ref(), env).is_empty());
+    }
+
+    #[test]
+    fn dereference() {
+        let mut deps = mock_dependencies_with_balance(&coins(2, "token"));
+        let msg = InstantiateMsg {
+            owner: None,
+            fees: vec![],
+            deposit_per_byte: None,
+            network: None,
+        };
+        let info = mock_info("creator", &coins(2, "token"));
+        let _res = instantiate(deps.as_mut(), mock_env(), info, msg).unwrap();
+
+        let identifier1 = Addr::unchecked("identifier0001");
+        let auth_info = mock_info("identifier0001", &[]);
+        let dereference = |deps: Deps, did_url: &str| {
+            let res = query(
+                deps,
+                mock_env(),
+                QueryMsg::Dereference {
+                    did_url: did_url.to_string(),
+                },
+            )
+            .unwrap();
+            from_binary::<DereferenceResponse>(&res).unwrap()
         };
 
+        let msg = ExecuteMsg::AddVerificationMethod {
+            identifier: identifier1.clone(),
+            id: String::from("key-1"),
+            key_type: KeyType::Ed25519,
+            controller: None,
+            public_key: PublicKey::Hex("11".repeat(32)),
+            relationships: vec![Relationship::Authentication],
+            validity: 1000,
+            expected_version: None,
+        };
+        let _res = execute(deps.as_mut(), mock_env(), auth_info.clone(), msg).unwrap();
+        let msg = ExecuteMsg::AddService {
+            identifier: identifier1,
+            service: Service {
+                id: String::from("agent"),
+                service_type: String::from("DIDCommMessaging"),
+                service_endpoint: ServiceEndpoint::Uri(String::from("https://agent.example.com/")),
+                accept: vec![],
+                routing_keys: vec![],
+            },
+            expected_version: None,
+        };
         let _res = execute(deps.as_mut(), mock_env(), auth_info, msg).unwrap();
 
+        let value = dereference(deps.as_ref(), "did:grano:identifier0001");
+        assert!(matches!(
+            value.content,
+            Some(DereferencedContent::Document(document)) if document.id == "did:grano:cosmos-testnet-14002:identifier0001"
+        ));
+
+        let value = dereference(deps.as_ref(), "did:grano:identifier0001#key-1");
+        assert!(matches!(
+            value.content,
+            Some(DereferencedContent::VerificationMethod(method))
+                if method.id == "did:grano:cosmos-testnet-14002:identifier0001#key-1"
+        ));
+
+        let value = dereference(deps.as_ref(), "did:grano:identifier0001#agent");
+        assert!(matches!(
+            value.content,
+            Some(DereferencedContent::Service(service)) if service.id == "did:grano:cosmos-testnet-14002:identifier0001#agent"
+        ));
+
+        let value = dereference(
+            deps.as_ref(),
+            "did:grano:identifier0001?service=agent&relativeRef=%2Finbox",
+        );
+        assert_eq!(
+            value.content,
+            Some(DereferencedContent::Url(String::from(
+                "https://agent.example.com/inbox"
+            )))
+        );
+
+        let errors = [
+            (
+                "did:grano:identifier0001#key-9",
+                DereferenceError::UnknownFragment {
+                    fragment: String::from("key-9"),
+                },
+            ),
+            (
+                "did:grano:identifier0001?service=hub",
+                DereferenceError::UnknownService {
+                    service: String::from("hub"),
+                },
+            ),
+            (
+                "did:example:identifier0001",
+                DereferenceError::InvalidDidUrl {
+                    reason: String::from("unsupported DID method example"),
+                },
+            ),
+            (
+                "did:grano:identifier0001?relativeRef=/inbox",
+                DereferenceError::InvalidDidUrl {
+                    reason: String::from("relativeRef without service"),
+                },
+            ),
+            (
+                "did:grano:identifier0001/path",
+                DereferenceError::UnsupportedPath {
+                    path: String::from("/path"),
+                },
+            ),
+            (
+                "did:grano:identifier0001?hl=zQmWvQxTqbG2Z9HPJgG57jjwR154cKhbtJenbyYTWkjgF3e",
+                DereferenceError::UnsupportedParameter {
+                    name: String::from("hl"),
+                },
+            ),
+        ];
+        for (did_url, error) in errors {
+            let value = dereference(deps.as_ref(), did_url);
+            assert_eq!(value.content, None);
+            assert_eq!(value.error, Some(error));
+        }
+    }
+
+    #[test]
+    fn dids() {
+        let mut deps = mock_dependencies_with_balance(&coins(2, "token"));
+        let msg = InstantiateMsg {
+            owner: None,
+            fees: vec![],
+            deposit_per_byte: None,
+            network: Some(String::from("grano-1")),
+        };
+        let info = mock_info("creator", &coins(2, "token"));
+        let _res = instantiate(deps.as_mut(), mock_env(), info, msg).unwrap();
+
+        let res = query(deps.as_ref(), mock_env(), QueryMsg::Config {}).unwrap();
+        let value: ConfigResponse = from_binary(&res).unwrap();
+        assert_eq!(value.network, Some(String::from("grano-1")));
+
+        // identifiers and accounts are given as DIDs or bare addresses
+        let auth_info = mock_info("identifier0001", &[]);
+        let msg = ExecuteMsg::ChangeController {
+            identifier: Addr::unchecked("did:grano:grano-1:identifier0001"),
+            new_controller: Addr::unchecked("did:grano:addr0001"),
+            next_hash: None,
+            lease: None,
+            expected_version: None,
+        };
+        let res = execute(deps.as_mut(), mock_env(), auth_info.clone(), msg).unwrap();
+        assert_eq!(
+            get_attribute_value(res.clone(), "identifier"),
+            "identifier0001"
+        );
+        assert_eq!(
+            get_attribute_value(res, "did"),
+            "did:grano:grano-1:identifier0001"
+        );
+
+        let res = query(
+            deps.as_ref(),
+            mock_env(),
+            QueryMsg::Controller {
+                identifier: Addr::unchecked("did:grano:grano-1:identifier0001"),
+            },
+        )
+        .unwrap();
+        let value: ControllerResponse = from_binary(&res).unwrap();
+        assert_eq!(value.controller, Addr::unchecked("addr0001"));
+
         let res = query(
             deps.as_ref(),
             mock_env(),
-            QueryMsg::Controller {
-                identifier: Addr::unchecked(&identifier1),
+            QueryMsg::Resolve {
+                identifier: Addr::unchecked("identifier0001"),
             },
         )
         .unwrap();
-        let value: ControllerResponse = from_binary(&res).unwrap();
-        assert_eq!(controller1, value.controller);
-
-        // only the controller address can change the controller
-        let auth_info = mock_info("addr0001", &coins(2, "token"));
-        let controller2 = String::from("addr0002");
+        let value: ResolveResponse = from_binary(&res).unwrap();
+        assert_eq!(value.document.id, "did:grano:grano-1:identifier0001");
+        assert_eq!(value.document.controller, "did:grano:grano-1:addr0001");
 
-        let msg = ExecuteMsg::ChangeController {
-            identifier: Addr::unchecked(&identifier1),
-            new_controller: Addr::unchecked(&controller2),
+        // DIDs of other networks and DID URLs are rejected
+        let msg = ExecuteMsg::SetAttribute {
+            identifier: Addr::unchecked("did:grano:grano-2:identifier0001"),
+            name: String::from("service"),
+            value: String::from("github"),
+            validity: 1000,
+            expected_version: None,
         };
+        let err = execute(deps.as_mut(), mock_env(), auth_info, msg).unwrap_err();
+        assert_eq!(
+            err,
+            ContractError::InvalidDid {
+                did: String::from("did:grano:grano-2:identifier0001"),
+                reason: String::from("DID of network grano-2"),
+            }
+        );
+        let err = query(
+            deps.as_ref(),
+            mock_env(),
+            QueryMsg::Version {
+                identifier: Addr::unchecked("did:grano:grano-1:identifier0001#key-1"),
+            },
+        )
+        .unwrap_err();
+        assert_eq!(
+            err,
+            StdError::generic_err(
+                "Invalid DID did:grano:grano-1:identifier0001#key-1: DID URL instead of a DID"
+            )
+        );
 
-        let _res = execute(deps.as_mut(), mock_env(), auth_info, msg).unwrap();
-
+        // pagination cursors, the veto key and governance take DIDs as well
         let res = query(
             deps.as_ref(),
             mock_env(),
-            QueryMsg::Controller {
-                identifier: Addr::unchecked(&identifier1),
+            QueryMsg::AllIdentifiers {
+                start_after: Some(Addr::unchecked("did:grano:grano-1:identifier0000")),
+                limit: None,
             },
         )
         .unwrap();
-        let value: ControllerResponse = from_binary(&res).unwrap();
-        assert_eq!(controller2, value.controller);
+        let value: AllIdentifiersResponse = from_binary(&res).unwrap();
+        assert_eq!(value.identifiers.len(), 1);
 
+        let msg = ExecuteMsg::SetControllerPolicy {
+            identifier: Addr::unchecked("identifier0001"),
+            policy: Some(ControllerPolicy {
+                delay: Delay::Time(100),
+                veto: Some(Addr::unchecked("did:grano:grano-1:veto0001")),
+            }),
+            expected_version: None,
+        };
+        let _res = execute(deps.as_mut(), mock_env(), mock_info("addr0001", &[]), msg).unwrap();
         let res = query(
             deps.as_ref(),
             mock_env(),
-            QueryMsg::Changed {
-                identifier: Addr::unchecked(&identifier1),
+            QueryMsg::ControllerPolicy {
+                identifier: Addr::unchecked("identifier0001"),
             },
         )
         .unwrap();
-        let value: ChangedResponse = from_binary(&res).unwrap();
-        assert_ne!(0, value.block);
+        let value: ControllerPolicyResponse = from_binary(&res).unwrap();
+        assert_eq!(
+            value.policy.unwrap().veto,
+            Some(Addr::unchecked("veto0001"))
+        );
+
+        let msg = SudoMsg::ForceDeactivate {
+            identifier: Addr::unchecked("did:grano:grano-1:identifier0001"),
+            proposal_id: 1,
+        };
+        let res = sudo(deps.as_mut(), mock_env(), msg).unwrap();
+        assert!(res.events[0]
+            .attributes
+            .iter()
+            .any(|a| a.key == "identifier" && a.value == "identifier0001"));
     }
 
     #[test]
-    fn change_controller_by_attacker() {
-        let mut deps = mock_dependencies_with_balance(&coins(2, "token"));
-        let msg = InstantiateMsg {};
+    fn dids_in_cursors_vetoes_and_sudo() {
+        use k256::ecdsa::signature::Signer;
+
+        let controller_key = k256::ecdsa::SigningKey::from_bytes(&[3u8; 32]).unwrap();
+        let delegate_key = k256::ecdsa::SigningKey::from_bytes(&[4u8; 32]).unwrap();
+        let mut deps = OwnedDeps {
+            storage: MockStorage::default(),
+            api: AccountApi {
+                base: MockApi::default(),
+                accounts: vec![
+                    (
+                        Addr::unchecked("addr0001"),
+                        controller_key.verifying_key().to_bytes().to_vec(),
+                    ),
+                    (
+                        Addr::unchecked("issuer0001"),
+                        delegate_key.verifying_key().to_bytes().to_vec(),
+                    ),
+                ],
+            },
+            querier: MockQuerier::default(),
+            custom_query_type: PhantomData,
+        };
+        let msg = InstantiateMsg {
+            owner: None,
+            fees: vec![],
+            deposit_per_byte: None,
+            network: Some(String::from("grano-1")),
+        };
         let info = mock_info("creator", &coins(2, "token"));
         let _res = instantiate(deps.as_mut(), mock_env(), info, msg).unwrap();
+        let msg = ExecuteMsg::UpdateConfig {
+            paused: None,
+            paused_operations: None,
+            fees: None,
+            deposit_per_byte: None,
+            indexed_prefixes: Some(vec![String::from("service")]),
+            max_batch: None,
+            max_services: None,
+        };
+        let _res = execute(deps.as_mut(), mock_env(), mock_info("creator", &[]), msg).unwrap();
 
-        let identifier1 = String::from("identifier0001");
-        let controller1 = String::from("addr0001");
+        let controller_info = mock_info("addr0001", &[]);
+        for identifier in ["identifier0001", "identifier0002", "identifier0003"] {
+            let msg = ExecuteMsg::ChangeController {
+                identifier: Addr::unchecked(identifier),
+                new_controller: Addr::unchecked("did:grano:grano-1:addr0001"),
+                next_hash: None,
+                lease: None,
+                expected_version: None,
+            };
+            let _res = execute(deps.as_mut(), mock_env(), mock_info(identifier, &[]), msg).unwrap();
+            let msg = ExecuteMsg::SetAttribute {
+                identifier: Addr::unchecked(identifier),
+                name: String::from("service"),
+                value: String::from("github"),
+                validity: 1000,
+                expected_version: None,
+            };
+            let _res = execute(deps.as_mut(), mock_env(), controller_info.clone(), msg).unwrap();
+        }
 
-        // only the original identifier address can change the controller at the first time
-        let auth_info = mock_info("attacker", &coins(2, "token"));
+        // pagination cursors
+        let all_identifiers = |deps: Deps, start_after: &str| {
+            query(
+                deps,
+                mock_env(),
+                QueryMsg::AllIdentifiers {
+                    start_after: Some(Addr::unchecked(start_after)),
+                    limit: None,
+                },
+            )
+            .map(|res| {
+                from_binary::<AllIdentifiersResponse>(&res)
+                    .unwrap()
+                    .identifiers
+                    .into_iter()
+                    .map(|info| info.identifier)
+                    .collect::<Vec<_>>()
+            })
+        };
+        let by_controller = |deps: Deps, start_after: &str| {
+            query(
+                deps,
+                mock_env(),
+                QueryMsg::IdentifiersByController {
+                    controller: Addr::unchecked("did:grano:grano-1:addr0001"),
+                    start_after: Some(Addr::unchecked(start_after)),
+                    limit: None,
+                },
+            )
+            .map(|res| {
+                from_binary::<IdentifiersResponse>(&res)
+                    .unwrap()
+                    .identifiers
+            })
+        };
+        let by_attribute = |deps: Deps, start_after: Option<&str>| {
+            query(
+                deps,
+                mock_env(),
+                QueryMsg::IdentifiersByAttribute {
+                    name: String::from("service"),
+                    value: String::from("github"),
+                    start_after: start_after.map(Addr::unchecked),
+                    limit: None,
+                },
+            )
+            .map(|res| {
+                from_binary::<IdentifiersResponse>(&res)
+                    .unwrap()
+                    .identifiers
+            })
+        };
+        let valid = "did:grano:grano-1:identifier0001";
+        let invalid = "did:grano:grano-2:identifier0001";
+        let rest = vec![
+            Addr::unchecked("identifier0002"),
+            Addr::unchecked("identifier0003"),
+        ];
+        let wrong_network = || {
+            Err(StdError::generic_err(
+                "Invalid DID did:grano:grano-2:identifier0001: DID of network grano-2",
+            ))
+        };
+        assert_eq!(all_identifiers(deps.as_ref(), valid), Ok(rest.clone()));
+        assert_eq!(all_identifiers(deps.as_ref(), invalid), wrong_network());
+        assert_eq!(by_controller(deps.as_ref(), valid), Ok(rest.clone()));
+        assert_eq!(by_controller(deps.as_ref(), invalid), wrong_network());
+        assert_eq!(by_attribute(deps.as_ref(), Some(valid)), Ok(rest));
+        assert_eq!(by_attribute(deps.as_ref(), Some(invalid)), wrong_network());
 
+        // the veto key
+        let policy = |veto: &str| ExecuteMsg::SetControllerPolicy {
+            identifier: Addr::unchecked("identifier0001"),
+            policy: Some(ControllerPolicy {
+                delay: Delay::Height(10),
+                veto: Some(Addr::unchecked(veto)),
+            }),
+            expected_version: None,
+        };
+        let err = execute(
+            deps.as_mut(),
+            mock_env(),
+            controller_info.clone(),
+            policy("did:grano:grano-2:veto0001"),
+        )
+        .unwrap_err();
+        assert_eq!(
+            err,
+            ContractError::InvalidDid {
+                did: String::from("did:grano:grano-2:veto0001"),
+                reason: String::from("DID of network grano-2"),
+            }
+        );
+        let _res = execute(
+            deps.as_mut(),
+            mock_env(),
+            controller_info.clone(),
+            policy("did:grano:grano-1:veto0001"),
+        )
+        .unwrap();
         let msg = ExecuteMsg::ChangeController {
-            identifier: Addr::unchecked(&identifier1),
-            new_controller: Addr::unchecked(&controller1),
+            identifier: Addr::unchecked("identifier0001"),
+            new_controller: Addr::unchecked("addr0002"),
+            next_hash: None,
+            lease: None,
+            expected_version: None,
         };
+        let _res = execute(deps.as_mut(), mock_env(), controller_info.clone(), msg).unwrap();
+        let msg = ExecuteMsg::CancelPendingChange {
+            identifier: Addr::unchecked("identifier0001"),
+            expected_version: None,
+        };
+        let _res = execute(deps.as_mut(), mock_env(), mock_info("veto0001", &[]), msg).unwrap();
 
-        let err = execute(deps.as_mut(), mock_env(), auth_info, msg).unwrap_err();
-        assert_eq!(err, ContractError::Unauthorized {});
+        // accounts that signed are returned as DIDs
+        let message = b"credential";
+        let verify = |deps: Deps, signature: k256::ecdsa::Signature, relationship| {
+            let res = query(
+                deps,
+                mock_env(),
+                QueryMsg::VerifySignature {
+                    identifier: Addr::unchecked("did:grano:grano-1:identifier0001"),
+                    message: Binary::from(message.to_vec()),
+                    signature: Binary::from(signature.as_ref().to_vec()),
+                    relationship,
+                },
+            )
+            .unwrap();
+            from_binary::<VerifySignatureResponse>(&res).unwrap().method
+        };
+        assert_eq!(
+            verify(
+                deps.as_ref(),
+                controller_key.sign(message),
+                Relationship::Authentication
+            ),
+            Some(SignatureMethod::Controller {
+                did: String::from("did:grano:grano-1:addr0001")
+            })
+        );
+        let msg = ExecuteMsg::GrantRelationship {
+            identifier: Addr::unchecked("identifier0001"),
+            target: RelationshipTarget::Delegate {
+                address: Addr::unchecked("did:grano:grano-1:issuer0001"),
+            },
+            relationship: Relationship::AssertionMethod,
+            validity: 1000,
+            expected_version: None,
+        };
+        let _res = execute(deps.as_mut(), mock_env(), controller_info, msg).unwrap();
+        assert_eq!(
+            verify(
+                deps.as_ref(),
+                delegate_key.sign(message),
+                Relationship::AssertionMethod
+            ),
+            Some(SignatureMethod::Delegate {
+                did: String::from("did:grano:grano-1:issuer0001")
+            })
+        );
 
+        // governance
+        let msg = SudoMsg::ForceChangeController {
+            identifier: Addr::unchecked("did:grano:grano-1:identifier0003"),
+            new_controller: Addr::unchecked("did:grano:grano-2:addr0002"),
+            proposal_id: 1,
+        };
+        let err = sudo(deps.as_mut(), mock_env(), msg).unwrap_err();
+        assert_eq!(
+            err,
+            ContractError::InvalidDid {
+                did: String::from("did:grano:grano-2:addr0002"),
+                reason: String::from("DID of network grano-2"),
+            }
+        );
+        let msg = SudoMsg::ForceChangeController {
+            identifier: Addr::unchecked("did:grano:grano-1:identifier0003"),
+            new_controller: Addr::unchecked("did:grano:grano-1:addr0002"),
+            proposal_id: 1,
+        };
+        let _res = sudo(deps.as_mut(), mock_env(), msg).unwrap();
         let res = query(
             deps.as_ref(),
             mock_env(),
             QueryMsg::Controller {
-                identifier: Addr::unchecked(&identifier1),
+                identifier: Addr::unchecked("identifier0003"),
             },
         )
         .unwrap();
         let value: ControllerResponse = from_binary(&res).unwrap();
-        assert_eq!(identifier1, value.controller);
+        assert_eq!(value.controller, Addr::unchecked("addr0002"));
 
-        // only the original identifier address can change the controller at the first time
-        let auth_info = mock_info("identifier0001", &coins(2, "token"));
+        let msg = SudoMsg::ForceRevokeAttribute {
+            identifier: Addr::unchecked("did:grano:grano-1:identifier0002"),
+            name: String::from("service"),
+            value: String::from("github"),
+            proposal_id: 2,
+        };
+        let _res = sudo(deps.as_mut(), mock_env(), msg).unwrap();
+        assert_eq!(
+            by_attribute(deps.as_ref(), None),
+            Ok(vec![
+                Addr::unchecked("identifier0001"),
+                Addr::unchecked("identifier0003")
+            ])
+        );
+    }
 
+    #[test]
+    fn metadata() {
+        let mut deps = mock_dependencies_with_balance(&coins(2, "token"));
+        let msg = InstantiateMsg {
+            owner: None,
+            fees: vec![],
+            deposit_per_byte: None,
+            network: None,
+        };
+        let info = mock_info("creator", &coins(2, "token"));
+        let _res = instantiate(deps.as_mut(), mock_env(), info, msg).unwrap();
+
+        let identifier1 = Addr::unchecked("identifier0001");
+        let auth_info = mock_info("identifier0001", &[]);
+        let metadata = |deps: Deps, env: Env| {
+            let res = query(
+                deps,
+                env,
+                QueryMsg::Metadata {
+                    identifier: Addr::unchecked("identifier0001"),
+                },
+            )
+            .unwrap();
+            let value: MetadataResponse = from_binary(&res).unwrap();
+            value.did_document_metadata
+        };
+
+        assert_eq!(
+            metadata(deps.as_ref(), mock_env()),
+            DidDocumentMetadata {
+                created: None,
+                created_height: None,
+                updated: None,
+                deactivated: false,
+                version_id: 0,
+                next_update: None,
+            }
+        );
+
+        let env1 = mock_env();
+        let msg = ExecuteMsg::SetAttribute {
+            identifier: identifier1.clone(),
+            name: String::from("service"),
+            value: String::from("github"),
+            validity: 1000,
+            expected_version: None,
+        };
+        let _res = execute(deps.as_mut(), env1.clone(), auth_info.clone(), msg).unwrap();
+
+        // the lease ends before the attribute value expires
+        let mut env2 = mock_env();
+        env2.block.height += 10;
+        env2.block.time = env2.block.time.plus_seconds(100);
         let msg = ExecuteMsg::ChangeController {
-            identifier: Addr::unchecked(&identifier1),
-            new_controller: Addr::unchecked(&controller1),
+            identifier: identifier1.clone(),
+            new_controller: Addr::unchecked("addr0001"),
+            next_hash: None,
+            lease: Some(Lease {
+                expires: env1.block.time.plus_seconds(500),
+                fallback: None,
+            }),
+            expected_version: None,
         };
+        let _res = execute(deps.as_mut(), env2.clone(), auth_info, msg).unwrap();
 
-        let _res = execute(deps.as_mut(), mock_env(), auth_info, msg).unwrap();
+        let expected = DidDocumentMetadata {
+            created: Some(env1.block.time),
+            created_height: Some(env1.block.height),
+            updated: Some(env2.block.time),
+            deactivated: false,
+            version_id: 2,
+            next_update: Some(env1.block.time.plus_seconds(500)),
+        };
+        assert_eq!(metadata(deps.as_ref(), env2.clone()), expected);
 
         let res = query(
             deps.as_ref(),
-            mock_env(),
-            QueryMsg::Controller {
-                identifier: Addr::unchecked(&identifier1),
+            env2.clone(),
+            QueryMsg::Resolve {
+                identifier: identifier1,
             },
         )
         .unwrap();
-        let value: ControllerResponse = from_binary(&res).unwrap();
-        assert_eq!(controller1, value.controller);
+        let value: ResolveResponse = from_binary(&res).unwrap();
+        assert_eq!(value.did_document_metadata, expected);
 
-        let auth_info = mock_info("attacker", &coins(2, "token"));
+        // once the lease ended, the attribute value expires next
+        let mut env3 = env2;
+        env3.block.time = env1.block.time.plus_seconds(600);
+        assert_eq!(
+            metadata(deps.as_ref(), env3).next_update,
+            Some(env1.block.time.plus_seconds(1000))
+        );
+    }
 
-        let msg = ExecuteMsg::ChangeController {
-            identifier: Addr::unchecked(&identifier1),
-            new_controller: Addr::unchecked(&identifier1),
+    /// canonicalizes the accounts with a secp256k1 key to their key hash like a chain does
+    struct AccountApi {
+        base: MockApi,
+        accounts: Vec<(Addr, Vec<u8>)>,
+    }
+
+    impl Api for AccountApi {
+        fn addr_validate(&self, human: &str) -> StdResult<Addr> {
+            self.base.addr_validate(human)
+        }
+
+        fn addr_canonicalize(&self, human: &str) -> StdResult<CanonicalAddr> {
+            use ripemd::Ripemd160;
+            use sha2::{Digest, Sha256};
+
+            match self
+                .accounts
+                .iter()
+                .find(|(address, _)| address.as_str() == human)
+            {
+                Some((_, key)) => Ok(CanonicalAddr::from(
+                    Ripemd160::digest(Sha256::digest(key)).as_slice(),
+                )),
+                None => self.base.addr_canonicalize(human),
+            }
+        }
+
+        fn addr_humanize(&self, canonical: &CanonicalAddr) -> StdResult<Addr> {
+            self.base.addr_humanize(canonical)
+        }
+
+        fn secp256k1_verify(
+            &self,
+            message_hash: &[u8],
+            signature: &[u8],
+            public_key: &[u8],
+        ) -> Result<bool, VerificationError> {
+            self.base
+                .secp256k1_verify(message_hash, signature, public_key)
+        }
+
+        fn secp256k1_recover_pubkey(
+            &self,
+            message_hash: &[u8],
+            signature: &[u8],
+            recovery_param: u8,
+        ) -> Result<Vec<u8>, RecoverPubkeyError> {
+            self.base
+                .secp256k1_recover_pubkey(message_hash, signature, recovery_param)
+        }
+
+        fn ed25519_verify(
+            &self,
+            message: &[u8],
+            signature: &[u8],
+            public_key: &[u8],
+        ) -> Result<bool, VerificationError> {
+            self.base.ed25519_verify(message, signature, public_key)
+        }
+
+        fn ed25519_batch_verify(
+            &self,
+            messages: &[&[u8]],
+            signatures: &[&[u8]],
+            public_keys: &[&[u8]],
+        ) -> Result<bool, VerificationError> {
+            self.base
+                .ed25519_batch_verify(messages, signatures, public_keys)
+        }
+
+        fn debug(&self, message: &str) {
+            self.base.debug(message)
+        }
+    }
+
+    #[test]
+    fn aliases() {
+        use k256::ecdsa::signature::Signer;
+
+        let account_key = k256::ecdsa::SigningKey::from_bytes(&[3u8; 32]).unwrap();
+        let mut deps = OwnedDeps {
+            storage: MockStorage::default(),
+            api: AccountApi {
+                base: MockApi::default(),
+                accounts: vec![(
+                    Addr::unchecked("identifier0003"),
+                    account_key.verifying_key().to_bytes().to_vec(),
+                )],
+            },
+            querier: MockQuerier::default(),
+            custom_query_type: PhantomData,
+        };
+        let msg = InstantiateMsg {
+            owner: None,
+            fees: vec![],
+            deposit_per_byte: None,
+            network: None,
         };
+        let info = mock_info("creator", &coins(2, "token"));
+        let _res = instantiate(deps.as_mut(), mock_env(), info, msg).unwrap();
 
-        let err = execute(deps.as_mut(), mock_env(), auth_info, msg).unwrap_err();
-        assert_eq!(err, ContractError::Unauthorized {});
+        let identifier1 = Addr::unchecked("identifier0001");
+        let identifier2 = Addr::unchecked("identifier0002");
+        let identifier3 = Addr::unchecked("identifier0003");
+        let auth_info1 = mock_info("identifier0001", &[]);
+        let auth_info2 = mock_info("identifier0002", &[]);
+        let aliases = |deps: Deps, identifier: &Addr| {
+            let res = query(
+                deps,
+                mock_env(),
+                QueryMsg::Aliases {
+                    identifier: identifier.clone(),
+                },
+            )
+            .unwrap();
+            from_binary::<AliasesResponse>(&res).unwrap()
+        };
+        let also_known_as = |deps: Deps, identifier: &Addr| {
+            let res = query(
+                deps,
+                mock_env(),
+                QueryMsg::Resolve {
+                    identifier: identifier.clone(),
+                },
+            )
+            .unwrap();
+            from_binary::<ResolveResponse>(&res)
+                .unwrap()
+                .document
+                .also_known_as
+        };
 
-        let res = query(
-            deps.as_ref(),
+        let msg = ExecuteMsg::LinkAlias {
+            identifier: identifier1.clone(),
+            other: identifier1.clone(),
+            signature: None,
+            expected_version: None,
+        };
+        let err = execute(deps.as_mut(), mock_env(), auth_info1.clone(), msg).unwrap_err();
+        assert_eq!(
+            err,
+            ContractError::InvalidAlias {
+                reason: String::from("an identifier cannot be its own alias")
+            }
+        );
+
+        // the link is asserted by one side only
+        let msg = ExecuteMsg::LinkAlias {
+            identifier: identifier1.clone(),
+            other: identifier2.clone(),
+            signature: None,
+            expected_version: None,
+        };
+        let res = execute(deps.as_mut(), mock_env(), auth_info1.clone(), msg).unwrap();
+        assert_eq!(get_attribute_value(res, "verified"), "false");
+        assert_eq!(
+            aliases(deps.as_ref(), &identifier1).aliases,
+            vec![AliasInfo {
+                alias: String::from("did:grano:cosmos-testnet-14002:identifier0002"),
+                verified: false,
+            }]
+        );
+        assert!(also_known_as(deps.as_ref(), &identifier1).is_empty());
+
+        let msg = ExecuteMsg::LinkAlias {
+            identifier: identifier2.clone(),
+            other: identifier1.clone(),
+            signature: None,
+            expected_version: None,
+        };
+        let res = execute(deps.as_mut(), mock_env(), auth_info2, msg).unwrap();
+        assert_eq!(get_attribute_value(res, "verified"), "true");
+        assert_eq!(
+            also_known_as(deps.as_ref(), &identifier1),
+            vec![String::from(
+                "did:grano:cosmos-testnet-14002:identifier0002"
+            )]
+        );
+        assert_eq!(
+            also_known_as(deps.as_ref(), &identifier2),
+            vec![String::from(
+                "did:grano:cosmos-testnet-14002:identifier0001"
+            )]
+        );
+
+        // unlinking one side unverifies the link
+        let msg = ExecuteMsg::UnlinkAlias {
+            identifier: identifier1.clone(),
+            other: identifier2.clone(),
+            expected_version: None,
+        };
+        let _res = execute(deps.as_mut(), mock_env(), auth_info1.clone(), msg.clone()).unwrap();
+        assert!(also_known_as(deps.as_ref(), &identifier2).is_empty());
+        assert_eq!(
+            aliases(deps.as_ref(), &identifier2).aliases,
+            vec![AliasInfo {
+                alias: String::from("did:grano:cosmos-testnet-14002:identifier0001"),
+                verified: false,
+            }]
+        );
+        let err = execute(deps.as_mut(), mock_env(), auth_info1.clone(), msg).unwrap_err();
+        assert_eq!(err, ContractError::AliasNotFound { other: identifier2 });
+
+        // keys of the other side cannot sign for its controller
+        let ed25519_key = ed25519_zebra::SigningKey::from([7u8; 32]);
+        let public_key = ed25519_zebra::VerificationKey::from(&ed25519_key);
+        let msg = ExecuteMsg::AddVerificationMethod {
+            identifier: identifier3.clone(),
+            id: String::from("key-1"),
+            key_type: KeyType::Ed25519,
+            controller: None,
+            public_key: PublicKey::Multibase(format!(
+                "m{}",
+                Binary::from(public_key.as_ref()).to_base64()
+            )),
+            relationships: vec![Relationship::Authentication],
+            validity: 1000,
+            expected_version: None,
+        };
+        let _res = execute(
+            deps.as_mut(),
             mock_env(),
-            QueryMsg::Controller {
-                identifier: Addr::unchecked(&identifier1),
-            },
+            mock_info("identifier0003", &[]),
+            msg,
         )
         .unwrap();
-        let value: ControllerResponse = from_binary(&res).unwrap();
-        assert_eq!(controller1, value.controller);
+
+        let msg = ExecuteMsg::AddHook {
+            identifier: identifier3.clone(),
+            hook: Addr::unchecked("hook0003"),
+            on_failure: HookFailure::Abort,
+            expected_version: None,
+        };
+        let _res = execute(
+            deps.as_mut(),
+            mock_env(),
+            mock_info("identifier0003", &[]),
+            msg,
+        )
+        .unwrap();
+
+        assert_eq!(aliases(deps.as_ref(), &identifier3).nonce, 0);
+        let message = alias_message(&mock_env().contract.address, &identifier3, &identifier1, 0);
+        let signature = <[u8; 64]>::from(ed25519_key.sign(&message)).to_vec();
+        let msg = ExecuteMsg::LinkAlias {
+            identifier: identifier1.clone(),
+            other: identifier3.clone(),
+            signature: Some(Binary::from(signature)),
+            expected_version: None,
+        };
+        let err = execute(deps.as_mut(), mock_env(), auth_info1.clone(), msg).unwrap_err();
+        assert_eq!(err, ContractError::InvalidAliasSignature {});
+
+        // one transaction with the signature of the controller account of the other side
+        let signature: k256::ecdsa::Signature = account_key.sign(&message);
+        let msg = ExecuteMsg::LinkAlias {
+            identifier: identifier1.clone(),
+            other: identifier3.clone(),
+            signature: Some(Binary::from(signature.as_ref().to_vec())),
+            expected_version: None,
+        };
+        let res = execute(deps.as_mut(), mock_env(), auth_info1.clone(), msg.clone()).unwrap();
+        assert_eq!(get_attribute_value(res.clone(), "verified"), "true");
+        // the other side is bumped and notified as well
+        assert_eq!(get_attribute_value(res.clone(), "otherVersion"), "3");
+        assert_eq!(res.messages.len(), 1);
+        assert_eq!(
+            also_known_as(deps.as_ref(), &identifier3),
+            vec![String::from(
+                "did:grano:cosmos-testnet-14002:identifier0001"
+            )]
+        );
+        assert_eq!(aliases(deps.as_ref(), &identifier3).nonce, 1);
+
+        // the nonce keeps the signature from being replayed
+        let err = execute(deps.as_mut(), mock_env(), auth_info1.clone(), msg).unwrap_err();
+        assert_eq!(err, ContractError::InvalidAliasSignature {});
+
+        let msg = ExecuteMsg::UnlinkAlias {
+            identifier: identifier1.clone(),
+            other: identifier3.clone(),
+            expected_version: None,
+        };
+        let res = execute(deps.as_mut(), mock_env(), auth_info1, msg).unwrap();
+        assert_eq!(get_attribute_value(res.clone(), "otherVersion"), "4");
+        assert_eq!(res.messages.len(), 1);
+        assert!(also_known_as(deps.as_ref(), &identifier3).is_empty());
     }
 
     #[test]
-    fn set_attribute() {
+    fn recovery_waits_for_policy() {
         let mut deps = mock_dependencies_with_balance(&coins(2, "token"));
-        let msg = InstantiateMsg {};
+        let msg = InstantiateMsg {
+            owner: None,
+            fees: vec![],
+            deposit_per_byte: None,
+            network: None,
+        };
         let info = mock_info("creator", &coins(2, "token"));
         let _res = instantiate(deps.as_mut(), mock_env(), info, msg).unwrap();
 
-        let identifier1 = String::from("identifier0001");
+        let identifier1 = Addr::unchecked("identifier0001");
+        let auth_info = mock_info("identifier0001", &[]);
+        let controller = |deps: Deps, env: Env| {
+            let res = query(
+                deps,
+                env,
+                QueryMsg::Controller {
+                    identifier: Addr::unchecked("identifier0001"),
+                },
+            )
+            .unwrap();
+            from_binary::<ControllerResponse>(&res).unwrap().controller
+        };
 
-        // only the original identifier address can change the controller at the first time
-        let auth_info = mock_info("identifier0001", &coins(2, "token"));
+        let msg = ExecuteMsg::SetControllerPolicy {
+            identifier: identifier1.clone(),
+            policy: Some(ControllerPolicy {
+                delay: Delay::Time(86400),
+                veto: Some(Addr::unchecked("veto0001")),
+            }),
+            expected_version: None,
+        };
+        let _res = execute(deps.as_mut(), mock_env(), auth_info.clone(), msg).unwrap();
 
-        let msg = ExecuteMsg::SetAttribute {
-            identifier: Addr::unchecked(&identifier1),
-            name: String::from("identifier_name"),
-            value: String::from("abc"),
-            validity: 0,
+        // a stolen controller key cannot install a guardian that recovers right away
+        let msg = ExecuteMsg::SetGuardians {
+            identifier: identifier1.clone(),
+            guardians: vec![Addr::unchecked("thief")],
+            threshold: 1,
+            delay: 0,
+            expected_version: None,
         };
+        let err = execute(deps.as_mut(), mock_env(), auth_info.clone(), msg).unwrap_err();
+        assert_eq!(err, ContractError::RecoveryDelayTooShort { min: 86400 });
 
+        // the guardian set waits for the timelock
+        let msg = ExecuteMsg::SetGuardians {
+            identifier: identifier1.clone(),
+            guardians: vec![Addr::unchecked("guardian0001")],
+            threshold: 1,
+            delay: 86400,
+            expected_version: None,
+        };
         let res = execute(deps.as_mut(), mock_env(), auth_info.clone(), msg).unwrap();
+        assert_eq!(get_attribute_value(res, "change"), "setGuardians");
+        let msg = ExecuteMsg::StartRecovery {
+            identifier: identifier1.clone(),
+            new_controller: Addr::unchecked("addr0001"),
+            expected_version: None,
+        };
+        let err = execute(
+            deps.as_mut(),
+            mock_env(),
+            mock_info("guardian0001", &[]),
+            msg.clone(),
+        )
+        .unwrap_err();
+        assert_eq!(
+            err,
+            ContractError::NoGuardians {
+                identifier: identifier1.clone()
+            }
+        );
 
-        // check name attribute
-        let name = get_attribute_value(res.clone(), "name");
+        let mut env = mock_env();
+        env.block.time = env.block.time.plus_seconds(86400);
+        let execute_pending = ExecuteMsg::ExecutePendingChange {
+            identifier: identifier1.clone(),
+            expected_version: None,
+        };
+        let _res = execute(
+            deps.as_mut(),
+            env.clone(),
+            auth_info.clone(),
+            execute_pending.clone(),
+        )
+        .unwrap();
+        let _res = execute(
+            deps.as_mut(),
+            env.clone(),
+            mock_info("guardian0001", &[]),
+            msg,
+        )
+        .unwrap();
 
-        assert_eq!(name, "identifier_name");
+        // the executed recovery is scheduled and can be vetoed
+        env.block.time = env.block.time.plus_seconds(86400);
+        let msg = ExecuteMsg::ExecuteRecovery {
+            identifier: identifier1.clone(),
+            expected_version: None,
+        };
+        let res = execute(
+            deps.as_mut(),
+            env.clone(),
+            mock_info("guardian0001", &[]),
+            msg,
+        )
+        .unwrap();
+        assert_eq!(get_attribute_value(res, "change"), "recovery");
+        assert_eq!(controller(deps.as_ref(), env.clone()), identifier1);
 
-        // check value attribute
-        let value = get_attribute_value(res.clone(), "value");
+        let msg = ExecuteMsg::CancelPendingChange {
+            identifier: identifier1.clone(),
+            expected_version: None,
+        };
+        let _res = execute(deps.as_mut(), env.clone(), mock_info("veto0001", &[]), msg).unwrap();
+        env.block.time = env.block.time.plus_seconds(86400);
+        let err = execute(deps.as_mut(), env.clone(), auth_info, execute_pending).unwrap_err();
+        assert_eq!(
+            err,
+            ContractError::NoPendingChange {
+                identifier: identifier1.clone()
+            }
+        );
+        assert_eq!(controller(deps.as_ref(), env), identifier1);
+    }
 
-        assert_eq!(value, "abc");
+    #[test]
+    fn commitment_binds_recovery_and_pending_changes() {
+        let mut deps = mock_dependencies_with_balance(&coins(2, "token"));
+        let msg = InstantiateMsg {
+            owner: None,
+            fees: vec![],
+            deposit_per_byte: None,
+            network: None,
+        };
+        let info = mock_info("creator", &coins(2, "token"));
+        let _res = instantiate(deps.as_mut(), mock_env(), info, msg).unwrap();
 
-        // set attribute again
-        let msg = ExecuteMsg::SetAttribute {
-            identifier: Addr::unchecked(&identifier1),
-            name: String::from("identifier_name"),
-            value: String::from("def"),
-            validity: 0,
+        let identifier1 = Addr::unchecked("identifier0001");
+        let auth_info = mock_info("identifier0001", &[]);
+
+        // a change scheduled before the commitment has to match it when executed
+        let msg = ExecuteMsg::SetControllerPolicy {
+            identifier: identifier1.clone(),
+            policy: Some(ControllerPolicy {
+                delay: Delay::Height(10),
+                veto: None,
+            }),
+            expected_version: None,
+        };
+        let _res = execute(deps.as_mut(), mock_env(), auth_info.clone(), msg).unwrap();
+        let msg = ExecuteMsg::ChangeController {
+            identifier: identifier1.clone(),
+            new_controller: Addr::unchecked("thief"),
+            next_hash: None,
+            lease: None,
+            expected_version: None,
+        };
+        let _res = execute(deps.as_mut(), mock_env(), auth_info.clone(), msg).unwrap();
+        let msg = ExecuteMsg::CommitNextController {
+            identifier: identifier1.clone(),
+            next_hash: controller_hash(&Addr::unchecked("addr0001")),
+            expected_version: None,
+        };
+        let _res = execute(deps.as_mut(), mock_env(), auth_info.clone(), msg).unwrap();
+
+        let mut env = mock_env();
+        env.block.height += 10;
+        let msg = ExecuteMsg::ExecutePendingChange {
+            identifier: identifier1.clone(),
+            expected_version: None,
         };
+        let err = execute(deps.as_mut(), env.clone(), auth_info.clone(), msg).unwrap_err();
+        assert_eq!(err, ContractError::CommitmentMismatch {});
 
-        let res = execute(deps.as_mut(), mock_env(), auth_info.clone(), msg).unwrap();
+        // guardians can only recover to the committed controller
+        let msg = ExecuteMsg::CancelPendingChange {
+            identifier: identifier1.clone(),
+            expected_version: None,
+        };
+        let _res = execute(deps.as_mut(), env.clone(), auth_info.clone(), msg).unwrap();
+        let msg = ExecuteMsg::SetControllerPolicy {
+            identifier: identifier1.clone(),
+            policy: None,
+            expected_version: None,
+        };
+        let _res = execute(deps.as_mut(), env.clone(), auth_info.clone(), msg).unwrap();
+        env.block.height += 10;
+        let msg = ExecuteMsg::ExecutePendingChange {
+            identifier: identifier1.clone(),
+            expected_version: None,
+        };
+        let _res = execute(deps.as_mut(), env.clone(), auth_info.clone(), msg).unwrap();
 
-        // check name attribute
-        let name = get_attribute_value(res.clone(), "name");
-        assert_eq!(name, "identifier_name");
+        let msg = ExecuteMsg::SetGuardians {
+            identifier: identifier1.clone(),
+            guardians: vec![Addr::unchecked("guardian0001")],
+            threshold: 1,
+            delay: 0,
+            expected_version: None,
+        };
+        let _res = execute(deps.as_mut(), env.clone(), auth_info, msg).unwrap();
+        let guardian_info = mock_info("guardian0001", &[]);
+        let msg = ExecuteMsg::StartRecovery {
+            identifier: identifier1.clone(),
+            new_controller: Addr::unchecked("thief"),
+            expected_version: None,
+        };
+        let err = execute(deps.as_mut(), env.clone(), guardian_info.clone(), msg).unwrap_err();
+        assert_eq!(err, ContractError::CommitmentMismatch {});
 
-        // check value attribute
-        let value = get_attribute_value(res.clone(), "value");
-        assert_eq!(value, "def");
+        let msg = ExecuteMsg::StartRecovery {
+            identifier: identifier1.clone(),
+            new_controller: Addr::unchecked("addr0001"),
+            expected_version: None,
+        };
+        let _res = execute(deps.as_mut(), env.clone(), guardian_info.clone(), msg).unwrap();
+        let msg = ExecuteMsg::ExecuteRecovery {
+            identifier: identifier1.clone(),
+            expected_version: None,
+        };
+        let _res = execute(deps.as_mut(), env.clone(), guardian_info, msg).unwrap();
 
         let res = query(
             deps.as_ref(),
-            mock_env(),
-            QueryMsg::Attribute {
-                identifier: Addr::unchecked(&identifier1),
-                name: String::from("identifier_name"),
+            env,
+            QueryMsg::Controller {
+                identifier: identifier1,
             },
         )
         .unwrap();
+        let value: ControllerResponse = from_binary(&res).unwrap();
+        assert_eq!(value.controller, Addr::unchecked("addr0001"));
+    }
 
-        let value: AttributeResponse = from_binary(&res).unwrap();
-        assert_eq!(value.values, ["abc", "def"]);
+    #[test]
+    fn lease_survives_lessee_changes() {
+        let mut deps = mock_dependencies_with_balance(&coins(2, "token"));
+        let msg = InstantiateMsg {
+            owner: None,
+            fees: vec![],
+            deposit_per_byte: None,
+            network: None,
+        };
+        let info = mock_info("creator", &coins(2, "token"));
+        let _res = instantiate(deps.as_mut(), mock_env(), info, msg).unwrap();
 
-        // check validTo
-        let res = query(
-            deps.as_ref(),
+        let identifier1 = Addr::unchecked("identifier0001");
+        let custodian = mock_info("custodian", &[]);
+        let controller = |deps: Deps, env: Env| {
+            let res = query(
+                deps,
+                env,
+                QueryMsg::Controller {
+                    identifier: Addr::unchecked("identifier0001"),
+                },
+            )
+            .unwrap();
+            from_binary::<ControllerResponse>(&res).unwrap().controller
+        };
+
+        let msg = ExecuteMsg::SetGuardians {
+            identifier: identifier1.clone(),
+            guardians: vec![Addr::unchecked("guardian0001")],
+            threshold: 1,
+            delay: 0,
+            expected_version: None,
+        };
+        let _res = execute(
+            deps.as_mut(),
             mock_env(),
-            QueryMsg::ValidTo {
-                identifier: Addr::unchecked(&identifier1),
-                name: String::from("identifier_name"),
-                value: String::from("abc"),
-            },
+            mock_info("identifier0001", &[]),
+            msg,
         )
         .unwrap();
 
-        let value: ValidToResponse = from_binary(&res).unwrap();
-        assert_ne!(value.valid_to.seconds(), 0);
-
-        // set attribute again
-        let msg = ExecuteMsg::SetAttribute {
-            identifier: Addr::unchecked(&identifier1),
-            name: String::from("identifier_name"),
-            value: String::from("abc"),
-            validity: 0,
+        let expires = mock_env().block.time.plus_seconds(100);
+        let msg = ExecuteMsg::ChangeController {
+            identifier: identifier1.clone(),
+            new_controller: Addr::unchecked("custodian"),
+            next_hash: None,
+            lease: Some(Lease {
+                expires,
+                fallback: None,
+            }),
+            expected_version: None,
         };
-
-        let res = execute(deps.as_mut(), mock_env(), auth_info.clone(), msg).unwrap();
-
-        // check name attribute
-        let name = get_attribute_value(res.clone(), "name");
-        assert_eq!(name, "identifier_name");
-
-        // check value attribute
-        let value = get_attribute_value(res.clone(), "value");
-        assert_eq!(value, "abc");
-
-        let res = query(
-            deps.as_ref(),
+        let _res = execute(
+            deps.as_mut(),
             mock_env(),
-            QueryMsg::Attribute {
-                identifier: Addr::unchecked(&identifier1),
-                name: String::from("identifier_name"),
-            },
+            mock_info("identifier0001", &[]),
+            msg,
         )
         .unwrap();
 
-        let value: AttributeResponse = from_binary(&res).unwrap();
-        assert_eq!(value.values, ["abc", "def"]);
+        // the lessee cannot drop or extend the lease
+        let msg = ExecuteMsg::ChangeController {
+            identifier: identifier1.clone(),
+            new_controller: Addr::unchecked("custodian"),
+            next_hash: None,
+            lease: Some(Lease {
+                expires: expires.plus_seconds(1000),
+                fallback: None,
+            }),
+            expected_version: None,
+        };
+        let err = execute(deps.as_mut(), mock_env(), custodian.clone(), msg).unwrap_err();
+        assert_eq!(err, ContractError::LeaseActive { expires });
 
-        // check validTo
-        let res = query(
-            deps.as_ref(),
-            mock_env(),
-            QueryMsg::ValidTo {
-                identifier: Addr::unchecked(&identifier1),
-                name: String::from("identifier_name"),
-                value: String::from("def"),
-            },
+        let msg = ExecuteMsg::ChangeController {
+            identifier: identifier1.clone(),
+            new_controller: Addr::unchecked("custodian"),
+            next_hash: None,
+            lease: None,
+            expected_version: None,
+        };
+        let _res = execute(deps.as_mut(), mock_env(), custodian.clone(), msg).unwrap();
+
+        // neither can a recovery by the guardians
+        let guardian = mock_info("guardian0001", &[]);
+        let msg = ExecuteMsg::StartRecovery {
+            identifier: identifier1.clone(),
+            new_controller: Addr::unchecked("custodian"),
+            expected_version: None,
+        };
+        let _res = execute(deps.as_mut(), mock_env(), guardian.clone(), msg).unwrap();
+        let msg = ExecuteMsg::ExecuteRecovery {
+            identifier: identifier1.clone(),
+            expected_version: None,
+        };
+        let _res = execute(deps.as_mut(), mock_env(), guardian, msg).unwrap();
+        assert_eq!(
+            controller(deps.as_ref(), mock_env()),
+            Addr::unchecked("custodian")
+        );
+
+        let mut env = mock_env();
+        env.block.time = expires;
+        assert_eq!(controller(deps.as_ref(), env.clone()), identifier1);
+
+        // after the lease the fallback can change the controller without it
+        let msg = ExecuteMsg::ChangeController {
+            identifier: identifier1.clone(),
+            new_controller: Addr::unchecked("addr0001"),
+            next_hash: None,
+            lease: None,
+            expected_version: None,
+        };
+        let _res = execute(
+            deps.as_mut(),
+            env.clone(),
+            mock_info("identifier0001", &[]),
+            msg,
         )
         .unwrap();
-
-        let value: ValidToResponse = from_binary(&res).unwrap();
-        assert_ne!(value.valid_to.seconds(), 0);
+        env.block.time = env.block.time.plus_seconds(1000);
+        assert_eq!(controller(deps.as_ref(), env), Addr::unchecked("addr0001"));
     }
 
     #[test]
-    fn set_attribute_by_attacker() {
+    fn hooks_cannot_block_governance() {
         let mut deps = mock_dependencies_with_balance(&coins(2, "token"));
-        let msg = InstantiateMsg {};
+        let msg = InstantiateMsg {
+            owner: None,
+            fees: vec![],
+            deposit_per_byte: None,
+            network: None,
+        };
         let info = mock_info("creator", &coins(2, "token"));
         let _res = instantiate(deps.as_mut(), mock_env(), info, msg).unwrap();
 
-        let identifier1 = String::from("identifier0001");
+        let identifier1 = Addr::unchecked("identifier0001");
+        let auth_info = mock_info("identifier0001", &[]);
 
-        // only the original identifier address can change the controller at the first time
-        let auth_info = mock_info("attacker", &coins(2, "token"));
+        for i in 0..MAX_HOOKS {
+            let msg = ExecuteMsg::AddHook {
+                identifier: identifier1.clone(),
+                hook: Addr::unchecked(format!("hook{:04}", i)),
+                on_failure: HookFailure::Abort,
+                expected_version: None,
+            };
+            let _res = execute(deps.as_mut(), mock_env(), auth_info.clone(), msg).unwrap();
+        }
+        let msg = ExecuteMsg::AddHook {
+            identifier: identifier1.clone(),
+            hook: Addr::unchecked("hook9999"),
+            on_failure: HookFailure::Abort,
+            expected_version: None,
+        };
+        let err = execute(deps.as_mut(), mock_env(), auth_info, msg).unwrap_err();
+        assert_eq!(err, ContractError::TooManyHooks { max: MAX_HOOKS });
 
-        let msg = ExecuteMsg::SetAttribute {
-            identifier: Addr::unchecked(&identifier1),
-            name: String::from("identifier_name"),
-            value: String::from("abc"),
-            validity: 0,
+        // hooks that abort are notified as ignored in governance changes
+        let msg = SudoMsg::ForceChangeController {
+            identifier: identifier1.clone(),
+            new_controller: Addr::unchecked("addr0001"),
+            proposal_id: 7,
         };
+        let res = sudo(deps.as_mut(), mock_env(), msg).unwrap();
+        assert_eq!(res.messages.len(), MAX_HOOKS);
+        assert!(res.messages.iter().all(|m| m.reply_on == ReplyOn::Error));
 
-        let err = execute(deps.as_mut(), mock_env(), auth_info, msg).unwrap_err();
-        assert_eq!(err, ContractError::Unauthorized {});
+        let msg = SudoMsg::ForceDeactivate {
+            identifier: identifier1,
+            proposal_id: 8,
+        };
+        let res = sudo(deps.as_mut(), mock_env(), msg).unwrap();
+        assert_eq!(res.messages.len(), MAX_HOOKS);
+        assert!(res.messages.iter().all(|m| m.reply_on == ReplyOn::Error));
     }
 
     #[test]
-    fn revoke_attribute() {
+    fn controller_policy_delay_is_bounded() {
         let mut deps = mock_dependencies_with_balance(&coins(2, "token"));
-        let msg = InstantiateMsg {};
+        let msg = InstantiateMsg {
+            owner: None,
+            fees: vec![],
+            deposit_per_byte: None,
+            network: None,
+        };
         let info = mock_info("creator", &coins(2, "token"));
         let _res = instantiate(deps.as_mut(), mock_env(), info, msg).unwrap();
 
-        let identifier1 = String::from("identifier0001");
-
-        // only the original identifier address can change the controller at the first time
-        let auth_info = mock_info("identifier0001", &coins(2, "token"));
-
-        let msg = ExecuteMsg::SetAttribute {
-            identifier: Addr::unchecked(&identifier1),
-            name: String::from("identifier_name"),
-            value: String::from("abc"),
-            validity: 0,
-        };
-
-        let res = execute(deps.as_mut(), mock_env(), auth_info, msg).unwrap();
-
-        // check name attribute
-        let name = get_attribute_value(res.clone(), "name");
-
-        assert_eq!(name, "identifier_name");
+        let identifier1 = Addr::unchecked("identifier0001");
+        let auth_info = mock_info("identifier0001", &[]);
 
-        // check value attribute
-        let value = get_attribute_value(res.clone(), "value");
+        for (delay, max) in [
+            (Delay::Height(u64::MAX), Delay::MAX_HEIGHT),
+            (Delay::Time(Delay::MAX_SECONDS + 1), Delay::MAX_SECONDS),
+        ] {
+            let msg = ExecuteMsg::SetControllerPolicy {
+                identifier: identifier1.clone(),
+                policy: Some(ControllerPolicy { delay, veto: None }),
+                expected_version: None,
+            };
+            let err = execute(deps.as_mut(), mock_env(), auth_info.clone(), msg).unwrap_err();
+            assert_eq!(err, ContractError::DelayTooLong { max });
+        }
 
-        assert_eq!(value, "abc");
+        // deadlines never overflow
+        let block = mock_env().block;
+        assert_eq!(
+            Delay::Height(u64::MAX).after(&block),
+            Err(ContractError::DelayTooLong {
+                max: Delay::MAX_HEIGHT
+            })
+        );
+        assert_eq!(
+            Delay::Time(u64::MAX).after(&block),
+            Err(ContractError::DelayTooLong {
+                max: Delay::MAX_SECONDS
+            })
+        );
 
-        //revoke_attribute test
-        let msg = ExecuteMsg::RevokeAttribute {
-            identifier: Addr::unchecked(&identifier1),
-            name: String::from("identifier_name"),
-            value: String::from("abc"),
+        // the longest delay still lets the policy be removed
+        let msg = ExecuteMsg::SetControllerPolicy {
+            identifier: identifier1.clone(),
+            policy: Some(ControllerPolicy {
+                delay: Delay::Time(Delay::MAX_SECONDS),
+                veto: None,
+            }),
+            expected_version: None,
+        };
+        let _res = execute(deps.as_mut(), mock_env(), auth_info.clone(), msg).unwrap();
+        let msg = ExecuteMsg::SetControllerPolicy {
+            identifier: identifier1.clone(),
+            policy: None,
+            expected_version: None,
         };
-
-        // only the original identifier address can change the controller at the first time
-        let auth_info = mock_info("identifier0001", &coins(2, "token"));
         let res = execute(deps.as_mut(), mock_env(), auth_info, msg).unwrap();
+        assert_eq!(
+            get_attribute_value(res, "executableAt"),
+            Deadline::AtTime(block.time.plus_seconds(Delay::MAX_SECONDS)).to_string()
+        );
+    }
 
-        // check name attribute
-        let name = get_attribute_value(res.clone(), "name");
-
-        assert_eq!(name, "identifier_name");
+    #[test]
+    fn lessee_cannot_bind_the_fallback() {
+        let mut deps = mock_dependencies_with_balance(&coins(2, "token"));
+        let msg = InstantiateMsg {
+            owner: None,
+            fees: vec![],
+            deposit_per_byte: None,
+            network: None,
+        };
+        let info = mock_info("creator", &coins(2, "token"));
+        let _res = instantiate(deps.as_mut(), mock_env(), info, msg).unwrap();
 
-        // check value attribute
-        let value = get_attribute_value(res.clone(), "value");
+        let identifier1 = Addr::unchecked("identifier0001");
+        let auth_info = mock_info("identifier0001", &[]);
+        let lessee_info = mock_info("addr0001", &[]);
+        let expires = mock_env().block.time.plus_seconds(100);
 
-        assert_eq!(value, "abc");
+        let msg = ExecuteMsg::ChangeController {
+            identifier: identifier1.clone(),
+            new_controller: Addr::unchecked("addr0001"),
+            next_hash: None,
+            lease: Some(Lease {
+                expires,
+                fallback: None,
+            }),
+            expected_version: None,
+        };
+        let _res = execute(deps.as_mut(), mock_env(), auth_info.clone(), msg).unwrap();
 
-        // check validity attribute
-        let validity = get_attribute_value(res.clone(), "validTo");
+        // nothing the lessee sets may outlive the lease
+        let msgs = vec![
+            ExecuteMsg::CommitNextController {
+                identifier: identifier1.clone(),
+                next_hash: controller_hash(&Addr::unchecked("addr0002")),
+                expected_version: None,
+            },
+            ExecuteMsg::ChangeController {
+                identifier: identifier1.clone(),
+                new_controller: Addr::unchecked("addr0002"),
+                next_hash: Some(controller_hash(&Addr::unchecked("addr0003"))),
+                lease: None,
+                expected_version: None,
+            },
+            ExecuteMsg::SetControllerPolicy {
+                identifier: identifier1.clone(),
+                policy: Some(ControllerPolicy {
+                    delay: Delay::Height(10),
+                    veto: None,
+                }),
+                expected_version: None,
+            },
+            ExecuteMsg::SetGuardians {
+                identifier: identifier1.clone(),
+                guardians: vec![Addr::unchecked("addr0002")],
+                threshold: 1,
+                delay: 0,
+                expected_version: None,
+            },
+        ];
+        for msg in msgs {
+            let err = execute(deps.as_mut(), mock_env(), lessee_info.clone(), msg).unwrap_err();
+            assert_eq!(err, ContractError::LeaseActive { expires });
+        }
 
-        assert_eq!(validity, "0");
+        // the fallback changes the controller once the lease ended
+        let mut env = mock_env();
+        env.block.time = expires;
+        let msg = ExecuteMsg::ChangeController {
+            identifier: identifier1.clone(),
+            new_controller: Addr::unchecked("addr0004"),
+            next_hash: None,
+            lease: None,
+            expected_version: None,
+        };
+        let _res = execute(deps.as_mut(), env.clone(), auth_info, msg).unwrap();
 
-        // check validTo
         let res = query(
             deps.as_ref(),
-            mock_env(),
-            QueryMsg::ValidTo {
-                identifier: Addr::unchecked(&identifier1),
-                name: String::from("identifier_name"),
-                value: String::from("abc"),
+            env,
+            QueryMsg::Controller {
+                identifier: identifier1,
             },
         )
         .unwrap();
-
-        let value: ValidToResponse = from_binary(&res).unwrap();
-        assert_eq!(value.valid_to.seconds(), 0);
-
-        //revoke_attribute test again with wrong value
-        let msg = ExecuteMsg::RevokeAttribute {
-            identifier: Addr::unchecked(&identifier1),
-            name: String::from("identifier_name"),
-            value: String::from("xyz"),
-        };
-
-        let auth_info = mock_info("identifier0001", &coins(2, "token"));
-        let err = execute(deps.as_mut(), mock_env(), auth_info, msg).unwrap_err();
-        assert_eq!(
-            err,
-            ContractError::InvalidKeyPair {
-                identifier: Addr::unchecked(&identifier1),
-                name: String::from("identifier_name"),
-                value: String::from("xyz"),
-            }
-        );
+        let value: ControllerResponse = from_binary(&res).unwrap();
+        assert_eq!(value.controller, Addr::unchecked("addr0004"));
     }
 
     #[test]
-    fn revoke_attribute_by_attacker() {
+    fn forced_change_clears_recovery() {
         let mut deps = mock_dependencies_with_balance(&coins(2, "token"));
-        let msg = InstantiateMsg {};
+        let msg = InstantiateMsg {
+            owner: None,
+            fees: vec![],
+            deposit_per_byte: None,
+            network: None,
+        };
         let info = mock_info("creator", &coins(2, "token"));
         let _res = instantiate(deps.as_mut(), mock_env(), info, msg).unwrap();
 
-        let identifier1 = String::from("identifier0001");
-
-        // only the original identifier address can change the controller at the first time
-        let auth_info = mock_info("identifier0001", &coins(2, "token"));
+        let identifier1 = Addr::unchecked("identifier0001");
+        let auth_info = mock_info("identifier0001", &[]);
+        let guardian = mock_info("guardian0001", &[]);
 
-        let msg = ExecuteMsg::SetAttribute {
-            identifier: Addr::unchecked(&identifier1),
-            name: String::from("identifier_name"),
-            value: String::from("abc"),
-            validity: 0,
+        // the compromised key sets up a recovery to the attacker
+        let msgs = vec![
+            ExecuteMsg::SetGuardians {
+                identifier: identifier1.clone(),
+                guardians: vec![Addr::unchecked("guardian0001")],
+                threshold: 1,
+                delay: 100,
+                expected_version: None,
+            },
+            ExecuteMsg::CommitNextController {
+                identifier: identifier1.clone(),
+                next_hash: controller_hash(&Addr::unchecked("attacker")),
+                expected_version: None,
+            },
+            ExecuteMsg::SetControllerPolicy {
+                identifier: identifier1.clone(),
+                policy: Some(ControllerPolicy {
+                    delay: Delay::Height(10),
+                    veto: Some(Addr::unchecked("attacker")),
+                }),
+                expected_version: None,
+            },
+        ];
+        for msg in msgs {
+            let _res = execute(deps.as_mut(), mock_env(), auth_info.clone(), msg).unwrap();
+        }
+        let msg = ExecuteMsg::StartRecovery {
+            identifier: identifier1.clone(),
+            new_controller: Addr::unchecked("attacker"),
+            expected_version: None,
         };
+        let _res = execute(deps.as_mut(), mock_env(), guardian.clone(), msg).unwrap();
 
-        let res = execute(deps.as_mut(), mock_env(), auth_info, msg).unwrap();
-
-        // check name attribute
-        let name = get_attribute_value(res.clone(), "name");
-
-        assert_eq!(name, "identifier_name");
+        let msg = SudoMsg::ForceChangeController {
+            identifier: identifier1.clone(),
+            new_controller: Addr::unchecked("addr0001"),
+            proposal_id: 1,
+        };
+        let _res = sudo(deps.as_mut(), mock_env(), msg).unwrap();
 
-        // check value attribute
-        let value = get_attribute_value(res.clone(), "value");
+        // the pending recovery cannot run anymore, and no new one can start
+        let mut env = mock_env();
+        env.block.time = env.block.time.plus_seconds(100);
+        let msg = ExecuteMsg::ExecuteRecovery {
+            identifier: identifier1.clone(),
+            expected_version: None,
+        };
+        let err = execute(deps.as_mut(), env.clone(), guardian.clone(), msg).unwrap_err();
+        assert_eq!(
+            err,
+            ContractError::NoRecovery {
+                identifier: identifier1.clone()
+            }
+        );
+        let msg = ExecuteMsg::StartRecovery {
+            identifier: identifier1.clone(),
+            new_controller: Addr::unchecked("attacker"),
+            expected_version: None,
+        };
+        let err = execute(deps.as_mut(), env.clone(), guardian, msg).unwrap_err();
+        assert_eq!(
+            err,
+            ContractError::NoGuardians {
+                identifier: identifier1.clone()
+            }
+        );
 
-        assert_eq!(value, "abc");
+        let res = query(
+            deps.as_ref(),
+            env.clone(),
+            QueryMsg::Commitment {
+                identifier: identifier1.clone(),
+            },
+        )
+        .unwrap();
+        let value: CommitmentResponse = from_binary(&res).unwrap();
+        assert_eq!(value.next_hash, None);
 
-        //revoke_attribute test
-        let msg = ExecuteMsg::RevokeAttribute {
-            identifier: Addr::unchecked(&identifier1),
-            name: String::from("identifier_name"),
-            value: String::from("xyz"),
+        // the new controller is bound by neither the commitment nor the policy
+        let msg = ExecuteMsg::ChangeController {
+            identifier: identifier1,
+            new_controller: Addr::unchecked("addr0002"),
+            next_hash: None,
+            lease: None,
+            expected_version: None,
         };
-
-        // only the original identifier address can change the controller at the first time
-        let auth_info = mock_info("attacker", &coins(2, "token"));
-        let err = execute(deps.as_mut(), mock_env(), auth_info, msg).unwrap_err();
-        assert_eq!(err, ContractError::Unauthorized {});
+        let res = execute(deps.as_mut(), env, mock_info("addr0001", &[]), msg).unwrap();
+        assert_eq!(get_attribute_value(res, "executeMsg"), "changeController");
     }
 }
@@ -0,0 +1,117 @@
+use cosmwasm_std::Addr;
+
+pub const DID_METHOD: &str = "grano";
+
+/// Parts of a `did:grano:[<network>:]<address>[/path][?query][#fragment]` DID URL
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct DidUrl {
+    pub identifier: Addr,
+    /// empty or starting with `/`
+    pub path: String,
+    /// percent decoded parameters in order
+    pub query: Vec<(String, String)>,
+    pub fragment: Option<String>,
+}
+
+impl DidUrl {
+    pub fn param(&self, name: &str) -> Option<&str> {
+        self.query
+            .iter()
+            .find(|(param, _)| param == name)
+            .map(|(_, value)| value.as_str())
+    }
+}
+
+/// `did:grano:<network>:<address>`, deployments without a network leave it out
+pub fn format_did(network: Option<&str>, address: &Addr) -> String {
+    match network {
+        Some(network) => format!("did:{}:{}:{}", DID_METHOD, network, address),
+        None => format!("did:{}:{}", DID_METHOD, address),
+    }
+}
+
+/// address of a DID or of a bare address, DIDs of another network are rejected.
+/// the error is the reason.
+pub fn parse_did(network: Option<&str>, did: &str) -> Result<Addr, String> {
+    if !did.starts_with("did:") {
+        return Ok(Addr::unchecked(did));
+    }
+    let url = parse_did_url(network, did)?;
+    if !url.path.is_empty() || !url.query.is_empty() || url.fragment.is_some() {
+        return Err(String::from("DID URL instead of a DID"));
+    }
+    Ok(url.identifier)
+}
+
+/// parses a DID URL following the DID Core grammar, the network can be left out.
+/// the error is the reason.
+pub fn parse_did_url(network: Option<&str>, did_url: &str) -> Result<DidUrl, String> {
+    let (rest, fragment) = match did_url.split_once('#') {
+        Some((rest, fragment)) => (rest, Some(fragment)),
+        None => (did_url, None),
+    };
+    let (rest, query) = match rest.split_once('?') {
+        Some((rest, query)) => (rest, Some(query)),
+        None => (rest, None),
+    };
+    let (did, path) = match rest.find('/') {
+        Some(index) => rest.split_at(index),
+        None => (rest, ""),
+    };
+
+    let method_specific_id = match did.strip_prefix("did:").and_then(|did| did.split_once(':')) {
+        Some((method, method_specific_id)) if method == DID_METHOD => method_specific_id,
+        Some((method, _)) => return Err(format!("unsupported DID method {}", method)),
+        None => return Err(String::from("not a DID")),
+    };
+    let valid_part = |part: &str| {
+        !part.is_empty()
+            && part
+                .chars()
+                .all(|c| c.is_ascii_alphanumeric() || c == '.' || c == '-' || c == '_')
+    };
+    let (did_network, identifier) = match method_specific_id.split_once(':') {
+        Some((did_network, identifier)) => (Some(did_network), identifier),
+        None => (None, method_specific_id),
+    };
+    if !did_network.map_or(true, valid_part) || !valid_part(identifier) {
+        return Err(format!("invalid identifier {}", method_specific_id));
+    }
+    if let (Some(did_network), Some(network)) = (did_network, network) {
+        if did_network != network {
+            return Err(format!("DID of network {}", did_network));
+        }
+    }
+
+    let mut params = vec![];
+    for param in query.unwrap_or("").split('&').filter(|p| !p.is_empty()) {
+        let (name, value) = param.split_once('=').unwrap_or((param, ""));
+        params.push((percent_decode(name)?, percent_decode(value)?));
+    }
+    let fragment = fragment.map(percent_decode).transpose()?;
+
+    Ok(DidUrl {
+        identifier: Addr::unchecked(identifier),
+        path: percent_decode(path)?,
+        query: params,
+        fragment,
+    })
+}
+
+fn percent_decode(encoded: &str) -> Result<String, String> {
+    let invalid = || format!("invalid percent encoding in {}", encoded);
+    let bytes = encoded.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        if bytes[i] == b'%' {
+            let hex = encoded.get(i + 1..i + 3).ok_or_else(invalid)?;
+            decoded.push(u8::from_str_radix(hex, 16).map_err(|_| invalid())?);
+            i += 3;
+        } else {
+            decoded.push(bytes[i]);
+            i += 1;
+        }
+    }
+    String::from_utf8(decoded).map_err(|_| invalid())
+}
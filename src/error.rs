@@ -1,4 +1,6 @@
-use cosmwasm_std::{Addr, StdError};
+use cosmwasm_std::{Addr, Coin, StdError, Timestamp};
+
+use crate::msg::{KeyType, Operation, Relationship};
 use thiserror::Error;
 
 #[derive(Error, Debug, PartialEq)]
@@ -9,6 +11,36 @@ pub enum ContractError {
     #[error("Unauthorized")]
     Unauthorized {},
 
+    #[error("Paused")]
+    Paused {},
+
+    #[error("Deactivated: {identifier:?}")]
+    Deactivated { identifier: Addr },
+
+    #[error("Insufficient fee: {required}")]
+    InsufficientFee { required: Coin },
+
+    #[error("Duplicate fee for operation: {operation:?}")]
+    DuplicateFee { operation: Operation },
+
+    #[error("Insufficient deposit: {required}")]
+    InsufficientDeposit { required: Coin },
+
+    #[error("Nothing to prune")]
+    NothingToPrune {},
+
+    #[error("Cannot migrate from {previous_contract}")]
+    CannotMigrate { previous_contract: String },
+
+    #[error("Owner required to migrate")]
+    MissingOwner {},
+
+    #[error("Migration in progress, migrate again to finish the backfill")]
+    MigrationInProgress {},
+
+    #[error("No fees to withdraw")]
+    NoFees {},
+
     #[error("Invalid (identifier, name) pair: {identifier:?}, {name:?}, {value:?}")]
     InvalidKeyPair {
         identifier: Addr,
@@ -16,6 +48,111 @@ pub enum ContractError {
         value: String,
     },
 
+    #[error("Hook already registered: {hook:?}")]
+    HookAlreadyRegistered { hook: Addr },
+
+    #[error("Too many hooks, the maximum is {max}")]
+    TooManyHooks { max: usize },
+
+    #[error("Hook not registered: {hook:?}")]
+    HookNotRegistered { hook: Addr },
+
+    #[error("Unknown reply id: {id}")]
+    UnknownReplyId { id: u64 },
+
+    #[error("Pending change exists: {identifier:?}")]
+    PendingChangeExists { identifier: Addr },
+
+    #[error("Delay too long, the maximum is {max}")]
+    DelayTooLong { max: u64 },
+
+    #[error("No pending change: {identifier:?}")]
+    NoPendingChange { identifier: Addr },
+
+    #[error("Pending change not executable yet: {identifier:?}")]
+    PendingChangeNotReady { identifier: Addr },
+
+    #[error("Commitment exists: {identifier:?}")]
+    CommitmentExists { identifier: Addr },
+
+    #[error("New controller does not match the commitment")]
+    CommitmentMismatch {},
+
+    #[error("Next controller commitment required")]
+    MissingCommitment {},
+
+    #[error("Invalid public key: {reason}")]
+    InvalidPublicKey { reason: String },
+
+    #[error("Invalid verification method id: {id}")]
+    InvalidVerificationMethodId { id: String },
+
+    #[error("Verification method exists: {id}")]
+    VerificationMethodExists { id: String },
+
+    #[error("Verification method not found: {id}")]
+    VerificationMethodNotFound { id: String },
+
+    #[error("Invalid DID {did}: {reason}")]
+    InvalidDid { did: String, reason: String },
+
+    #[error("Invalid service: {reason}")]
+    InvalidService { reason: String },
+
+    #[error("Service exists: {id}")]
+    ServiceExists { id: String },
+
+    #[error("Service not found: {id}")]
+    ServiceNotFound { id: String },
+
+    #[error("Too many services, the maximum is {max}")]
+    TooManyServices { max: u32 },
+
+    #[error("{key_type:?} keys cannot be used for {relationship:?}")]
+    InvalidRelationship {
+        key_type: KeyType,
+        relationship: Relationship,
+    },
+
+    #[error("Delegates cannot be used for {relationship:?}")]
+    InvalidDelegateRelationship { relationship: Relationship },
+
+    #[error("Invalid alias: {reason}")]
+    InvalidAlias { reason: String },
+
+    #[error("Alias signature does not verify")]
+    InvalidAliasSignature {},
+
+    #[error("Alias not found: {other}")]
+    AliasNotFound { other: Addr },
+
+    #[error("Version mismatch: expected {expected}, current {current}")]
+    VersionMismatch { expected: u64, current: u64 },
+
+    #[error("Lease already ended: {expires}")]
+    LeaseEnded { expires: Timestamp },
+
+    #[error("Lease in effect until {expires}")]
+    LeaseActive { expires: Timestamp },
+
+    #[error("Invalid guardian threshold: {threshold} of {guardians}")]
+    InvalidThreshold { threshold: u32, guardians: u32 },
+
+    #[error("No guardians: {identifier:?}")]
+    NoGuardians { identifier: Addr },
+
+    #[error("Recovery in progress: {identifier:?}")]
+    RecoveryInProgress { identifier: Addr },
+
+    #[error("No recovery: {identifier:?}")]
+    NoRecovery { identifier: Addr },
+
+    #[error("Recovery not executable yet: {identifier:?}")]
+    RecoveryNotReady { identifier: Addr },
+
+    #[error("Recovery delay has to be at least {min} seconds")]
+    RecoveryDelayTooShort { min: u64 },
+
     #[error("Custom Error val: {val:?}")]
     CustomError { val: String },
     // Add any other custom errors you like here.
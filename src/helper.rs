@@ -1,6 +1,76 @@
-use cosmwasm_std::{Addr, Attribute, Response};
+use cosmwasm_std::{
+    to_binary, Addr, Attribute, BankMsg, Binary, BlockInfo, Coin, CosmosMsg, Empty, Order,
+    QuerierWrapper, QueryRequest, Record, Response, StdError, StdResult, Storage, SubMsg,
+    Timestamp, Uint128, WasmMsg, WasmQuery,
+};
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::collections::BTreeMap;
 
+use crate::did::parse_did;
 use crate::error::ContractError;
+use crate::msg::{
+    AttributeResponse, ChangedResponse, ControllerResponse, DidChange, DidChangedHookMsg,
+    DidDocument, ExecuteMsg, HookFailure, KeyType, Lease, Operation, OperationFee, QueryMsg,
+    Relationship, ResolveResponse, Service, ServiceEndpoint, ValidToResponse,
+};
+use crate::state::{
+    controllers, leases, GuardianSet, Registration, Stats, ATTRIBUTE_INDEX, COLLECTED_FEES,
+    COMMITMENTS, CONFIG, DEACTIVATED, DEPOSITS, GLOBAL_HOOKS, GUARDIANS, HOOKS, IDENTIFIERS,
+    MAX_SERVICE_ENDPOINTS, MAX_URL_LENGTH, STATS, UPDATED, VERSIONS,
+};
+
+pub const HOOK_REPLY_ID: u64 = 1;
+
+/// loads the controller in effect at the block, None while the identifier controls itself.
+/// once a lease ends, its fallback is in control.
+pub fn load_controller(
+    storage: &dyn Storage,
+    block: &BlockInfo,
+    identifier: &Addr,
+) -> StdResult<Option<Addr>> {
+    if let Some(lease) = leases().may_load(storage, identifier)? {
+        if lease.expires <= block.time {
+            return Ok(lease.fallback);
+        }
+    }
+    controllers().may_load(storage, identifier)
+}
+
+/// the lease for a controller change. a lease in effect is kept with its expiry and fallback,
+/// so only the fallback ends it once it took over.
+pub fn keep_lease(
+    storage: &dyn Storage,
+    block: &BlockInfo,
+    identifier: &Addr,
+    lease: Option<Lease>,
+) -> Result<Option<Lease>, ContractError> {
+    match leases().may_load(storage, identifier)? {
+        Some(active) if active.expires > block.time => match lease {
+            Some(lease) if lease != active => Err(ContractError::LeaseActive {
+                expires: active.expires,
+            }),
+            _ => Ok(Some(active)),
+        },
+        _ => Ok(lease),
+    }
+}
+
+/// a controller in a lease cannot leave commitments, policies or guardians behind that bind
+/// the fallback once the lease ends
+pub fn only_unleased(
+    storage: &dyn Storage,
+    block: &BlockInfo,
+    identifier: &Addr,
+) -> Result<(), ContractError> {
+    match leases().may_load(storage, identifier)? {
+        Some(lease) if lease.expires > block.time => Err(ContractError::LeaseActive {
+            expires: lease.expires,
+        }),
+        _ => Ok(()),
+    }
+}
 
 pub fn only_controller(
     sender: &Addr,
@@ -23,6 +93,561 @@ pub fn only_controller(
     }
 }
 
+pub fn only_guardian(
+    storage: &dyn Storage,
+    sender: &Addr,
+    identifier: &Addr,
+) -> Result<GuardianSet, ContractError> {
+    let guardian_set =
+        GUARDIANS
+            .may_load(storage, identifier)?
+            .ok_or_else(|| ContractError::NoGuardians {
+                identifier: identifier.clone(),
+            })?;
+    if !guardian_set.guardians.contains(sender) {
+        return Err(ContractError::Unauthorized {});
+    }
+    Ok(guardian_set)
+}
+
+/// sha256 of the address, as committed with CommitNextController
+pub fn controller_hash(controller: &Addr) -> Binary {
+    Binary::from(Sha256::digest(controller.as_bytes()).to_vec())
+}
+
+/// with a commitment in place, the new controller has to match it and commit to the next one
+pub fn check_commitment(
+    storage: &dyn Storage,
+    identifier: &Addr,
+    new_controller: &Addr,
+    next_hash: &Option<Binary>,
+) -> Result<(), ContractError> {
+    if let Some(committed) = COMMITMENTS.may_load(storage, identifier)? {
+        if controller_hash(new_controller) != committed {
+            return Err(ContractError::CommitmentMismatch {});
+        }
+        if next_hash.is_none() {
+            return Err(ContractError::MissingCommitment {});
+        }
+    }
+    Ok(())
+}
+
+/// recoveries have to go to the committed controller, which ends the commitment
+pub fn check_recovery_commitment(
+    storage: &dyn Storage,
+    identifier: &Addr,
+    new_controller: &Addr,
+) -> Result<(), ContractError> {
+    match COMMITMENTS.may_load(storage, identifier)? {
+        Some(committed) if controller_hash(new_controller) != committed => {
+            Err(ContractError::CommitmentMismatch {})
+        }
+        _ => Ok(()),
+    }
+}
+
+pub fn only_owner(storage: &dyn Storage, sender: &Addr) -> Result<(), ContractError> {
+    let config = CONFIG.load(storage)?;
+    if sender != &config.owner {
+        return Err(ContractError::Unauthorized {});
+    }
+    Ok(())
+}
+
+pub fn only_active(storage: &dyn Storage, identifier: &Addr) -> Result<(), ContractError> {
+    if DEACTIVATED.has(storage, identifier) {
+        return Err(ContractError::Deactivated {
+            identifier: identifier.clone(),
+        });
+    }
+    Ok(())
+}
+
+pub fn only_version(
+    storage: &dyn Storage,
+    identifier: &Addr,
+    expected: u64,
+) -> Result<(), ContractError> {
+    let current = VERSIONS.may_load(storage, identifier)?.unwrap_or(0);
+    if current != expected {
+        return Err(ContractError::VersionMismatch { expected, current });
+    }
+    Ok(())
+}
+
+/// increments the version of the identifier and returns the new one
+pub fn bump_version(
+    storage: &mut dyn Storage,
+    block: &BlockInfo,
+    identifier: &Addr,
+) -> StdResult<u64> {
+    let version = VERSIONS.may_load(storage, identifier)?.unwrap_or(0) + 1;
+    VERSIONS.save(storage, identifier, &version)?;
+    UPDATED.save(storage, identifier, &block.time)?;
+    Ok(version)
+}
+
+/// message the signer signs to assert being also known as the alias, the nonce is the one of
+/// the signer
+pub fn alias_message(contract: &Addr, signer: &Addr, alias: &Addr, nonce: u64) -> Vec<u8> {
+    format!("link_alias:{}:{}:{}:{}", contract, signer, alias, nonce).into_bytes()
+}
+
+/// replaces the DIDs of a message by their addresses
+pub fn normalize_dids(network: Option<&str>, dids: Vec<&mut Addr>) -> Result<(), ContractError> {
+    for did in dids {
+        *did = parse_did(network, did.as_str()).map_err(|reason| ContractError::InvalidDid {
+            did: did.to_string(),
+            reason,
+        })?;
+    }
+    Ok(())
+}
+
+/// fragments of verification methods and services
+pub fn valid_fragment(id: &str) -> bool {
+    !id.is_empty()
+        && id.len() <= 64
+        && id
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_' || c == '.')
+}
+
+/// absolute URI with a scheme, http and https need a host
+pub fn valid_url(url: &str) -> bool {
+    let (scheme, rest) = match url.split_once(':') {
+        Some(parts) => parts,
+        None => return false,
+    };
+    let valid_scheme = scheme.starts_with(|c: char| c.is_ascii_alphabetic())
+        && scheme
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '+' || c == '-' || c == '.');
+    let valid_rest = match scheme {
+        "http" | "https" => rest
+            .strip_prefix("//")
+            .map_or(false, |rest| !rest.is_empty() && !rest.starts_with('/')),
+        _ => !rest.is_empty(),
+    };
+    url.len() <= MAX_URL_LENGTH
+        && valid_scheme
+        && valid_rest
+        && !url.chars().any(|c| c.is_whitespace() || c.is_control())
+}
+
+pub fn validate_service(service: &Service) -> Result<(), ContractError> {
+    let invalid = |reason: &str| ContractError::InvalidService {
+        reason: reason.to_string(),
+    };
+    if !valid_fragment(&service.id) {
+        return Err(invalid("invalid id"));
+    }
+    if service.service_type.is_empty() || service.service_type.len() > 64 {
+        return Err(invalid("invalid type"));
+    }
+    let (endpoints, accept, routing_keys) = match &service.service_endpoint {
+        ServiceEndpoint::Uri(uri) => (vec![uri], vec![], vec![]),
+        ServiceEndpoint::Set(uris) => (uris.iter().collect(), vec![], vec![]),
+        ServiceEndpoint::Map(map) => (
+            vec![&map.uri],
+            map.accept.iter().collect(),
+            map.routing_keys.iter().collect(),
+        ),
+    };
+    if endpoints.is_empty() || endpoints.len() > MAX_SERVICE_ENDPOINTS {
+        return Err(invalid("invalid number of endpoints"));
+    }
+    if let Some(endpoint) = endpoints.into_iter().find(|e| !valid_url(e)) {
+        return Err(invalid(&format!("invalid endpoint {}", endpoint)));
+    }
+    let accept: Vec<&String> = service.accept.iter().chain(accept).collect();
+    if accept.len() > MAX_SERVICE_ENDPOINTS || accept.iter().any(|a| a.is_empty() || a.len() > 64) {
+        return Err(invalid("invalid accept"));
+    }
+    let routing_keys: Vec<&String> = service.routing_keys.iter().chain(routing_keys).collect();
+    if routing_keys.len() > MAX_SERVICE_ENDPOINTS || routing_keys.iter().any(|k| !valid_url(k)) {
+        return Err(invalid("invalid routing keys"));
+    }
+    Ok(())
+}
+
+pub fn only_key_relationship(
+    key_type: KeyType,
+    relationship: Relationship,
+) -> Result<(), ContractError> {
+    // X25519 keys only agree on keys, they cannot sign
+    if key_type == KeyType::X25519 && relationship != Relationship::KeyAgreement {
+        return Err(ContractError::InvalidRelationship {
+            key_type,
+            relationship,
+        });
+    }
+    Ok(())
+}
+
+pub fn only_unpaused(storage: &dyn Storage, operation: Operation) -> Result<(), ContractError> {
+    let config = CONFIG.load(storage)?;
+    if config.paused || config.paused_operations.contains(&operation) {
+        return Err(ContractError::Paused {});
+    }
+    Ok(())
+}
+
+/// checks the funds sent along with the operation against its fee and books the fee as collected.
+/// returns the funds left for the operation itself.
+pub fn collect_fee(
+    storage: &mut dyn Storage,
+    operation: Operation,
+    funds: Vec<Coin>,
+) -> Result<Vec<Coin>, ContractError> {
+    let config = CONFIG.load(storage)?;
+    let required = match config.fees.iter().find(|f| f.operation == operation) {
+        Some(operation_fee) => &operation_fee.fee,
+        None => return Ok(funds),
+    };
+
+    let remaining = take_funds(funds, required).ok_or(ContractError::InsufficientFee {
+        required: required.clone(),
+    })?;
+
+    COLLECTED_FEES.update(storage, &required.denom, |collected| -> StdResult<_> {
+        Ok(collected.unwrap_or_default() + required.amount)
+    })?;
+    Ok(remaining)
+}
+
+/// locks the storage deposit of a new attribute value from the funds left after the fee
+pub fn lock_deposit(
+    storage: &mut dyn Storage,
+    identifier: &Addr,
+    name: &str,
+    value: &str,
+    funds: Vec<Coin>,
+) -> Result<Vec<Coin>, ContractError> {
+    let config = CONFIG.load(storage)?;
+    let per_byte = match config.deposit_per_byte {
+        Some(per_byte) if !per_byte.amount.is_zero() => per_byte,
+        _ => return Ok(funds),
+    };
+
+    let bytes = Uint128::from((name.len() + value.len()) as u128);
+    let required = Coin {
+        denom: per_byte.denom,
+        amount: per_byte
+            .amount
+            .checked_mul(bytes)
+            .map_err(StdError::overflow)?,
+    };
+
+    let remaining = take_funds(funds, &required).ok_or(ContractError::InsufficientDeposit {
+        required: required.clone(),
+    })?;
+
+    DEPOSITS.save(storage, (identifier, name, value), &required)?;
+    Ok(remaining)
+}
+
+/// registers the identifier the first time it is seen
+pub fn register_identifier(
+    storage: &mut dyn Storage,
+    block: &BlockInfo,
+    identifier: &Addr,
+) -> StdResult<()> {
+    if IDENTIFIERS.has(storage, identifier) {
+        return Ok(());
+    }
+    let registration = Registration {
+        height: block.height,
+        time: block.time,
+    };
+    IDENTIFIERS.save(storage, identifier, &registration)?;
+    update_stats(storage, |stats| stats.identifiers += 1)
+}
+
+pub fn update_stats(storage: &mut dyn Storage, action: impl FnOnce(&mut Stats)) -> StdResult<()> {
+    let mut stats = STATS.may_load(storage)?.unwrap_or_default();
+    action(&mut stats);
+    STATS.save(storage, &stats)
+}
+
+/// indexes the attribute value if its name starts with one of the indexed prefixes
+pub fn index_attribute(
+    storage: &mut dyn Storage,
+    identifier: &Addr,
+    name: &str,
+    value: &str,
+) -> StdResult<()> {
+    let config = CONFIG.load(storage)?;
+    if config
+        .indexed_prefixes
+        .iter()
+        .any(|prefix| name.starts_with(prefix.as_str()))
+    {
+        ATTRIBUTE_INDEX.save(storage, (name, value, identifier), &true)?;
+    }
+    Ok(())
+}
+
+/// subtracts the amount from the funds, None if the funds are insufficient
+fn take_funds(mut funds: Vec<Coin>, amount: &Coin) -> Option<Vec<Coin>> {
+    if amount.amount.is_zero() {
+        return Some(funds);
+    }
+    let coin = funds.iter_mut().find(|coin| coin.denom == amount.denom)?;
+    coin.amount = coin.amount.checked_sub(amount.amount).ok()?;
+    Some(funds)
+}
+
+/// sends the funds nothing was charged for back to the sender
+pub fn refund_msg(recipient: &Addr, funds: Vec<Coin>) -> Option<BankMsg> {
+    let amount: Vec<Coin> = funds
+        .into_iter()
+        .filter(|coin| !coin.amount.is_zero())
+        .collect();
+    if amount.is_empty() {
+        return None;
+    }
+    Some(BankMsg::Send {
+        to_address: recipient.to_string(),
+        amount,
+    })
+}
+
+/// adds the coin to the list, merging amounts of the same denom
+pub fn add_coin(coins: &mut Vec<Coin>, coin: Coin) {
+    match coins.iter_mut().find(|c| c.denom == coin.denom) {
+        Some(c) => c.amount += coin.amount,
+        None => coins.push(coin),
+    }
+}
+
+pub fn validate_fees(fees: &[OperationFee]) -> Result<(), ContractError> {
+    for (i, operation_fee) in fees.iter().enumerate() {
+        if fees[..i]
+            .iter()
+            .any(|f| f.operation == operation_fee.operation)
+        {
+            return Err(ContractError::DuplicateFee {
+                operation: operation_fee.operation,
+            });
+        }
+    }
+    Ok(())
+}
+
+/// builds a DidChangedHook submessage for the identifier's hooks and the global hooks.
+/// a contract registered in both places is notified once, with the identifier's policy.
+pub fn hook_submsgs(
+    storage: &dyn Storage,
+    identifier: &Addr,
+    change: DidChange,
+) -> StdResult<Vec<SubMsg>> {
+    build_hook_submsgs(storage, identifier, change, false)
+}
+
+/// hooks notified as with HookFailure::Ignore, for changes no hook may block: passed proposals,
+/// and the other side of an alias link, which the sender does not control
+pub fn ignoring_hook_submsgs(
+    storage: &dyn Storage,
+    identifier: &Addr,
+    change: DidChange,
+) -> StdResult<Vec<SubMsg>> {
+    build_hook_submsgs(storage, identifier, change, true)
+}
+
+fn build_hook_submsgs(
+    storage: &dyn Storage,
+    identifier: &Addr,
+    change: DidChange,
+    ignore_failures: bool,
+) -> StdResult<Vec<SubMsg>> {
+    let mut hooks = HOOKS
+        .prefix(identifier)
+        .range(storage, None, None, Order::Ascending)
+        .collect::<StdResult<Vec<(Addr, HookFailure)>>>()?;
+
+    for global_hook in GLOBAL_HOOKS.range(storage, None, None, Order::Ascending) {
+        let (hook, on_failure) = global_hook?;
+        if !hooks.iter().any(|(h, _)| h == &hook) {
+            hooks.push((hook, on_failure));
+        }
+    }
+
+    hooks
+        .into_iter()
+        .map(|(hook, on_failure)| {
+            let msg = DidChangedHookMsg {
+                identifier: identifier.clone(),
+                change: change.clone(),
+            }
+            .into_cosmos_msg(hook)?;
+            Ok(match on_failure {
+                HookFailure::Abort if !ignore_failures => SubMsg::new(msg),
+                _ => SubMsg::reply_on_error(msg, HOOK_REPLY_ID),
+            })
+        })
+        .collect()
+}
+
+/// storage for dry runs, keeps the writes in memory on top of the contract storage
+pub struct DryRunStorage<'a> {
+    storage: &'a dyn Storage,
+    /// None for removed keys
+    writes: BTreeMap<Vec<u8>, Option<Vec<u8>>>,
+}
+
+impl<'a> DryRunStorage<'a> {
+    pub fn new(storage: &'a dyn Storage) -> Self {
+        DryRunStorage {
+            storage,
+            writes: BTreeMap::new(),
+        }
+    }
+}
+
+impl Storage for DryRunStorage<'_> {
+    fn get(&self, key: &[u8]) -> Option<Vec<u8>> {
+        match self.writes.get(key) {
+            Some(value) => value.clone(),
+            None => self.storage.get(key),
+        }
+    }
+
+    fn range<'b>(
+        &'b self,
+        start: Option<&[u8]>,
+        end: Option<&[u8]>,
+        order: Order,
+    ) -> Box<dyn Iterator<Item = Record> + 'b> {
+        let mut records: BTreeMap<Vec<u8>, Vec<u8>> =
+            self.storage.range(start, end, Order::Ascending).collect();
+        for (key, value) in &self.writes {
+            let in_range = start.map_or(true, |start| key.as_slice() >= start)
+                && end.map_or(true, |end| key.as_slice() < end);
+            if !in_range {
+                continue;
+            }
+            match value {
+                Some(value) => records.insert(key.clone(), value.clone()),
+                None => records.remove(key),
+            };
+        }
+        match order {
+            Order::Ascending => Box::new(records.into_iter()),
+            Order::Descending => Box::new(records.into_iter().rev()),
+        }
+    }
+
+    fn set(&mut self, key: &[u8], value: &[u8]) {
+        self.writes.insert(key.to_vec(), Some(value.to_vec()));
+    }
+
+    fn remove(&mut self, key: &[u8]) {
+        self.writes.insert(key.to_vec(), None);
+    }
+}
+
+/// DidRegistryContract is a wrapper around Addr that provides typed helpers
+/// for contracts consuming the DID registry
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq, JsonSchema)]
+pub struct DidRegistryContract(pub Addr);
+
+impl DidRegistryContract {
+    pub fn addr(&self) -> Addr {
+        self.0.clone()
+    }
+
+    pub fn call<T: Into<ExecuteMsg>>(&self, msg: T, funds: Vec<Coin>) -> StdResult<CosmosMsg> {
+        let msg = to_binary(&msg.into())?;
+        Ok(WasmMsg::Execute {
+            contract_addr: self.addr().into(),
+            msg,
+            funds,
+        }
+        .into())
+    }
+
+    pub fn query_request(&self, msg: &QueryMsg) -> StdResult<QueryRequest<Empty>> {
+        Ok(WasmQuery::Smart {
+            contract_addr: self.addr().into(),
+            msg: to_binary(msg)?,
+        }
+        .into())
+    }
+
+    pub fn controller(
+        &self,
+        querier: &QuerierWrapper,
+        identifier: &Addr,
+    ) -> StdResult<ControllerResponse> {
+        let msg = QueryMsg::Controller {
+            identifier: identifier.clone(),
+        };
+        querier.query(&self.query_request(&msg)?)
+    }
+
+    pub fn attribute(
+        &self,
+        querier: &QuerierWrapper,
+        identifier: &Addr,
+        name: &str,
+    ) -> StdResult<AttributeResponse> {
+        let msg = QueryMsg::Attribute {
+            identifier: identifier.clone(),
+            name: name.to_string(),
+        };
+        querier.query(&self.query_request(&msg)?)
+    }
+
+    pub fn valid_to(
+        &self,
+        querier: &QuerierWrapper,
+        identifier: &Addr,
+        name: &str,
+        value: &str,
+    ) -> StdResult<ValidToResponse> {
+        let msg = QueryMsg::ValidTo {
+            identifier: identifier.clone(),
+            name: name.to_string(),
+            value: value.to_string(),
+        };
+        querier.query(&self.query_request(&msg)?)
+    }
+
+    /// returns true if the attribute value is still valid at the given block time
+    pub fn is_valid_attribute(
+        &self,
+        querier: &QuerierWrapper,
+        identifier: &Addr,
+        name: &str,
+        value: &str,
+        now: Timestamp,
+    ) -> StdResult<bool> {
+        let res = self.valid_to(querier, identifier, name, value)?;
+        Ok(res.valid_to > now)
+    }
+
+    pub fn resolve(&self, querier: &QuerierWrapper, identifier: &Addr) -> StdResult<DidDocument> {
+        let msg = QueryMsg::Resolve {
+            identifier: identifier.clone(),
+        };
+        let res: ResolveResponse = querier.query(&self.query_request(&msg)?)?;
+        Ok(res.document)
+    }
+
+    pub fn changed(
+        &self,
+        querier: &QuerierWrapper,
+        identifier: &Addr,
+    ) -> StdResult<ChangedResponse> {
+        let msg = QueryMsg::Changed {
+            identifier: identifier.clone(),
+        };
+        querier.query(&self.query_request(&msg)?)
+    }
+}
+
 pub fn get_attribute_value(response: Response, target: &str) -> String {
     let attribute: Vec<Attribute> = response
         .attributes
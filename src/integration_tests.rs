@@ -0,0 +1,352 @@
+#[cfg(test)]
+mod tests {
+    use crate::helper::DidRegistryContract;
+    use crate::msg::{
+        AliasesResponse, ExecuteMsg, HookFailure, InstantiateMsg, Operation, OperationFee, QueryMsg,
+    };
+    use cosmwasm_std::{
+        coin, coins, Addr, Binary, Deps, DepsMut, Empty, Env, MessageInfo, Response, StdError,
+        StdResult,
+    };
+    use cw_multi_test::{App, Contract, ContractWrapper, Executor};
+
+    pub fn contract_did() -> Box<dyn Contract<Empty>> {
+        let contract = ContractWrapper::new(
+            crate::contract::execute,
+            crate::contract::instantiate,
+            crate::contract::query,
+        )
+        .with_reply(crate::contract::reply);
+        Box::new(contract)
+    }
+
+    /// hook contract failing on every change it is notified of
+    pub fn contract_failing_hook() -> Box<dyn Contract<Empty>> {
+        fn execute(_: DepsMut, _: Env, _: MessageInfo, _: Empty) -> StdResult<Response> {
+            Err(StdError::generic_err("hook failed"))
+        }
+        fn instantiate(_: DepsMut, _: Env, _: MessageInfo, _: Empty) -> StdResult<Response> {
+            Ok(Response::new())
+        }
+        fn query(_: Deps, _: Env, _: Empty) -> StdResult<Binary> {
+            Err(StdError::generic_err("no queries"))
+        }
+        Box::new(ContractWrapper::new(execute, instantiate, query))
+    }
+
+    const USER: &str = "identifier0001";
+    const CONTROLLER: &str = "addr0001";
+    const ADMIN: &str = "admin";
+
+    fn proper_instantiate() -> (App, DidRegistryContract) {
+        let mut app = App::default();
+        let did_id = app.store_code(contract_did());
+
+        let msg = InstantiateMsg {
+            owner: None,
+            fees: vec![],
+            deposit_per_byte: None,
+            network: None,
+        };
+        let did_contract_addr = app
+            .instantiate_contract(did_id, Addr::unchecked(ADMIN), &msg, &[], "did", None)
+            .unwrap();
+
+        let did_contract = DidRegistryContract(did_contract_addr);
+
+        (app, did_contract)
+    }
+
+    mod registry {
+        use super::*;
+
+        #[test]
+        fn change_controller() {
+            let (mut app, did_contract) = proper_instantiate();
+            let identifier = Addr::unchecked(USER);
+
+            let res = did_contract.controller(&app.wrap(), &identifier).unwrap();
+            assert_eq!(res.controller, identifier);
+
+            let msg = ExecuteMsg::ChangeController {
+                identifier: identifier.clone(),
+                new_controller: Addr::unchecked(CONTROLLER),
+                next_hash: None,
+                lease: None,
+                expected_version: None,
+            };
+            let cosmos_msg = did_contract.call(msg, vec![]).unwrap();
+            app.execute(identifier.clone(), cosmos_msg).unwrap();
+
+            let res = did_contract.controller(&app.wrap(), &identifier).unwrap();
+            assert_eq!(res.controller, Addr::unchecked(CONTROLLER));
+
+            let res = did_contract.changed(&app.wrap(), &identifier).unwrap();
+            assert_eq!(res.block, app.block_info().height);
+
+            // the previous controller cannot change it anymore
+            let msg = ExecuteMsg::ChangeController {
+                identifier: identifier.clone(),
+                new_controller: identifier.clone(),
+                next_hash: None,
+                lease: None,
+                expected_version: None,
+            };
+            let cosmos_msg = did_contract.call(msg, vec![]).unwrap();
+            app.execute(identifier, cosmos_msg).unwrap_err();
+        }
+
+        #[test]
+        fn set_and_revoke_attribute() {
+            let (mut app, did_contract) = proper_instantiate();
+            let identifier = Addr::unchecked(USER);
+
+            let msg = ExecuteMsg::SetAttribute {
+                identifier: identifier.clone(),
+                name: String::from("service"),
+                value: String::from("github"),
+                validity: 1000,
+                expected_version: None,
+            };
+            let cosmos_msg = did_contract.call(msg, vec![]).unwrap();
+            app.execute(identifier.clone(), cosmos_msg).unwrap();
+
+            let res = did_contract
+                .attribute(&app.wrap(), &identifier, "service")
+                .unwrap();
+            assert_eq!(res.values, ["github"]);
+
+            let now = app.block_info().time;
+            let res = did_contract
+                .valid_to(&app.wrap(), &identifier, "service", "github")
+                .unwrap();
+            assert_eq!(res.valid_to, now.plus_seconds(1000));
+
+            let valid = did_contract
+                .is_valid_attribute(&app.wrap(), &identifier, "service", "github", now)
+                .unwrap();
+            assert!(valid);
+
+            let valid = did_contract
+                .is_valid_attribute(
+                    &app.wrap(),
+                    &identifier,
+                    "service",
+                    "github",
+                    now.plus_seconds(1000),
+                )
+                .unwrap();
+            assert!(!valid);
+
+            let msg = ExecuteMsg::RevokeAttribute {
+                identifier: identifier.clone(),
+                name: String::from("service"),
+                value: String::from("github"),
+                expected_version: None,
+            };
+            let cosmos_msg = did_contract.call(msg, vec![]).unwrap();
+            app.execute(identifier.clone(), cosmos_msg).unwrap();
+
+            let valid = did_contract
+                .is_valid_attribute(&app.wrap(), &identifier, "service", "github", now)
+                .unwrap();
+            assert!(!valid);
+        }
+
+        #[test]
+        fn surplus_funds_are_refunded() {
+            let identifier = Addr::unchecked(USER);
+            let mut app = App::new(|router, _, storage| {
+                router
+                    .bank
+                    .init_balance(storage, &identifier, coins(1000, "ugrano"))
+                    .unwrap();
+            });
+            let did_id = app.store_code(contract_did());
+            let msg = InstantiateMsg {
+                owner: None,
+                fees: vec![OperationFee {
+                    operation: Operation::SetAttribute,
+                    fee: coin(10, "ugrano"),
+                }],
+                deposit_per_byte: Some(coin(1, "ugrano")),
+                network: None,
+            };
+            let did_contract_addr = app
+                .instantiate_contract(did_id, Addr::unchecked(ADMIN), &msg, &[], "did", None)
+                .unwrap();
+            let did_contract = DidRegistryContract(did_contract_addr);
+
+            // the fee and the deposit of 13 bytes are kept, the rest is refunded
+            let msg = ExecuteMsg::SetAttribute {
+                identifier: identifier.clone(),
+                name: String::from("service"),
+                value: String::from("github"),
+                validity: 1000,
+                expected_version: None,
+            };
+            let cosmos_msg = did_contract.call(msg, coins(100, "ugrano")).unwrap();
+            app.execute(identifier.clone(), cosmos_msg).unwrap();
+
+            let balance = app.wrap().query_balance(&identifier, "ugrano").unwrap();
+            assert_eq!(balance.amount.u128(), 1000 - 10 - 13);
+
+            // funds sent along with free operations go back as well
+            let msg = ExecuteMsg::ChangeController {
+                identifier: identifier.clone(),
+                new_controller: Addr::unchecked(CONTROLLER),
+                next_hash: None,
+                lease: None,
+                expected_version: None,
+            };
+            let cosmos_msg = did_contract.call(msg, coins(50, "ugrano")).unwrap();
+            app.execute(identifier.clone(), cosmos_msg).unwrap();
+
+            let balance = app.wrap().query_balance(&identifier, "ugrano").unwrap();
+            assert_eq!(balance.amount.u128(), 1000 - 10 - 13);
+            let balance = app
+                .wrap()
+                .query_balance(did_contract.addr(), "ugrano")
+                .unwrap();
+            assert_eq!(balance.amount.u128(), 10 + 13);
+        }
+
+        #[test]
+        fn failing_hooks() {
+            let (mut app, did_contract) = proper_instantiate();
+            let identifier = Addr::unchecked(USER);
+            let hook_id = app.store_code(contract_failing_hook());
+            let hook = app
+                .instantiate_contract(
+                    hook_id,
+                    Addr::unchecked(ADMIN),
+                    &Empty {},
+                    &[],
+                    "hook",
+                    None,
+                )
+                .unwrap();
+
+            // an aborting hook reverts the whole change
+            let msg = ExecuteMsg::AddHook {
+                identifier: identifier.clone(),
+                hook: hook.clone(),
+                on_failure: HookFailure::Abort,
+                expected_version: None,
+            };
+            let cosmos_msg = did_contract.call(msg, vec![]).unwrap();
+            app.execute(identifier.clone(), cosmos_msg).unwrap();
+
+            let set_attribute = ExecuteMsg::SetAttribute {
+                identifier: identifier.clone(),
+                name: String::from("service"),
+                value: String::from("github"),
+                validity: 1000,
+                expected_version: None,
+            };
+            let cosmos_msg = did_contract.call(set_attribute.clone(), vec![]).unwrap();
+            let err = app.execute(identifier.clone(), cosmos_msg).unwrap_err();
+            assert_eq!(err.root_cause().to_string(), "Generic error: hook failed");
+
+            let res = did_contract
+                .attribute(&app.wrap(), &identifier, "service")
+                .unwrap();
+            assert!(res.values.is_empty());
+
+            // an ignored hook failure keeps the change
+            let msg = ExecuteMsg::RemoveHook {
+                identifier: identifier.clone(),
+                hook: hook.clone(),
+                expected_version: None,
+            };
+            let cosmos_msg = did_contract.call(msg, vec![]).unwrap();
+            app.execute(identifier.clone(), cosmos_msg).unwrap();
+            let msg = ExecuteMsg::AddHook {
+                identifier: identifier.clone(),
+                hook,
+                on_failure: HookFailure::Ignore,
+                expected_version: None,
+            };
+            let cosmos_msg = did_contract.call(msg, vec![]).unwrap();
+            app.execute(identifier.clone(), cosmos_msg).unwrap();
+
+            let cosmos_msg = did_contract.call(set_attribute, vec![]).unwrap();
+            app.execute(identifier.clone(), cosmos_msg).unwrap();
+
+            let res = did_contract
+                .attribute(&app.wrap(), &identifier, "service")
+                .unwrap();
+            assert_eq!(res.values, ["github"]);
+        }
+
+        #[test]
+        fn failing_alias_hooks() {
+            let (mut app, did_contract) = proper_instantiate();
+            let identifier = Addr::unchecked(USER);
+            let other = Addr::unchecked("identifier0002");
+            let hook_id = app.store_code(contract_failing_hook());
+            let hook = app
+                .instantiate_contract(
+                    hook_id,
+                    Addr::unchecked(ADMIN),
+                    &Empty {},
+                    &[],
+                    "hook",
+                    None,
+                )
+                .unwrap();
+
+            // the other identifier asserts the link and registers an aborting hook
+            let msgs = vec![
+                ExecuteMsg::LinkAlias {
+                    identifier: other.clone(),
+                    other: identifier.clone(),
+                    signature: None,
+                    expected_version: None,
+                },
+                ExecuteMsg::AddHook {
+                    identifier: other.clone(),
+                    hook,
+                    on_failure: HookFailure::Abort,
+                    expected_version: None,
+                },
+            ];
+            for msg in msgs {
+                let cosmos_msg = did_contract.call(msg, vec![]).unwrap();
+                app.execute(other.clone(), cosmos_msg).unwrap();
+            }
+
+            // its hook cannot block linking nor unlinking the alias
+            let msg = ExecuteMsg::LinkAlias {
+                identifier: identifier.clone(),
+                other: other.clone(),
+                signature: None,
+                expected_version: None,
+            };
+            let cosmos_msg = did_contract.call(msg, vec![]).unwrap();
+            app.execute(identifier.clone(), cosmos_msg).unwrap();
+
+            let verified = |app: &App| {
+                let msg = QueryMsg::Aliases {
+                    identifier: Addr::unchecked("identifier0002"),
+                };
+                let res: AliasesResponse = app
+                    .wrap()
+                    .query_wasm_smart(did_contract.addr(), &msg)
+                    .unwrap();
+                res.aliases[0].verified
+            };
+            assert!(verified(&app));
+
+            let msg = ExecuteMsg::UnlinkAlias {
+                identifier: identifier.clone(),
+                other: other.clone(),
+                expected_version: None,
+            };
+            let cosmos_msg = did_contract.call(msg, vec![]).unwrap();
+            app.execute(identifier, cosmos_msg).unwrap();
+
+            assert!(!verified(&app));
+        }
+    }
+}
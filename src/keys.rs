@@ -0,0 +1,235 @@
+use cosmwasm_std::{Addr, Api, Binary};
+use ripemd::Ripemd160;
+use sha2::{Digest, Sha256};
+
+use crate::error::ContractError;
+use crate::msg::{Jwk, KeyType, PublicKey};
+
+const BASE58_ALPHABET: &[u8; 58] = b"123456789ABCDEFGHJKLMNPQRSTUVWXYZabcdefghijkmnopqrstuvwxyz";
+
+/// decodes the public key and checks its length for the key type
+pub fn decode_public_key(
+    key_type: KeyType,
+    public_key: &PublicKey,
+) -> Result<Vec<u8>, ContractError> {
+    let key = match public_key {
+        PublicKey::Multibase(encoded) => {
+            let bytes =
+                decode_multibase(encoded).ok_or_else(|| invalid("unknown multibase encoding"))?;
+            // the multicodec header is optional, did:key style keys come with it
+            match bytes.strip_prefix(multicodec(key_type)) {
+                Some(key) => key.to_vec(),
+                None => bytes,
+            }
+        }
+        PublicKey::Jwk(jwk) => decode_jwk(key_type, jwk)?,
+        PublicKey::Hex(encoded) => {
+            let encoded = encoded.strip_prefix("0x").unwrap_or(encoded);
+            decode_hex(encoded).ok_or_else(|| invalid("invalid hex"))?
+        }
+    };
+
+    let valid = match key_type {
+        KeyType::Ed25519 | KeyType::X25519 => key.len() == 32,
+        // compressed or uncompressed SEC1 points
+        KeyType::Secp256k1 | KeyType::P256 => {
+            matches!((key.len(), key.first()), (33, Some(2 | 3)) | (65, Some(4)))
+        }
+    };
+    if !valid {
+        return Err(invalid(&format!(
+            "invalid {:?} key of {} bytes",
+            key_type,
+            key.len()
+        )));
+    }
+    Ok(key)
+}
+
+/// multibase base58btc of the key with its multicodec header, as in did:key
+pub fn to_multibase(key_type: KeyType, key: &[u8]) -> String {
+    let mut bytes = multicodec(key_type).to_vec();
+    bytes.extend_from_slice(key);
+    format!("z{}", base58_encode(&bytes))
+}
+
+/// key type, purpose and key of an ERC1056 style `did/pub/<type>/<purpose>/<encoding>` attribute
+pub fn decode_key_attribute<'a>(name: &'a str, value: &str) -> Option<(KeyType, &'a str, Vec<u8>)> {
+    let mut parts = name.strip_prefix("did/pub/")?.split('/');
+    let key_type = match parts.next()? {
+        "Ed25519" => KeyType::Ed25519,
+        "Secp256k1" => KeyType::Secp256k1,
+        "X25519" => KeyType::X25519,
+        "P256" => KeyType::P256,
+        _ => return None,
+    };
+    let purpose = parts.next()?;
+    let public_key = match parts.next().unwrap_or("hex") {
+        "hex" => PublicKey::Hex(value.to_string()),
+        "base64" => PublicKey::Multibase(format!("m{}", value)),
+        "base58" => PublicKey::Multibase(format!("z{}", value)),
+        _ => return None,
+    };
+    let key = decode_public_key(key_type, &public_key).ok()?;
+    Some((key_type, purpose, key))
+}
+
+/// checks the signature of the message, secp256k1 signatures are over its sha256 hash.
+/// the api has no P-256 verification and X25519 keys cannot sign.
+pub fn verify_signature(
+    api: &dyn Api,
+    key_type: KeyType,
+    key: &[u8],
+    message: &[u8],
+    signature: &[u8],
+) -> bool {
+    match key_type {
+        KeyType::Ed25519 => api.ed25519_verify(message, signature, key).unwrap_or(false),
+        KeyType::Secp256k1 => api
+            .secp256k1_verify(&Sha256::digest(message), signature, key)
+            .unwrap_or(false),
+        KeyType::X25519 | KeyType::P256 => false,
+    }
+}
+
+/// recovers the secp256k1 key of the signature and compares its address with the account,
+/// addresses derive from the compressed key as in the Cosmos SDK
+pub fn signed_by_account(api: &dyn Api, account: &Addr, message: &[u8], signature: &[u8]) -> bool {
+    let canonical = match api.addr_canonicalize(account.as_str()) {
+        Ok(canonical) => canonical,
+        Err(_) => return false,
+    };
+    let hash = Sha256::digest(message);
+    (0..2).any(|recovery_param| {
+        match api.secp256k1_recover_pubkey(&hash, signature, recovery_param) {
+            Ok(key) if key.len() == 65 => {
+                let mut compressed = vec![2 + (key[64] & 1)];
+                compressed.extend_from_slice(&key[1..33]);
+                Ripemd160::digest(Sha256::digest(&compressed)).as_slice() == canonical.as_slice()
+            }
+            _ => false,
+        }
+    })
+}
+
+/// varint encoded multicodec of the public key type
+fn multicodec(key_type: KeyType) -> &'static [u8] {
+    match key_type {
+        KeyType::Ed25519 => &[0xed, 0x01],
+        KeyType::X25519 => &[0xec, 0x01],
+        KeyType::Secp256k1 => &[0xe7, 0x01],
+        KeyType::P256 => &[0x80, 0x24],
+    }
+}
+
+fn decode_jwk(key_type: KeyType, jwk: &Jwk) -> Result<Vec<u8>, ContractError> {
+    let (kty, crv) = match key_type {
+        KeyType::Ed25519 => ("OKP", "Ed25519"),
+        KeyType::X25519 => ("OKP", "X25519"),
+        KeyType::Secp256k1 => ("EC", "secp256k1"),
+        KeyType::P256 => ("EC", "P-256"),
+    };
+    if jwk.kty != kty || jwk.crv != crv {
+        return Err(invalid(&format!("JWK has to be {} {}", kty, crv)));
+    }
+
+    // every supported curve has 32 byte coordinates
+    let x = decode_base64(&jwk.x, true).ok_or_else(|| invalid("invalid JWK x"))?;
+    if x.len() != 32 {
+        return Err(invalid(&format!("invalid JWK x of {} bytes", x.len())));
+    }
+    match (kty, &jwk.y) {
+        ("OKP", _) => Ok(x),
+        (_, Some(y)) => {
+            let y = decode_base64(y, true).ok_or_else(|| invalid("invalid JWK y"))?;
+            if y.len() != 32 {
+                return Err(invalid(&format!("invalid JWK y of {} bytes", y.len())));
+            }
+            Ok([vec![4], x, y].concat())
+        }
+        (_, None) => Err(invalid("JWK y missing")),
+    }
+}
+
+fn decode_multibase(encoded: &str) -> Option<Vec<u8>> {
+    let mut chars = encoded.chars();
+    let prefix = chars.next()?;
+    let data = chars.as_str();
+    match prefix {
+        'z' => base58_decode(data),
+        'f' => decode_hex(data),
+        'm' => decode_base64(data, false),
+        'u' => decode_base64(data, true),
+        _ => None,
+    }
+}
+
+fn decode_base64(encoded: &str, url: bool) -> Option<Vec<u8>> {
+    let mut encoded = encoded.trim_end_matches('=').to_string();
+    if url {
+        encoded = encoded.replace('-', "+").replace('_', "/");
+    }
+    while encoded.len() % 4 != 0 {
+        encoded.push('=');
+    }
+    Binary::from_base64(&encoded).ok().map(|b| b.to_vec())
+}
+
+fn decode_hex(encoded: &str) -> Option<Vec<u8>> {
+    if encoded.len() % 2 != 0 || !encoded.is_ascii() {
+        return None;
+    }
+    (0..encoded.len())
+        .step_by(2)
+        .map(|i| u8::from_str_radix(&encoded[i..i + 2], 16).ok())
+        .collect()
+}
+
+fn base58_decode(encoded: &str) -> Option<Vec<u8>> {
+    let mut bytes: Vec<u8> = vec![];
+    for c in encoded.bytes() {
+        let mut carry = BASE58_ALPHABET.iter().position(|&a| a == c)? as u32;
+        for byte in bytes.iter_mut() {
+            carry += (*byte as u32) * 58;
+            *byte = carry as u8;
+            carry >>= 8;
+        }
+        while carry > 0 {
+            bytes.push(carry as u8);
+            carry >>= 8;
+        }
+    }
+    // every leading '1' is a leading zero byte
+    let zeros = encoded.bytes().take_while(|&c| c == b'1').count();
+    bytes.extend(std::iter::repeat(0).take(zeros));
+    bytes.reverse();
+    Some(bytes)
+}
+
+fn base58_encode(data: &[u8]) -> String {
+    let mut digits: Vec<u8> = vec![];
+    for &byte in data {
+        let mut carry = byte as u32;
+        for digit in digits.iter_mut() {
+            carry += (*digit as u32) << 8;
+            *digit = (carry % 58) as u8;
+            carry /= 58;
+        }
+        while carry > 0 {
+            digits.push((carry % 58) as u8);
+            carry /= 58;
+        }
+    }
+    let zeros = data.iter().take_while(|&&b| b == 0).count();
+    std::iter::repeat(b'1')
+        .take(zeros)
+        .chain(digits.iter().rev().map(|&d| BASE58_ALPHABET[d as usize]))
+        .map(char::from)
+        .collect()
+}
+
+fn invalid(reason: &str) -> ContractError {
+    ContractError::InvalidPublicKey {
+        reason: reason.to_string(),
+    }
+}
@@ -1,6 +1,10 @@
+mod auth;
 pub mod contract;
+pub mod did;
 mod error;
 pub mod helper;
+mod integration_tests;
+pub mod keys;
 pub mod msg;
 pub mod state;
 
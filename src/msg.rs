@@ -1,9 +1,32 @@
-use cosmwasm_std::{Addr, Timestamp};
+use std::fmt;
+
+use cosmwasm_std::{
+    to_binary, Addr, Binary, BlockInfo, Coin, CosmosMsg, StdResult, Timestamp, WasmMsg,
+};
 use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
 
+use crate::error::ContractError;
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq, JsonSchema)]
+pub struct InstantiateMsg {
+    /// defaults to the instantiating address
+    pub owner: Option<Addr>,
+    #[serde(default)]
+    pub fees: Vec<OperationFee>,
+    /// storage deposit locked per byte of every new attribute value
+    pub deposit_per_byte: Option<Coin>,
+    /// network of the `did:grano:<network>:<address>` DIDs, defaults to the chain id
+    pub network: Option<String>,
+}
+
 #[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq, JsonSchema)]
-pub struct InstantiateMsg {}
+pub struct MigrateMsg {
+    /// owner of deployments that predate the config
+    pub owner: Option<Addr>,
+    /// entries the backfill handles in this migration, running the migration again resumes it
+    pub limit: Option<u32>,
+}
 
 #[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq, JsonSchema)]
 #[serde(rename_all = "snake_case")]
@@ -11,20 +34,688 @@ pub enum ExecuteMsg {
     ChangeController {
         identifier: Addr,
         new_controller: Addr,
+        /// sha256 of the controller after the new one, required while a commitment is in place
+        next_hash: Option<Binary>,
+        /// hands control back once the lease ends
+        lease: Option<Lease>,
+        expected_version: Option<u64>,
+    },
+    /// commits to the sha256 hash of the next controller address
+    CommitNextController {
+        identifier: Addr,
+        next_hash: Binary,
+        expected_version: Option<u64>,
     },
     SetAttribute {
         identifier: Addr,
         name: String,  // TODO: change to byte
         value: String, // TODO: change to byte
         validity: u64,
+        expected_version: Option<u64>,
     },
     RevokeAttribute {
         identifier: Addr,
         name: String,  // TODO: change to byte
         value: String, // TODO: change to byte
+        expected_version: Option<u64>,
+    },
+    /// removes the expired and revoked values of an attribute and refunds their deposits
+    PruneAttribute {
+        identifier: Addr,
+        name: String,
+        expected_version: Option<u64>,
+    },
+    /// adds a typed public key, resolved into the verification methods of the document
+    AddVerificationMethod {
+        identifier: Addr,
+        /// fragment of the method, unique per identifier
+        id: String,
+        #[serde(rename = "type")]
+        key_type: KeyType,
+        /// defaults to the identifier
+        controller: Option<Addr>,
+        public_key: PublicKey,
+        relationships: Vec<Relationship>,
+        validity: u64,
+        expected_version: Option<u64>,
+    },
+    RemoveVerificationMethod {
+        identifier: Addr,
+        id: String,
+        expected_version: Option<u64>,
+    },
+    /// adds a service, resolved into the services of the document
+    AddService {
+        identifier: Addr,
+        service: Service,
+        expected_version: Option<u64>,
+    },
+    RemoveService {
+        identifier: Addr,
+        id: String,
+        expected_version: Option<u64>,
+    },
+    /// grants a verification relationship to a key or a delegate until validity runs out,
+    /// granting it again overwrites the validity
+    GrantRelationship {
+        identifier: Addr,
+        target: RelationshipTarget,
+        relationship: Relationship,
+        validity: u64,
+        expected_version: Option<u64>,
+    },
+    RevokeRelationship {
+        identifier: Addr,
+        target: RelationshipTarget,
+        relationship: Relationship,
+        expected_version: Option<u64>,
+    },
+    /// asserts that the identifier is also known as the other one. the link is verified once
+    /// the other one asserts it as well, or right away with its signature over alias_message.
+    LinkAlias {
+        identifier: Addr,
+        other: Addr,
+        /// signature of an authentication key of the other identifier
+        signature: Option<Binary>,
+        expected_version: Option<u64>,
+    },
+    /// withdraws the assertion of the identifier, which unverifies the link
+    UnlinkAlias {
+        identifier: Addr,
+        other: Addr,
+        expected_version: Option<u64>,
+    },
+    /// while a policy is set, controller and policy changes wait for its delay
+    SetControllerPolicy {
+        identifier: Addr,
+        policy: Option<ControllerPolicy>,
+        expected_version: Option<u64>,
+    },
+    CancelPendingChange {
+        identifier: Addr,
+        expected_version: Option<u64>,
+    },
+    ExecutePendingChange {
+        identifier: Addr,
+        expected_version: Option<u64>,
+    },
+    SetGuardians {
+        identifier: Addr,
+        guardians: Vec<Addr>,
+        threshold: u32,
+        /// seconds between reaching the threshold and executing the recovery
+        delay: u64,
+        expected_version: Option<u64>,
+    },
+    StartRecovery {
+        identifier: Addr,
+        new_controller: Addr,
+        expected_version: Option<u64>,
+    },
+    ApproveRecovery {
+        identifier: Addr,
+        expected_version: Option<u64>,
+    },
+    CancelRecovery {
+        identifier: Addr,
+        expected_version: Option<u64>,
+    },
+    ExecuteRecovery {
+        identifier: Addr,
+        expected_version: Option<u64>,
+    },
+    AddHook {
+        identifier: Addr,
+        hook: Addr,
+        on_failure: HookFailure,
+        expected_version: Option<u64>,
+    },
+    RemoveHook {
+        identifier: Addr,
+        hook: Addr,
+        expected_version: Option<u64>,
+    },
+    AddGlobalHook {
+        hook: Addr,
+        on_failure: HookFailure,
+    },
+    RemoveGlobalHook {
+        hook: Addr,
+    },
+    UpdateConfig {
+        paused: Option<bool>,
+        paused_operations: Option<Vec<Operation>>,
+        fees: Option<Vec<OperationFee>>,
+        deposit_per_byte: Option<Coin>,
+        indexed_prefixes: Option<Vec<String>>,
+        max_batch: Option<u32>,
+        max_services: Option<u32>,
+    },
+    TransferOwnership {
+        new_owner: Addr,
+    },
+    AcceptOwnership {},
+    WithdrawFees {
+        /// defaults to the owner
+        recipient: Option<Addr>,
+    },
+}
+
+/// Operations that can be paused one by one by the owner
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum Operation {
+    ChangeController,
+    SetAttribute,
+    RevokeAttribute,
+    PruneAttribute,
+    Recovery,
+    ManageHooks,
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum KeyType {
+    Ed25519,
+    Secp256k1,
+    X25519,
+    P256,
+}
+
+/// Encodings accepted for public keys
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum PublicKey {
+    /// base58btc (z), base16 (f), base64 (m) or base64url (u), with or without multicodec header
+    Multibase(String),
+    Jwk(Jwk),
+    Hex(String),
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq, JsonSchema)]
+pub struct Jwk {
+    pub kty: String,
+    pub crv: String,
+    pub x: String,
+    pub y: Option<String>,
+}
+
+/// Service of a DID document, `id` is the fragment when adding it
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq, JsonSchema)]
+pub struct Service {
+    pub id: String,
+    #[serde(rename = "type")]
+    pub service_type: String,
+    pub service_endpoint: ServiceEndpoint,
+    #[serde(default)]
+    pub accept: Vec<String>,
+    #[serde(default)]
+    pub routing_keys: Vec<String>,
+}
+
+/// Endpoint of a service, serialized as a string, an array or a map as in DID Core
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq, JsonSchema)]
+#[serde(untagged)]
+pub enum ServiceEndpoint {
+    Uri(String),
+    Set(Vec<String>),
+    Map(ServiceEndpointMap),
+}
+
+/// Endpoint object of DIDCommMessaging services
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq, JsonSchema)]
+pub struct ServiceEndpointMap {
+    pub uri: String,
+    #[serde(default)]
+    pub accept: Vec<String>,
+    #[serde(default)]
+    pub routing_keys: Vec<String>,
+}
+
+/// Verification relationships of DID Core
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum Relationship {
+    Authentication,
+    AssertionMethod,
+    KeyAgreement,
+    CapabilityInvocation,
+    CapabilityDelegation,
+}
+
+impl Relationship {
+    pub const ALL: [Relationship; 5] = [
+        Relationship::Authentication,
+        Relationship::AssertionMethod,
+        Relationship::KeyAgreement,
+        Relationship::CapabilityInvocation,
+        Relationship::CapabilityDelegation,
+    ];
+
+    /// storage key of the relationship
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Relationship::Authentication => "authentication",
+            Relationship::AssertionMethod => "assertion_method",
+            Relationship::KeyAgreement => "key_agreement",
+            Relationship::CapabilityInvocation => "capability_invocation",
+            Relationship::CapabilityDelegation => "capability_delegation",
+        }
+    }
+}
+
+/// Holder of a verification relationship
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum RelationshipTarget {
+    /// verification method by its fragment
+    Key { id: String },
+    /// account that acts for the identifier, as ERC1056 delegates do
+    Delegate { address: Addr },
+}
+
+/// Timelock for the controller changes of an identifier
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq, JsonSchema)]
+pub struct ControllerPolicy {
+    pub delay: Delay,
+    /// can cancel pending changes besides the controller
+    pub veto: Option<Addr>,
+}
+
+/// Lease of a controller, control reverts without any further transaction once it ends
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq, JsonSchema)]
+pub struct Lease {
+    pub expires: Timestamp,
+    /// takes over once the lease ends, defaults to the identifier itself
+    pub fallback: Option<Addr>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum Delay {
+    Height(u64),
+    /// seconds
+    Time(u64),
+}
+
+impl Delay {
+    /// about a year of 6 second blocks
+    pub const MAX_HEIGHT: u64 = 5_256_000;
+    /// a year
+    pub const MAX_SECONDS: u64 = 365 * 24 * 60 * 60;
+
+    pub fn validate(&self) -> Result<(), ContractError> {
+        match self {
+            Delay::Height(height) if *height > Self::MAX_HEIGHT => {
+                Err(ContractError::DelayTooLong {
+                    max: Self::MAX_HEIGHT,
+                })
+            }
+            Delay::Time(seconds) if *seconds > Self::MAX_SECONDS => {
+                Err(ContractError::DelayTooLong {
+                    max: Self::MAX_SECONDS,
+                })
+            }
+            _ => Ok(()),
+        }
+    }
+
+    pub fn after(&self, block: &BlockInfo) -> Result<Deadline, ContractError> {
+        match self {
+            Delay::Height(height) => block
+                .height
+                .checked_add(*height)
+                .map(Deadline::AtHeight)
+                .ok_or(ContractError::DelayTooLong {
+                    max: Self::MAX_HEIGHT,
+                }),
+            Delay::Time(seconds) => seconds
+                .checked_mul(1_000_000_000)
+                .and_then(|nanos| block.time.nanos().checked_add(nanos))
+                .map(|nanos| Deadline::AtTime(Timestamp::from_nanos(nanos)))
+                .ok_or(ContractError::DelayTooLong {
+                    max: Self::MAX_SECONDS,
+                }),
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum Deadline {
+    AtHeight(u64),
+    AtTime(Timestamp),
+}
+
+impl Deadline {
+    pub fn is_reached(&self, block: &BlockInfo) -> bool {
+        match self {
+            Deadline::AtHeight(height) => block.height >= *height,
+            Deadline::AtTime(time) => block.time >= *time,
+        }
+    }
+}
+
+impl fmt::Display for Deadline {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Deadline::AtHeight(height) => write!(f, "height: {}", height),
+            Deadline::AtTime(time) => write!(f, "time: {}", time.seconds()),
+        }
+    }
+}
+
+/// A change waiting for the delay of the controller policy
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum ScheduledChange {
+    ChangeController {
+        new_controller: Addr,
+        next_hash: Option<Binary>,
+        lease: Option<Lease>,
+    },
+    SetControllerPolicy {
+        policy: Option<ControllerPolicy>,
+    },
+    SetGuardians {
+        guardians: Vec<Addr>,
+        threshold: u32,
+        delay: u64,
+    },
+    /// recovery approved by the guardians
+    Recovery {
+        new_controller: Addr,
+    },
+}
+
+/// The fee that has to be sent along with an operation
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq, JsonSchema)]
+pub struct OperationFee {
+    pub operation: Operation,
+    pub fee: Coin,
+}
+
+impl ExecuteMsg {
+    /// the pausable operation of the message, owner messages are never paused
+    pub fn operation(&self) -> Option<Operation> {
+        match self {
+            ExecuteMsg::ChangeController { .. }
+            | ExecuteMsg::CommitNextController { .. }
+            | ExecuteMsg::SetControllerPolicy { .. }
+            | ExecuteMsg::CancelPendingChange { .. }
+            | ExecuteMsg::ExecutePendingChange { .. } => Some(Operation::ChangeController),
+            ExecuteMsg::SetAttribute { .. } => Some(Operation::SetAttribute),
+            ExecuteMsg::RevokeAttribute { .. } => Some(Operation::RevokeAttribute),
+            ExecuteMsg::PruneAttribute { .. } => Some(Operation::PruneAttribute),
+            ExecuteMsg::AddVerificationMethod { .. } => Some(Operation::SetAttribute),
+            ExecuteMsg::RemoveVerificationMethod { .. } => Some(Operation::RevokeAttribute),
+            ExecuteMsg::AddService { .. } => Some(Operation::SetAttribute),
+            ExecuteMsg::RemoveService { .. } => Some(Operation::RevokeAttribute),
+            ExecuteMsg::GrantRelationship { .. } => Some(Operation::SetAttribute),
+            ExecuteMsg::RevokeRelationship { .. } => Some(Operation::RevokeAttribute),
+            ExecuteMsg::LinkAlias { .. } => Some(Operation::SetAttribute),
+            ExecuteMsg::UnlinkAlias { .. } => Some(Operation::RevokeAttribute),
+            ExecuteMsg::SetGuardians { .. }
+            | ExecuteMsg::StartRecovery { .. }
+            | ExecuteMsg::ApproveRecovery { .. }
+            | ExecuteMsg::CancelRecovery { .. }
+            | ExecuteMsg::ExecuteRecovery { .. } => Some(Operation::Recovery),
+            ExecuteMsg::AddHook { .. } | ExecuteMsg::RemoveHook { .. } => {
+                Some(Operation::ManageHooks)
+            }
+            ExecuteMsg::AddGlobalHook { .. }
+            | ExecuteMsg::RemoveGlobalHook { .. }
+            | ExecuteMsg::UpdateConfig { .. }
+            | ExecuteMsg::TransferOwnership { .. }
+            | ExecuteMsg::AcceptOwnership {}
+            | ExecuteMsg::WithdrawFees { .. } => None,
+        }
+    }
+
+    /// the version of the identifier the message was built against, checked before execution
+    pub fn expected_version(&self) -> Option<u64> {
+        match self {
+            ExecuteMsg::ChangeController {
+                expected_version, ..
+            }
+            | ExecuteMsg::CommitNextController {
+                expected_version, ..
+            }
+            | ExecuteMsg::SetAttribute {
+                expected_version, ..
+            }
+            | ExecuteMsg::RevokeAttribute {
+                expected_version, ..
+            }
+            | ExecuteMsg::PruneAttribute {
+                expected_version, ..
+            }
+            | ExecuteMsg::AddVerificationMethod {
+                expected_version, ..
+            }
+            | ExecuteMsg::RemoveVerificationMethod {
+                expected_version, ..
+            }
+            | ExecuteMsg::AddService {
+                expected_version, ..
+            }
+            | ExecuteMsg::RemoveService {
+                expected_version, ..
+            }
+            | ExecuteMsg::GrantRelationship {
+                expected_version, ..
+            }
+            | ExecuteMsg::RevokeRelationship {
+                expected_version, ..
+            }
+            | ExecuteMsg::LinkAlias {
+                expected_version, ..
+            }
+            | ExecuteMsg::UnlinkAlias {
+                expected_version, ..
+            }
+            | ExecuteMsg::SetControllerPolicy {
+                expected_version, ..
+            }
+            | ExecuteMsg::CancelPendingChange {
+                expected_version, ..
+            }
+            | ExecuteMsg::ExecutePendingChange {
+                expected_version, ..
+            }
+            | ExecuteMsg::SetGuardians {
+                expected_version, ..
+            }
+            | ExecuteMsg::StartRecovery {
+                expected_version, ..
+            }
+            | ExecuteMsg::ApproveRecovery {
+                expected_version, ..
+            }
+            | ExecuteMsg::CancelRecovery {
+                expected_version, ..
+            }
+            | ExecuteMsg::ExecuteRecovery {
+                expected_version, ..
+            }
+            | ExecuteMsg::AddHook {
+                expected_version, ..
+            }
+            | ExecuteMsg::RemoveHook {
+                expected_version, ..
+            } => *expected_version,
+            ExecuteMsg::AddGlobalHook { .. }
+            | ExecuteMsg::RemoveGlobalHook { .. }
+            | ExecuteMsg::UpdateConfig { .. }
+            | ExecuteMsg::TransferOwnership { .. }
+            | ExecuteMsg::AcceptOwnership {}
+            | ExecuteMsg::WithdrawFees { .. } => None,
+        }
+    }
+
+    /// the identifier modified by the message
+    pub fn identifier(&self) -> Option<&Addr> {
+        match self {
+            ExecuteMsg::ChangeController { identifier, .. }
+            | ExecuteMsg::CommitNextController { identifier, .. }
+            | ExecuteMsg::SetAttribute { identifier, .. }
+            | ExecuteMsg::RevokeAttribute { identifier, .. }
+            | ExecuteMsg::PruneAttribute { identifier, .. }
+            | ExecuteMsg::AddVerificationMethod { identifier, .. }
+            | ExecuteMsg::RemoveVerificationMethod { identifier, .. }
+            | ExecuteMsg::AddService { identifier, .. }
+            | ExecuteMsg::RemoveService { identifier, .. }
+            | ExecuteMsg::GrantRelationship { identifier, .. }
+            | ExecuteMsg::RevokeRelationship { identifier, .. }
+            | ExecuteMsg::LinkAlias { identifier, .. }
+            | ExecuteMsg::UnlinkAlias { identifier, .. }
+            | ExecuteMsg::SetControllerPolicy { identifier, .. }
+            | ExecuteMsg::CancelPendingChange { identifier, .. }
+            | ExecuteMsg::ExecutePendingChange { identifier, .. }
+            | ExecuteMsg::SetGuardians { identifier, .. }
+            | ExecuteMsg::StartRecovery { identifier, .. }
+            | ExecuteMsg::ApproveRecovery { identifier, .. }
+            | ExecuteMsg::CancelRecovery { identifier, .. }
+            | ExecuteMsg::ExecuteRecovery { identifier, .. }
+            | ExecuteMsg::AddHook { identifier, .. }
+            | ExecuteMsg::RemoveHook { identifier, .. } => Some(identifier),
+            ExecuteMsg::AddGlobalHook { .. }
+            | ExecuteMsg::RemoveGlobalHook { .. }
+            | ExecuteMsg::UpdateConfig { .. }
+            | ExecuteMsg::TransferOwnership { .. }
+            | ExecuteMsg::AcceptOwnership {}
+            | ExecuteMsg::WithdrawFees { .. } => None,
+        }
+    }
+
+    /// identifiers and accounts of the message, which may be given as DIDs
+    pub fn dids_mut(&mut self) -> Vec<&mut Addr> {
+        match self {
+            ExecuteMsg::ChangeController {
+                identifier,
+                new_controller,
+                lease,
+                ..
+            } => {
+                let mut dids = vec![identifier, new_controller];
+                dids.extend(lease.as_mut().and_then(|lease| lease.fallback.as_mut()));
+                dids
+            }
+            ExecuteMsg::AddVerificationMethod {
+                identifier,
+                controller,
+                ..
+            } => {
+                let mut dids = vec![identifier];
+                dids.extend(controller.as_mut());
+                dids
+            }
+            ExecuteMsg::GrantRelationship {
+                identifier, target, ..
+            }
+            | ExecuteMsg::RevokeRelationship {
+                identifier, target, ..
+            } => match target {
+                RelationshipTarget::Delegate { address } => vec![identifier, address],
+                RelationshipTarget::Key { .. } => vec![identifier],
+            },
+            ExecuteMsg::LinkAlias {
+                identifier, other, ..
+            }
+            | ExecuteMsg::UnlinkAlias {
+                identifier, other, ..
+            } => vec![identifier, other],
+            ExecuteMsg::SetGuardians {
+                identifier,
+                guardians,
+                ..
+            } => {
+                let mut dids = vec![identifier];
+                dids.extend(guardians.iter_mut());
+                dids
+            }
+            ExecuteMsg::StartRecovery {
+                identifier,
+                new_controller,
+                ..
+            } => vec![identifier, new_controller],
+            ExecuteMsg::SetControllerPolicy {
+                identifier, policy, ..
+            } => {
+                let mut dids = vec![identifier];
+                dids.extend(policy.as_mut().and_then(|policy| policy.veto.as_mut()));
+                dids
+            }
+            ExecuteMsg::CommitNextController { identifier, .. }
+            | ExecuteMsg::SetAttribute { identifier, .. }
+            | ExecuteMsg::RevokeAttribute { identifier, .. }
+            | ExecuteMsg::PruneAttribute { identifier, .. }
+            | ExecuteMsg::RemoveVerificationMethod { identifier, .. }
+            | ExecuteMsg::AddService { identifier, .. }
+            | ExecuteMsg::RemoveService { identifier, .. }
+            | ExecuteMsg::CancelPendingChange { identifier, .. }
+            | ExecuteMsg::ExecutePendingChange { identifier, .. }
+            | ExecuteMsg::ApproveRecovery { identifier, .. }
+            | ExecuteMsg::CancelRecovery { identifier, .. }
+            | ExecuteMsg::ExecuteRecovery { identifier, .. }
+            | ExecuteMsg::AddHook { identifier, .. }
+            | ExecuteMsg::RemoveHook { identifier, .. } => vec![identifier],
+            ExecuteMsg::AddGlobalHook { .. }
+            | ExecuteMsg::RemoveGlobalHook { .. }
+            | ExecuteMsg::UpdateConfig { .. }
+            | ExecuteMsg::TransferOwnership { .. }
+            | ExecuteMsg::AcceptOwnership {}
+            | ExecuteMsg::WithdrawFees { .. } => vec![],
+        }
+    }
+}
+
+/// Chain governance messages, executed without the controller's signature
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum SudoMsg {
+    ForceChangeController {
+        identifier: Addr,
+        new_controller: Addr,
+        proposal_id: u64,
+    },
+    ForceDeactivate {
+        identifier: Addr,
+        proposal_id: u64,
+    },
+    ForceRevokeAttribute {
+        identifier: Addr,
+        name: String,
+        value: String,
+        proposal_id: u64,
     },
 }
 
+impl SudoMsg {
+    /// identifiers and accounts of the message, which may be given as DIDs
+    pub fn dids_mut(&mut self) -> Vec<&mut Addr> {
+        match self {
+            SudoMsg::ForceChangeController {
+                identifier,
+                new_controller,
+                ..
+            } => vec![identifier, new_controller],
+            SudoMsg::ForceDeactivate { identifier, .. }
+            | SudoMsg::ForceRevokeAttribute { identifier, .. } => vec![identifier],
+        }
+    }
+}
+
+/// What happens to the whole transaction when a hook contract fails
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum HookFailure {
+    /// the hook error is recorded by `reply` and the change is kept
+    Ignore,
+    /// the hook error reverts the change
+    Abort,
+}
+
 #[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq, JsonSchema)]
 #[serde(rename_all = "snake_case")]
 pub enum QueryMsg {
@@ -40,9 +731,171 @@ pub enum QueryMsg {
         name: String,
         value: String,
     },
+    /// runs the message as sent by the sender without writing to storage
+    CanExecute {
+        sender: Addr,
+        #[serde(default)]
+        funds: Vec<Coin>,
+        msg: Box<ExecuteMsg>,
+    },
+    /// DID document with the valid attribute values
+    Resolve {
+        identifier: Addr,
+    },
+    /// document metadata, as returned along with resolution
+    Metadata {
+        identifier: Addr,
+    },
+    /// keys and delegates currently holding the relationship
+    KeysForRelationship {
+        identifier: Addr,
+        relationship: Relationship,
+    },
+    /// finds the currently valid key of the relationship that signed the message: verification
+    /// methods, `did/pub` attributes, the controller account and delegates, in this order
+    VerifySignature {
+        identifier: Addr,
+        message: Binary,
+        signature: Binary,
+        relationship: Relationship,
+    },
+    /// dereferences `did:grano:<network>:<address>` with a fragment or a `service` and `relativeRef` query
+    Dereference {
+        did_url: String,
+    },
+    /// services of the identifier, as in its DID document
+    Services {
+        identifier: Addr,
+    },
+    /// aliases the identifier asserted, verified or not
+    Aliases {
+        identifier: Addr,
+    },
+    /// batches are limited to the configured max_batch, items come back in order
+    Controllers {
+        identifiers: Vec<Addr>,
+    },
+    Attributes {
+        keys: Vec<(Addr, String)>,
+    },
+    ValidTos {
+        keys: Vec<(Addr, String, String)>,
+    },
+    ResolveMany {
+        identifiers: Vec<Addr>,
+    },
     Changed {
         identifier: Addr,
     },
+    Version {
+        identifier: Addr,
+    },
+    Hooks {
+        identifier: Addr,
+    },
+    GlobalHooks {},
+    Config {},
+    Fees {},
+    Deposits {
+        identifier: Addr,
+    },
+    Deactivated {
+        identifier: Addr,
+    },
+    ControllerPolicy {
+        identifier: Addr,
+    },
+    PendingChange {
+        identifier: Addr,
+    },
+    Commitment {
+        identifier: Addr,
+    },
+    Lease {
+        identifier: Addr,
+    },
+    /// identifiers in the order of their address, with the block they were first seen in
+    AllIdentifiers {
+        start_after: Option<Addr>,
+        limit: Option<u32>,
+    },
+    Stats {},
+    /// identifiers with a valid attribute value under an indexed prefix, paginated by identifier
+    IdentifiersByAttribute {
+        name: String,
+        value: String,
+        start_after: Option<Addr>,
+        limit: Option<u32>,
+    },
+    /// identifiers with an explicit controller, paginated by identifier
+    IdentifiersByController {
+        controller: Addr,
+        start_after: Option<Addr>,
+        limit: Option<u32>,
+    },
+    Guardians {
+        identifier: Addr,
+    },
+    Recovery {
+        identifier: Addr,
+    },
+}
+
+impl QueryMsg {
+    /// identifiers and accounts of the query, which may be given as DIDs
+    pub fn dids_mut(&mut self) -> Vec<&mut Addr> {
+        match self {
+            QueryMsg::Controller { identifier }
+            | QueryMsg::Attribute { identifier, .. }
+            | QueryMsg::ValidTo { identifier, .. }
+            | QueryMsg::Resolve { identifier }
+            | QueryMsg::Metadata { identifier }
+            | QueryMsg::KeysForRelationship { identifier, .. }
+            | QueryMsg::VerifySignature { identifier, .. }
+            | QueryMsg::Services { identifier }
+            | QueryMsg::Aliases { identifier }
+            | QueryMsg::Changed { identifier }
+            | QueryMsg::Version { identifier }
+            | QueryMsg::Hooks { identifier }
+            | QueryMsg::Deposits { identifier }
+            | QueryMsg::Deactivated { identifier }
+            | QueryMsg::ControllerPolicy { identifier }
+            | QueryMsg::PendingChange { identifier }
+            | QueryMsg::Commitment { identifier }
+            | QueryMsg::Lease { identifier }
+            | QueryMsg::Guardians { identifier }
+            | QueryMsg::Recovery { identifier } => vec![identifier],
+            QueryMsg::CanExecute { sender, .. } => vec![sender],
+            QueryMsg::IdentifiersByController {
+                controller,
+                start_after,
+                ..
+            } => {
+                let mut dids = vec![controller];
+                dids.extend(start_after.as_mut());
+                dids
+            }
+            QueryMsg::AllIdentifiers { start_after, .. }
+            | QueryMsg::IdentifiersByAttribute { start_after, .. } => {
+                start_after.as_mut().into_iter().collect()
+            }
+            QueryMsg::Controllers { identifiers } | QueryMsg::ResolveMany { identifiers } => {
+                identifiers.iter_mut().collect()
+            }
+            QueryMsg::Attributes { keys } => {
+                keys.iter_mut().map(|(identifier, _)| identifier).collect()
+            }
+            QueryMsg::ValidTos { keys } => keys
+                .iter_mut()
+                .map(|(identifier, _, _)| identifier)
+                .collect(),
+            QueryMsg::Dereference { .. }
+            | QueryMsg::GlobalHooks {}
+            | QueryMsg::Config {}
+            | QueryMsg::Fees {}
+            | QueryMsg::Stats {} => vec![],
+        }
+    }
 }
 
 // We define a custom struct for each query response
@@ -51,6 +904,33 @@ pub struct ControllerResponse {
     pub controller: Addr,
 }
 
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq, JsonSchema)]
+pub struct IdentifierInfo {
+    pub identifier: Addr,
+    pub created_height: u64,
+    pub created_time: Timestamp,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq, JsonSchema)]
+pub struct AllIdentifiersResponse {
+    pub identifiers: Vec<IdentifierInfo>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq, JsonSchema)]
+pub struct StatsResponse {
+    pub identifiers: u64,
+    pub attributes: u64,
+    /// values neither revoked nor pruned, expired values count until they are pruned
+    pub active_values: u64,
+    pub deactivated: u64,
+    pub controller_changes: u64,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq, JsonSchema)]
+pub struct IdentifiersResponse {
+    pub identifiers: Vec<Addr>,
+}
+
 #[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq, JsonSchema)]
 pub struct AttributeResponse {
     pub values: Vec<String>,
@@ -60,7 +940,420 @@ pub struct AttributeResponse {
 pub struct ValidToResponse {
     pub valid_to: Timestamp,
 }
+
+/// Check of execute that a dry run failed
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum FailedCheck {
+    Unauthorized,
+    Deactivated,
+    Paused,
+    InvalidKeyPair,
+    InsufficientFee,
+    InsufficientDeposit,
+    Other,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq, JsonSchema)]
+pub struct CanExecuteResponse {
+    pub allowed: bool,
+    pub failed_check: Option<FailedCheck>,
+    pub error: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq, JsonSchema)]
+pub struct DocumentAttribute {
+    pub name: String,
+    pub value: String,
+    pub valid_to: Timestamp,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq, JsonSchema)]
+pub struct VerificationMethodInfo {
+    /// DID#fragment
+    pub id: String,
+    #[serde(rename = "type")]
+    pub key_type: KeyType,
+    /// DID of the controller
+    pub controller: String,
+    pub public_key_multibase: String,
+    pub relationships: Vec<RelationshipValidity>,
+    /// none for the controller key, which is valid as long as it controls the identifier
+    pub valid_to: Option<Timestamp>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq, JsonSchema)]
+pub struct RelationshipValidity {
+    pub relationship: Relationship,
+    pub valid_to: Option<Timestamp>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq, JsonSchema)]
+pub struct DelegateInfo {
+    /// DID of the delegate
+    pub delegate: String,
+    pub relationships: Vec<RelationshipValidity>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq, JsonSchema)]
+pub struct DidDocument {
+    /// DID of the identifier
+    pub id: String,
+    /// DID of the controller
+    pub controller: String,
+    /// DIDs of the verified alias links
+    pub also_known_as: Vec<String>,
+    pub verification_methods: Vec<VerificationMethodInfo>,
+    pub delegates: Vec<DelegateInfo>,
+    /// ids are DID#fragment
+    pub services: Vec<Service>,
+    pub attributes: Vec<DocumentAttribute>,
+    pub deactivated: bool,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq, JsonSchema)]
+pub struct ResolveResponse {
+    pub document: DidDocument,
+    pub did_document_metadata: DidDocumentMetadata,
+}
+
+/// Metadata of a DID document, the version id counts the changes of the identifier
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq, JsonSchema)]
+pub struct DidDocumentMetadata {
+    /// none for identifiers the registry has never seen
+    pub created: Option<Timestamp>,
+    pub created_height: Option<u64>,
+    /// none until the first change, or for identifiers unchanged since updates were recorded
+    pub updated: Option<Timestamp>,
+    pub deactivated: bool,
+    pub version_id: u64,
+    /// when the document changes next without a transaction, as values expire or a lease ends
+    pub next_update: Option<Timestamp>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq, JsonSchema)]
+pub struct MetadataResponse {
+    pub did_document_metadata: DidDocumentMetadata,
+}
+
+/// keys and delegates only list the requested relationship
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq, JsonSchema)]
+pub struct KeysForRelationshipResponse {
+    pub keys: Vec<VerificationMethodInfo>,
+    pub delegates: Vec<DelegateInfo>,
+}
+
+/// Key that verified a signature
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum SignatureMethod {
+    /// verification method, id is DID#fragment
+    Key {
+        id: String,
+    },
+    Attribute {
+        name: String,
+        value: String,
+    },
+    /// controller account, by DID
+    Controller {
+        did: String,
+    },
+    /// delegate account, by DID
+    Delegate {
+        did: String,
+    },
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq, JsonSchema)]
+pub struct VerifySignatureResponse {
+    pub method: Option<SignatureMethod>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum DereferencedContent {
+    Document(Box<DidDocument>),
+    VerificationMethod(VerificationMethodInfo),
+    Service(Service),
+    /// service endpoint with the relative reference and fragment applied
+    Url(String),
+}
+
+/// Reason a DID URL could not be dereferenced
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum DereferenceError {
+    InvalidDidUrl { reason: String },
+    UnsupportedPath { path: String },
+    UnsupportedParameter { name: String },
+    UnknownFragment { fragment: String },
+    UnknownService { service: String },
+}
+
+/// either the content or the error is set
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq, JsonSchema)]
+pub struct DereferenceResponse {
+    pub content: Option<DereferencedContent>,
+    pub error: Option<DereferenceError>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq, JsonSchema)]
+pub struct ServicesResponse {
+    pub services: Vec<Service>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq, JsonSchema)]
+pub struct AliasInfo {
+    /// DID of the other identifier
+    pub alias: String,
+    /// whether the other identifier asserted the link as well
+    pub verified: bool,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq, JsonSchema)]
+pub struct AliasesResponse {
+    pub aliases: Vec<AliasInfo>,
+    /// nonce of the next alias signature of the identifier
+    pub nonce: u64,
+}
+
+/// found is false for identifiers the registry has never seen, the controller is the default one
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq, JsonSchema)]
+pub struct ControllerItem {
+    pub identifier: Addr,
+    pub controller: Addr,
+    pub found: bool,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq, JsonSchema)]
+pub struct ControllersResponse {
+    pub controllers: Vec<ControllerItem>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq, JsonSchema)]
+pub struct AttributeItem {
+    pub identifier: Addr,
+    pub name: String,
+    pub values: Vec<String>,
+    pub found: bool,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq, JsonSchema)]
+pub struct AttributesResponse {
+    pub attributes: Vec<AttributeItem>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq, JsonSchema)]
+pub struct ValidToItem {
+    pub identifier: Addr,
+    pub name: String,
+    pub value: String,
+    pub valid_to: Timestamp,
+    pub found: bool,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq, JsonSchema)]
+pub struct ValidTosResponse {
+    pub valid_tos: Vec<ValidToItem>,
+}
+
+/// found is false for identifiers the registry has never seen, the document is the default one
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq, JsonSchema)]
+pub struct DocumentItem {
+    pub identifier: Addr,
+    pub document: DidDocument,
+    pub did_document_metadata: DidDocumentMetadata,
+    pub found: bool,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq, JsonSchema)]
+pub struct ResolveManyResponse {
+    pub documents: Vec<DocumentItem>,
+}
 #[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq, JsonSchema)]
 pub struct ChangedResponse {
     pub block: u64,
 }
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq, JsonSchema)]
+pub struct VersionResponse {
+    /// bumped with every change of the identifier
+    pub version: u64,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq, JsonSchema)]
+pub struct DeactivatedResponse {
+    pub deactivated: bool,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq, JsonSchema)]
+pub struct ControllerPolicyResponse {
+    pub policy: Option<ControllerPolicy>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq, JsonSchema)]
+pub struct PendingChangeInfo {
+    pub change: ScheduledChange,
+    pub executable_at: Deadline,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq, JsonSchema)]
+pub struct PendingChangeResponse {
+    pub pending: Option<PendingChangeInfo>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq, JsonSchema)]
+pub struct CommitmentResponse {
+    pub next_hash: Option<Binary>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq, JsonSchema)]
+pub struct LeaseResponse {
+    /// the lease stays in place after it ends, until the next controller change
+    pub lease: Option<Lease>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq, JsonSchema)]
+pub struct GuardiansResponse {
+    pub guardians: Vec<Addr>,
+    pub threshold: u32,
+    pub delay: u64,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq, JsonSchema)]
+pub struct RecoveryInfo {
+    pub new_controller: Addr,
+    pub approvals: Vec<Addr>,
+    pub executable_at: Option<Timestamp>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq, JsonSchema)]
+pub struct RecoveryResponse {
+    pub recovery: Option<RecoveryInfo>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq, JsonSchema)]
+pub struct ConfigResponse {
+    pub owner: Addr,
+    pub pending_owner: Option<Addr>,
+    pub paused: bool,
+    pub paused_operations: Vec<Operation>,
+    pub indexed_prefixes: Vec<String>,
+    pub max_batch: u32,
+    pub max_services: u32,
+    pub network: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq, JsonSchema)]
+pub struct FeesResponse {
+    pub fees: Vec<OperationFee>,
+    /// fees collected since the last withdrawal
+    pub collected: Vec<Coin>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq, JsonSchema)]
+pub struct DepositResponse {
+    pub name: String,
+    pub value: String,
+    pub amount: Coin,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq, JsonSchema)]
+pub struct DepositsResponse {
+    pub deposits: Vec<DepositResponse>,
+    pub total: Vec<Coin>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq, JsonSchema)]
+pub struct HookResponse {
+    pub hook: Addr,
+    pub on_failure: HookFailure,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq, JsonSchema)]
+pub struct HooksResponse {
+    pub hooks: Vec<HookResponse>,
+}
+
+/// The change that triggered a `DidChangedHook`
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum DidChange {
+    ChangeController {
+        controller: Addr,
+    },
+    SetAttribute {
+        name: String,
+        value: String,
+        valid_to: Timestamp,
+    },
+    RevokeAttribute {
+        name: String,
+        value: String,
+    },
+    PruneAttribute {
+        name: String,
+        values: Vec<String>,
+    },
+    AddVerificationMethod {
+        id: String,
+    },
+    RemoveVerificationMethod {
+        id: String,
+    },
+    AddService {
+        id: String,
+    },
+    RemoveService {
+        id: String,
+    },
+    GrantRelationship {
+        target: RelationshipTarget,
+        relationship: Relationship,
+    },
+    RevokeRelationship {
+        target: RelationshipTarget,
+        relationship: Relationship,
+    },
+    LinkAlias {
+        other: Addr,
+    },
+    UnlinkAlias {
+        other: Addr,
+    },
+    Deactivate {},
+}
+
+/// DidChangedHookMsg is sent to every registered hook contract after a DID changes
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq, JsonSchema)]
+pub struct DidChangedHookMsg {
+    pub identifier: Addr,
+    pub change: DidChange,
+}
+
+impl DidChangedHookMsg {
+    /// serializes the message
+    pub fn into_binary(self) -> StdResult<Binary> {
+        let msg = DidChangedExecuteMsg::DidChangedHook(self);
+        to_binary(&msg)
+    }
+
+    /// creates a cosmos_msg sending this struct to the named contract
+    pub fn into_cosmos_msg<T: Into<String>>(self, contract_addr: T) -> StdResult<CosmosMsg> {
+        let msg = self.into_binary()?;
+        let execute = WasmMsg::Execute {
+            contract_addr: contract_addr.into(),
+            msg,
+            funds: vec![],
+        };
+        Ok(execute.into())
+    }
+}
+
+/// The execute message hook contracts have to accept
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum DidChangedExecuteMsg {
+    DidChangedHook(DidChangedHookMsg),
+}
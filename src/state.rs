@@ -1,15 +1,212 @@
-use cosmwasm_std::{Addr, Timestamp};
-use cw_storage_plus::Map;
+use cosmwasm_std::{Addr, Binary, Coin, Timestamp, Uint128};
+use cw_storage_plus::{Index, IndexList, IndexedMap, Item, Map, MultiIndex};
 use serde::{Deserialize, Serialize};
 
+use crate::msg::{
+    ControllerPolicy, Deadline, HookFailure, KeyType, Lease, Operation, OperationFee,
+    ScheduledChange, Service,
+};
+
 #[derive(Serialize, Deserialize, PartialEq, Eq, Debug, Clone)]
 pub struct Attribute {
     pub values: Vec<String>,
 }
 
-pub const CONTROLLERS: Map<&Addr, Addr> = Map::new("controller");
+pub struct ControllerIndexes<'a> {
+    pub controller: MultiIndex<'a, Addr, Addr, &'a Addr>,
+}
+
+impl<'a> IndexList<Addr> for ControllerIndexes<'a> {
+    fn get_indexes(&'_ self) -> Box<dyn Iterator<Item = &'_ dyn Index<Addr>> + '_> {
+        let v: Vec<&dyn Index<Addr>> = vec![&self.controller];
+        Box::new(v.into_iter())
+    }
+}
+
+/// controllers by identifier, indexed by controller
+pub fn controllers<'a>() -> IndexedMap<'a, &'a Addr, Addr, ControllerIndexes<'a>> {
+    let indexes = ControllerIndexes {
+        controller: MultiIndex::new(
+            |_pk, controller: &Addr| controller.clone(),
+            "controller",
+            "controller__controller",
+        ),
+    };
+    IndexedMap::new("controller", indexes)
+}
+
+/// step of the backfill after a migration from a version before the indexes, removed once done
+#[derive(Serialize, Deserialize, PartialEq, Eq, Debug, Clone)]
+pub enum Backfill {
+    /// saving the controllers again to index them
+    Controllers { start_after: Option<Addr> },
+    /// registering the identifiers changed before the enumeration
+    Identifiers { start_after: Option<Addr> },
+    /// counting the attributes and their active values
+    Attributes {
+        start_after: Option<(Addr, String)>,
+        attributes: u64,
+        active_values: u64,
+    },
+    /// counting the deactivated identifiers
+    Deactivated {
+        start_after: Option<Addr>,
+        deactivated: u64,
+    },
+}
+
+pub const BACKFILL: Item<Backfill> = Item::new("backfill");
+
 pub const CHANGED: Map<&Addr, u64> = Map::new("changed");
+pub const VERSIONS: Map<&Addr, u64> = Map::new("versions");
+/// time of the last change, identifiers unchanged since it was recorded have none
+pub const UPDATED: Map<&Addr, Timestamp> = Map::new("updated");
+/// nonce of the signatures an identifier gives for alias links, bumped with every use
 pub const NONCE: Map<&Addr, u64> = Map::new("nonce");
+/// identifiers deactivated by governance, they cannot be modified anymore
+pub const DEACTIVATED: Map<&Addr, bool> = Map::new("deactivated");
+
+#[derive(Serialize, Deserialize, PartialEq, Eq, Debug, Clone)]
+pub struct Registration {
+    pub height: u64,
+    pub time: Timestamp,
+}
+
+/// identifiers by the block they were first seen in
+pub const IDENTIFIERS: Map<&Addr, Registration> = Map::new("identifiers");
+
+#[derive(Serialize, Deserialize, PartialEq, Eq, Debug, Clone, Default)]
+pub struct Stats {
+    pub identifiers: u64,
+    pub attributes: u64,
+    /// values neither revoked nor pruned, expired values count until they are pruned
+    pub active_values: u64,
+    pub deactivated: u64,
+    pub controller_changes: u64,
+}
+
+pub const STATS: Item<Stats> = Item::new("stats");
 
 pub const ATTRIBUTES: Map<(&Addr, &str), Attribute> = Map::new("attribute");
 pub const VALIDITIES: Map<(&Addr, &str, &str), Timestamp> = Map::new("validities");
+/// storage deposits locked per attribute value, values stored before deposits have none
+pub const DEPOSITS: Map<(&Addr, &str, &str), Coin> = Map::new("deposits");
+/// identifiers by attribute name and value, for the names with an indexed prefix
+pub const ATTRIBUTE_INDEX: Map<(&str, &str, &Addr), bool> = Map::new("attribute_index");
+
+#[derive(Serialize, Deserialize, PartialEq, Eq, Debug, Clone)]
+pub struct VerificationMethod {
+    pub key_type: KeyType,
+    pub controller: Addr,
+    /// decoded key, without multicodec header
+    pub public_key: Binary,
+    pub valid_to: Timestamp,
+}
+
+pub const VERIFICATION_METHODS: Map<(&Addr, &str), VerificationMethod> =
+    Map::new("verification_methods");
+/// fragment of the default verification method from the account key of the controller
+pub const CONTROLLER_FRAGMENT: &str = "controller";
+/// valid to of the relationships of keys, by identifier, relationship and key fragment
+pub const KEY_RELATIONSHIPS: Map<(&Addr, &str, &str), Timestamp> = Map::new("key_relationships");
+/// valid to of the relationships of delegates, by identifier, relationship and delegate
+pub const DELEGATES: Map<(&Addr, &str, &Addr), Timestamp> = Map::new("delegates");
+
+/// services by identifier and fragment, `id` holds the fragment
+pub const SERVICES: Map<(&Addr, &str), Service> = Map::new("services");
+
+/// time the identifier asserted to be also known as the other one,
+/// the link is verified once both sides assert it
+pub const ALIASES: Map<(&Addr, &Addr), Timestamp> = Map::new("aliases");
+
+#[derive(Serialize, Deserialize, PartialEq, Eq, Debug, Clone)]
+pub struct Config {
+    pub owner: Addr,
+    pub pending_owner: Option<Addr>,
+    pub paused: bool,
+    pub paused_operations: Vec<Operation>,
+    pub fees: Vec<OperationFee>,
+    pub deposit_per_byte: Option<Coin>,
+    /// attribute name prefixes whose values are indexed
+    #[serde(default)]
+    pub indexed_prefixes: Vec<String>,
+    /// maximum number of items in a batch query, defaults to DEFAULT_MAX_BATCH
+    pub max_batch: Option<u32>,
+    /// maximum number of services of an identifier, defaults to DEFAULT_MAX_SERVICES
+    pub max_services: Option<u32>,
+    /// network of the DIDs, none for deployments before DIDs carried one
+    #[serde(default)]
+    pub network: Option<String>,
+}
+
+pub const DEFAULT_MAX_BATCH: u32 = 50;
+pub const DEFAULT_MAX_SERVICES: u32 = 20;
+/// maximum number of endpoints in a set or map endpoint
+pub const MAX_SERVICE_ENDPOINTS: usize = 10;
+pub const MAX_URL_LENGTH: usize = 256;
+/// maximum number of hooks of an identifier
+pub const MAX_HOOKS: usize = 10;
+
+pub const CONFIG: Item<Config> = Item::new("config");
+/// fees collected since the last withdrawal, by denom
+pub const COLLECTED_FEES: Map<&str, Uint128> = Map::new("collected_fees");
+pub const HOOKS: Map<(&Addr, &Addr), HookFailure> = Map::new("hooks");
+pub const GLOBAL_HOOKS: Map<&Addr, HookFailure> = Map::new("global_hooks");
+
+#[derive(Serialize, Deserialize, PartialEq, Eq, Debug, Clone)]
+pub struct PendingChange {
+    pub change: ScheduledChange,
+    pub executable_at: Deadline,
+}
+
+pub const CONTROLLER_POLICIES: Map<&Addr, ControllerPolicy> = Map::new("controller_policies");
+pub const PENDING_CHANGES: Map<&Addr, PendingChange> = Map::new("pending_changes");
+/// sha256 of the next controller address, pre-rotation commitment
+pub const COMMITMENTS: Map<&Addr, Binary> = Map::new("commitments");
+pub struct LeaseIndexes<'a> {
+    pub fallback: MultiIndex<'a, Addr, Lease, &'a Addr>,
+}
+
+impl<'a> IndexList<Lease> for LeaseIndexes<'a> {
+    fn get_indexes(&'_ self) -> Box<dyn Iterator<Item = &'_ dyn Index<Lease>> + '_> {
+        let v: Vec<&dyn Index<Lease>> = vec![&self.fallback];
+        Box::new(v.into_iter())
+    }
+}
+
+/// lease of the current controller, evaluated against the block time, indexed by the fallback
+/// that takes over once it ends
+pub fn leases<'a>() -> IndexedMap<'a, &'a Addr, Lease, LeaseIndexes<'a>> {
+    let indexes = LeaseIndexes {
+        fallback: MultiIndex::new(
+            |pk, lease: &Lease| {
+                lease
+                    .fallback
+                    .clone()
+                    .unwrap_or_else(|| Addr::unchecked(String::from_utf8_lossy(pk)))
+            },
+            "leases",
+            "leases__fallback",
+        ),
+    };
+    IndexedMap::new("leases", indexes)
+}
+
+#[derive(Serialize, Deserialize, PartialEq, Eq, Debug, Clone)]
+pub struct GuardianSet {
+    pub guardians: Vec<Addr>,
+    pub threshold: u32,
+    /// seconds between reaching the threshold and executing the recovery
+    pub delay: u64,
+}
+
+#[derive(Serialize, Deserialize, PartialEq, Eq, Debug, Clone)]
+pub struct Recovery {
+    pub new_controller: Addr,
+    pub approvals: Vec<Addr>,
+    /// set once the threshold of approvals is reached
+    pub executable_at: Option<Timestamp>,
+}
+
+pub const GUARDIANS: Map<&Addr, GuardianSet> = Map::new("guardians");
+pub const RECOVERIES: Map<&Addr, Recovery> = Map::new("recoveries");